@@ -55,6 +55,9 @@ window.CompletionTrackerPlugin = {
             search(query, onSuccess, onFail) {
                 post("/api/search", query, onSuccess, onFail);
             },
+            suggest(query, onSuccess, onFail) {
+                post("/api/search/suggest", query, onSuccess, onFail);
+            },
 
             readUniverseTags(reader, onSuccess, onFail) {
                 post("/api/universeTags/read", reader, onSuccess, onFail);
@@ -65,6 +68,56 @@ window.CompletionTrackerPlugin = {
             createUniverseTag(creator, onSuccess, onFail) {
                 post("/api/universeTag/create", creator, onSuccess, onFail);
             },
+
+            createSmartList(trackerName, creator, onSuccess, onFail) {
+                post(`/api/tracker/${encodeURIComponent(trackerName)}/smartList/create`, creator,
+                    onSuccess, onFail);
+            },
+            readSmartLists(trackerName, onSuccess, onFail) {
+                post(`/api/tracker/${encodeURIComponent(trackerName)}/smartLists/read`, {},
+                    onSuccess, onFail);
+            },
+            runSmartList(trackerName, selector, onSuccess, onFail) {
+                post(`/api/tracker/${encodeURIComponent(trackerName)}/smartList/run`, selector,
+                    onSuccess, onFail);
+            },
+            deleteSmartList(trackerName, selector, onSuccess, onFail) {
+                post(`/api/tracker/${encodeURIComponent(trackerName)}/smartList/delete`, selector,
+                    onSuccess, onFail);
+            },
+
+            importMyAnimeList(trackerName, xml, onSuccess, onFail) {
+                post(`/api/tracker/${encodeURIComponent(trackerName)}/import/myAnimeList`, { xml },
+                    onSuccess, onFail);
+            },
+            importCsv(trackerName, csvImport, onSuccess, onFail) {
+                post(`/api/tracker/${encodeURIComponent(trackerName)}/import/csv`, csvImport,
+                    onSuccess, onFail);
+            },
+            checkLinks(options, onSuccess, onFail) {
+                post("/api/links/check", options, onSuccess, onFail);
+            },
+            readBrokenLinks(onSuccess, onFail) {
+                post("/api/links/broken/read", {}, onSuccess, onFail);
+            },
+            readLinkTypes(onSuccess, onFail) {
+                post("/api/linkTypes/read", {}, onSuccess, onFail);
+            },
+            findSourcesByExternalId(finder, onSuccess, onFail) {
+                post("/api/sources/findByExternalId", finder, onSuccess, onFail);
+            },
+            createSource(sourceCreator, onSuccess, onFail) {
+                post("/api/source/create", sourceCreator, onSuccess, onFail);
+            },
+            importWikipedia(wikipediaImport, onSuccess, onFail) {
+                post("/api/source/import/wikipedia", wikipediaImport, onSuccess, onFail);
+            },
+            importNarou(narouImport, onSuccess, onFail) {
+                post("/api/source/import/narou", narouImport, onSuccess, onFail);
+            },
+            exportSite(siteExport, onSuccess, onFail) {
+                post("/api/site/export", siteExport, onSuccess, onFail);
+            },
         };
 
         Vue.prototype.$searchResultSorter = (result1, result2) => {
@@ -9,6 +9,9 @@ fn main() {
 
     fs::copy("assets/main.html", out_dir.join("main.html"))
         .expect("Failed to copy the main html");
+
+    fs::write(out_dir.join("site.css"), all_js::bundle_css())
+        .expect("Failed to write the site css file");
 }
 
 /// This module will handle the building of Vue files and making them into something useable
@@ -57,6 +60,17 @@ mod all_js {
         search_data[start_index..end_index].trim()
     }
 
+    /// All of the CSS files together, for the pages that don't use the JS bundle
+    pub fn bundle_css() -> String {
+        let mut bundled_css = String::new();
+        for css_file in super::read_dir(CSS_DIR, "css", false) {
+            bundled_css.push_str(&fs::read_to_string(&css_file)
+                .expect("Failed to read a css file"));
+            bundled_css.push('\n');
+        }
+        bundled_css
+    }
+
     fn create_css_function(css_data: &str) -> String {
         format!(r#"(function() {{
     "use strict";
@@ -0,0 +1,323 @@
+//! A client for the server's HTTP API, for scripts that would otherwise write the JSON by hand.
+//! Every route has a method here that takes and gives back the same types as the library.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{json};
+use url::{Url};
+
+use completion_tracker_lib::{
+    error::{Error},
+    exports::{
+        MediaType,
+        calendar::{CalendarFilter},
+        csv::{CsvExportOptions},
+        graph::{GraphFormat, GraphOptions},
+        site::{SiteReport},
+    },
+    imports::{
+        ImportReport,
+        csv::{CsvColumnMapping, CsvPreset},
+        narou::{NarouImport},
+        wikipedia::{WikipediaImport},
+    },
+    link_check::{ItemBrokenLinks, LinkCheckOptions, LinkCheckSummary},
+    simple_enum::{SimpleEnum},
+    sources::{
+        api::{
+            search::{SearchQuery, SearchResults, SuggestQuery, Suggestion},
+            source::{SourceCreator},
+            universe_tag::{UniverseTagCreator, UniverseTagReadResult, UniverseTagReader},
+        },
+        source_types::{Lang, link_types::{LinkTypeDefinition}},
+    },
+    tracking::smart_list::{SmartList, SmartListCreator, SmartListSelector},
+};
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The server sent back an error, with the same kind that the library gave it
+    Server {
+        status: u16,
+        error: Error,
+    },
+    /// The server couldn't be reached, or the connection broke
+    Transport(String),
+    /// The response wasn't what the route sends back
+    BadResponse(String),
+}
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Server { status, error } => write!(f, "{} ({}): {}", error.code(), status, error),
+            Self::Transport(message) => write!(f, "Failed to reach the server: {}", message),
+            Self::BadResponse(message) => write!(f, "The response couldn't be read: {}", message),
+        }
+    }
+}
+impl std::error::Error for ClientError {}
+
+/// What every route sends back. The code is only there when it failed.
+#[derive(Deserialize)]
+struct APIResponse {
+    success: bool,
+    #[serde(default)]
+    code: Option<String>,
+    data: serde_json::Value,
+}
+
+pub struct Client {
+    agent: ureq::Agent,
+    base_url: Url,
+}
+impl Client {
+    /// The base URL is where the server is running, like "http://localhost:8080"
+    pub fn new(base_url: &str) -> Result<Client, ClientError> {
+        let base_url = Url::parse(base_url)
+            .map_err(|e| ClientError::Transport(format!("{}: {}", base_url, e)))?;
+        if base_url.cannot_be_a_base() {
+            return Err(ClientError::Transport(format!("{} can't be a base URL", base_url)));
+        }
+        Ok(Client {
+            agent: ureq::AgentBuilder::new()
+                .user_agent(concat!("CompletionTrackerClient/", env!("CARGO_PKG_VERSION")))
+                .build(),
+            base_url,
+        })
+    }
+
+    /// Every segment gets escaped, so tracker names can have spaces and Japanese in them
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut().expect("The base URL was checked in new()")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn post<T: DeserializeOwned>(&self, segments: &[&str], body: &impl Serialize)
+    -> Result<T, ClientError> {
+        let body = serde_json::to_string(body)
+            .map_err(|e| ClientError::BadResponse(e.to_string()))?;
+        let (status, text) = read_response(self.agent.request_url("POST", &self.url(segments))
+            .set("Content-Type", "application/json")
+            .send_string(&body))?;
+        let response = parse_api_response(status, &text)?;
+        serde_json::from_value(response)
+            .map_err(|e| ClientError::BadResponse(e.to_string()))
+    }
+
+    /// The body of a file route. The errors still come back as JSON.
+    fn get_file(&self, segments: &[&str], query: &[(&str, String)]) -> Result<String, ClientError> {
+        let mut url = self.url(segments);
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        let (status, text) = read_response(self.agent.request_url("GET", &url).call())?;
+        if (200..300).contains(&status) {
+            Ok(text)
+        } else {
+            parse_api_response(status, &text).map(|_| text)
+        }
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> Result<SearchResults, ClientError> {
+        self.post(&["api", "search"], query)
+    }
+
+    pub fn suggest(&self, query: &SuggestQuery) -> Result<Vec<Suggestion>, ClientError> {
+        self.post(&["api", "search", "suggest"], query)
+    }
+
+    /// The OpenAPI description of all of these routes
+    pub fn openapi(&self) -> Result<String, ClientError> {
+        self.get_file(&["api", "openapi.json"], &[])
+    }
+
+    pub fn calendar(&self, filter: &CalendarFilter) -> Result<String, ClientError> {
+        let mut query = vec![("lang", filter.lang.as_str().to_string())];
+        if let Some(universe_tag_id) = filter.universe_tag_id {
+            query.push(("universeTag", universe_tag_id.to_string()));
+        }
+        if let Some(source_type) = filter.source_type {
+            query.push(("sourceType", source_type.as_str().to_string()));
+        }
+        self.get_file(&["api", "calendar.ics"], &query)
+    }
+
+    pub fn graph(&self, options: &GraphOptions) -> Result<String, ClientError> {
+        let file_name = match options.format {
+            GraphFormat::Dot => "graph.dot",
+            GraphFormat::GraphML => "graph.graphml",
+        };
+        let subgraphs = options.subgraphs.iter().map(|subgraph| subgraph.as_str())
+            .collect::<Vec<_>>().join(",");
+        self.get_file(&["api", file_name], &[
+            ("subgraphs", subgraphs),
+            ("lang", options.lang.as_str().to_string()),
+        ])
+    }
+
+    /// Writes the static site into the server's tracking folder
+    pub fn export_site(&self, lang: Lang) -> Result<SiteReport, ClientError> {
+        self.post(&["api", "site", "export"], &json!({ "lang": lang }))
+    }
+
+    pub fn check_links(&self, options: &LinkCheckOptions) -> Result<LinkCheckSummary, ClientError> {
+        self.post(&["api", "links", "check"], options)
+    }
+
+    pub fn read_broken_links(&self) -> Result<Vec<ItemBrokenLinks>, ClientError> {
+        self.post(&["api", "links", "broken", "read"], &json!({}))
+    }
+
+    pub fn read_link_types(&self) -> Result<Vec<LinkTypeDefinition>, ClientError> {
+        self.post(&["api", "linkTypes", "read"], &json!({}))
+    }
+
+    pub fn find_sources_by_external_id(&self, link_type: &str, external_id: &str)
+    -> Result<Vec<u64>, ClientError> {
+        self.post(&["api", "sources", "findByExternalId"], &json!({
+            "link_type": link_type,
+            "external_id": external_id,
+        }))
+    }
+
+    pub fn create_source(&self, creator: &SourceCreator) -> Result<u64, ClientError> {
+        self.post(&["api", "source", "create"], creator)
+    }
+
+    /// The page is a saved Wikipedia page, as wikitext or HTML
+    pub fn import_wikipedia(&self, page: &str, lang: Lang, url: Option<&str>)
+    -> Result<WikipediaImport, ClientError> {
+        self.post(&["api", "source", "import", "wikipedia"], &json!({
+            "page": page,
+            "lang": lang,
+            "url": url,
+        }))
+    }
+
+    /// The data is the API's JSON for the novel, or the HTML of its table of contents
+    pub fn import_narou(&self, url: &str, data: &str) -> Result<NarouImport, ClientError> {
+        self.post(&["api", "source", "import", "narou"], &json!({
+            "url": url,
+            "data": data,
+        }))
+    }
+
+    pub fn create_universe_tag(&self, creator: &UniverseTagCreator) -> Result<u64, ClientError> {
+        self.post(&["api", "universeTag", "create"], creator)
+    }
+
+    pub fn read_universe_tags(&self, reader: &UniverseTagReader)
+    -> Result<Vec<UniverseTagReadResult>, ClientError> {
+        self.post(&["api", "universeTags", "read"], reader)
+    }
+
+    pub fn read_root_universe_tag_ids(&self) -> Result<Vec<u64>, ClientError> {
+        self.post(&["api", "universeTags", "readRoot"], &json!({}))
+    }
+
+    pub fn create_smart_list(&self, tracker_name: &str, creator: &SmartListCreator)
+    -> Result<u64, ClientError> {
+        self.post(&["api", "tracker", tracker_name, "smartList", "create"], creator)
+    }
+
+    pub fn read_smart_lists(&self, tracker_name: &str) -> Result<Vec<SmartList>, ClientError> {
+        self.post(&["api", "tracker", tracker_name, "smartLists", "read"], &json!({}))
+    }
+
+    pub fn run_smart_list(&self, tracker_name: &str, selector: &SmartListSelector)
+    -> Result<SearchResults, ClientError> {
+        self.post(&["api", "tracker", tracker_name, "smartList", "run"], selector)
+    }
+
+    pub fn delete_smart_list(&self, tracker_name: &str, selector: &SmartListSelector)
+    -> Result<(), ClientError> {
+        self.post(&["api", "tracker", tracker_name, "smartList", "delete"], selector)
+    }
+
+    /// The XML is the contents of animelist.xml or mangalist.xml
+    pub fn import_my_anime_list(&self, tracker_name: &str, xml: &str)
+    -> Result<ImportReport, ClientError> {
+        self.post(&["api", "tracker", tracker_name, "import", "myAnimeList"], &json!({
+            "xml": xml,
+        }))
+    }
+
+    /// Either a preset or a mapping is needed. The mapping wins if both are given.
+    pub fn import_csv(&self, tracker_name: &str, csv: &str, preset: Option<CsvPreset>,
+    mapping: Option<&CsvColumnMapping>) -> Result<ImportReport, ClientError> {
+        self.post(&["api", "tracker", tracker_name, "import", "csv"], &json!({
+            "csv": csv,
+            "preset": preset,
+            "mapping": mapping,
+        }))
+    }
+
+    pub fn export_csv(&self, tracker_name: &str, options: &CsvExportOptions)
+    -> Result<String, ClientError> {
+        self.get_file(&["api", "tracker", tracker_name, "export.csv"], &[
+            ("lang", options.lang.as_str().to_string()),
+            ("bom", options.bom.to_string()),
+        ])
+    }
+
+    pub fn feed(&self, tracker_name: &str, lang: Lang) -> Result<String, ClientError> {
+        self.get_file(&["api", "tracker", tracker_name, "feed.atom"],
+            &[("lang", lang.as_str().to_string())])
+    }
+
+    /// The JSON to import into AniList
+    pub fn export_anilist(&self, tracker_name: &str, media_type: MediaType)
+    -> Result<String, ClientError> {
+        self.get_file(&["api", "tracker", tracker_name, "export", "aniList",
+            media_type_segment(media_type)], &[])
+    }
+
+    /// The XML to import into MyAnimeList
+    pub fn export_my_anime_list(&self, tracker_name: &str, media_type: MediaType)
+    -> Result<String, ClientError> {
+        self.get_file(&["api", "tracker", tracker_name, "export", "myAnimeList",
+            media_type_segment(media_type)], &[])
+    }
+}
+
+fn media_type_segment(media_type: MediaType) -> &'static str {
+    match media_type {
+        MediaType::Anime => "anime",
+        MediaType::Manga => "manga",
+    }
+}
+
+/// The status and body, whether or not the status was an error
+fn read_response(result: Result<ureq::Response, ureq::Error>) -> Result<(u16, String), ClientError> {
+    let response = match result {
+        Ok(response) => response,
+        Err(ureq::Error::Status(_, response)) => response,
+        Err(ureq::Error::Transport(e)) => return Err(ClientError::Transport(e.to_string())),
+    };
+    let status = response.status();
+    let text = response.into_string().map_err(|e| ClientError::Transport(e.to_string()))?;
+    Ok( (status, text) )
+}
+
+/// The data of an APIResult, or the error that was sent instead
+fn parse_api_response(status: u16, text: &str) -> Result<serde_json::Value, ClientError> {
+    let response: APIResponse = serde_json::from_str(text)
+        .map_err(|_| ClientError::BadResponse(format!("{} with the body: {}", status, text)))?;
+    if response.success {
+        return Ok(response.data);
+    }
+
+    let message = match response.data {
+        serde_json::Value::String(message) => message,
+        data => data.to_string(),
+    };
+    let code = response.code.unwrap_or_default();
+    match Error::from_code(&code, message) {
+        Some(error) => Err(ClientError::Server { status, error }),
+        None => Err(ClientError::BadResponse(format!("{} isn't an error code", code))),
+    }
+}
@@ -0,0 +1,223 @@
+//! Every test starts its own server on any free port, with a new sources DB and tracker.
+
+use std::{
+    collections::{BTreeMap},
+    fs,
+    path::{PathBuf},
+    sync::mpsc,
+    thread,
+    time::{Duration},
+};
+
+use completion_tracker::{App};
+use completion_tracker_client::{Client, ClientError};
+use completion_tracker_lib::{
+    error::{Error},
+    exports::{
+        MediaType,
+        calendar::{CalendarFilter},
+        csv::{CsvExportOptions},
+        graph::{GraphFormat, GraphOptions},
+    },
+    imports::csv::{CsvPreset},
+    sources::{
+        api::{
+            search::{ItemType, SearchQuery, SuggestQuery},
+            source::{SourceCreator},
+            universe_tag::{UniverseTagCreator, UniverseTagReader},
+        },
+        source_types::{Lang, LangMap, SourceType, link_types},
+    },
+    tracking::{
+        Tracker,
+        smart_list::{SmartListCreator, SmartListSelector},
+    },
+};
+
+const TRACKER: &str = "Default Tracker";
+
+fn test_folder(test_name: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("completion_tracker_client_{}_{}", std::process::id(), test_name))
+}
+
+/// Starts the server with a tracker in its own folder, giving back a client for it
+fn start_server(test_name: &str) -> Client {
+    let folder = test_folder(test_name);
+    let _ = fs::remove_dir_all(&folder);
+    let trackers = Tracker::new_from_info(&folder, &[]).unwrap();
+    let sources_db = completion_tracker_lib::init_source_db().unwrap();
+    let tracker_dbs = completion_tracker_lib::init_tracker_dbs(&trackers).unwrap();
+    let app = App::new(sources_db, tracker_dbs, folder.join("site"));
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let server = rouille::Server::new("127.0.0.1:0", move |req| app.handle(req)).unwrap();
+        sender.send(server.server_addr()).unwrap();
+        loop {
+            server.poll();
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+    let address = receiver.recv().unwrap();
+    Client::new(&format!("http://{}", address)).unwrap()
+}
+
+fn lang_map(english: &str) -> LangMap {
+    let mut map = LangMap::new();
+    map.insert(Lang::English, english.to_string());
+    map
+}
+
+fn novel(name: &str, links: &[&str]) -> SourceCreator {
+    SourceCreator {
+        names: lang_map(name),
+        descriptions: LangMap::new(),
+        source_type: SourceType::Novel,
+        related_links: links.iter().map(|link| (link.to_string(), LangMap::new())).collect(),
+        dates: Vec::new(),
+        universe_tags: Vec::new(),
+        related_sources: Vec::new(),
+        people: Vec::new(),
+        characters: Vec::new(),
+        companies: Vec::new(),
+    }
+}
+
+fn server_error<T>(result: Result<T, ClientError>) -> (u16, Error) {
+    match result {
+        Err(ClientError::Server { status, error }) => (status, error),
+        Err(e) => panic!("Expected an error from the server, not {}", e),
+        Ok(_) => panic!("Expected an error from the server"),
+    }
+}
+
+#[test]
+fn sources_and_universe_tags() {
+    let client = start_server("sources");
+    let tag_id = client.create_universe_tag(&UniverseTagCreator {
+        names: lang_map("Spice and Wolf"),
+        descriptions: LangMap::new(),
+        related_links: vec![
+            ("https://en.wikipedia.org/wiki/Spice_and_Wolf".to_string(), LangMap::new()),
+        ],
+        parents: Vec::new(),
+        children: Vec::new(),
+        related_universe_tags: Vec::new(),
+    }).unwrap();
+    assert_eq!(client.read_root_universe_tag_ids().unwrap(), vec![tag_id]);
+
+    let tags = client.read_universe_tags(&UniverseTagReader { ids: vec![tag_id] }).unwrap();
+    assert_eq!(tags[0].names.get(&Lang::English).map(String::as_str), Some("Spice and Wolf"));
+    let link = &tags[0].related_links[0];
+    assert_eq!(link.link_type().as_str(), link_types::WIKIPEDIA);
+    assert_eq!(link.external_id(), Some("en:Spice_and_Wolf"));
+
+    let mut creator = novel("Spice and Wolf", &["https://myanimelist.net/manga/9115"]);
+    creator.universe_tags.push(tag_id);
+    let source_id = client.create_source(&creator).unwrap();
+    assert_eq!(client.find_sources_by_external_id(link_types::MY_ANIME_LIST, "9115").unwrap(),
+        vec![source_id]);
+
+    let results = client.search(&SearchQuery {
+        query: "wolf".to_string(),
+        item_type: Some(ItemType::Source),
+        tracker: None,
+        type_boosts: BTreeMap::new(),
+    }).unwrap();
+    assert_eq!(results.iter().map(|result| result.id).collect::<Vec<_>>(), vec![source_id]);
+    let suggestions = client.suggest(&SuggestQuery {
+        prefix: "Spi".to_string(),
+        item_type: ItemType::UniverseTag,
+        limit: 5,
+        selected_ids: Vec::new(),
+    }).unwrap();
+    assert_eq!(suggestions[0].id, tag_id);
+
+    assert!(client.read_link_types().unwrap().iter()
+        .any(|definition| definition.name == link_types::SHOUSETSUKA_NAROU));
+    assert!(client.read_broken_links().unwrap().is_empty());
+}
+
+#[test]
+fn smart_lists() {
+    let client = start_server("smart_lists");
+    client.create_source(&novel("Spice and Wolf", &[])).unwrap();
+    let id = client.create_smart_list(TRACKER, &SmartListCreator {
+        name: "Novels".to_string(),
+        query: "type:novel".to_string(),
+        item_type: Some(ItemType::Source),
+        type_boosts: BTreeMap::new(),
+    }).unwrap();
+
+    let smart_lists = client.read_smart_lists(TRACKER).unwrap();
+    assert_eq!(smart_lists.iter().map(|list| list.id).collect::<Vec<_>>(), vec![id]);
+    assert_eq!(client.run_smart_list(TRACKER, &SmartListSelector { id }).unwrap().len(), 1);
+
+    client.delete_smart_list(TRACKER, &SmartListSelector { id }).unwrap();
+    assert!(client.read_smart_lists(TRACKER).unwrap().is_empty());
+}
+
+#[test]
+fn imports_and_exports() {
+    let client = start_server("imports");
+    let csv = "Title,Author,Additional Authors,Exclusive Shelf,Date Read,Read Count,Private Notes\n\
+        Spice and Wolf,Isuna Hasekura,,read,2020/01/02,1,\n";
+    let report = client.import_csv(TRACKER, csv, Some(CsvPreset::Goodreads), None).unwrap();
+    assert_eq!(report.created.len(), 1);
+
+    let exported = client.export_csv(TRACKER, &CsvExportOptions {
+        lang: Lang::English,
+        bom: false,
+    }).unwrap();
+    assert!(exported.contains("Spice and Wolf"));
+    assert!(client.feed(TRACKER, Lang::English).unwrap().contains("<feed"));
+    assert!(client.calendar(&CalendarFilter {
+        universe_tag_id: None,
+        source_type: Some(SourceType::Novel),
+        lang: Lang::English,
+    }).unwrap().contains("BEGIN:VCALENDAR"));
+    assert!(client.graph(&GraphOptions {
+        subgraphs: Vec::new(),
+        lang: Lang::English,
+        format: GraphFormat::Dot,
+    }).unwrap().contains("digraph"));
+    assert!(client.export_anilist(TRACKER, MediaType::Manga).unwrap().contains("lists"));
+    assert!(client.export_my_anime_list(TRACKER, MediaType::Manga).unwrap()
+        .contains("<myanimelist>"));
+    assert!(client.openapi().unwrap().contains("/api/search"));
+}
+
+#[test]
+fn errors_keep_their_kind() {
+    let client = start_server("errors");
+    let (status, error) = server_error(client.read_smart_lists("Nobody"));
+    assert_eq!(status, 404);
+    assert!(matches!(error, Error::NotFound(_)));
+
+    let (status, error) = server_error(client.create_source(&novel("Nowhere",
+        &["https://example.com/"])));
+    assert_eq!(status, 400);
+    assert!(matches!(error, Error::Validation(_)));
+
+    let (status, error) = server_error(client.import_csv(TRACKER, "Title\n", None, None));
+    assert_eq!(status, 400);
+    assert!(matches!(error, Error::Validation(_)));
+
+    let (status, error) = server_error(client.export_anilist("Nobody", MediaType::Anime));
+    assert_eq!(status, 404);
+    assert!(matches!(error, Error::NotFound(_)));
+
+    let gone = Client::new("http://127.0.0.1:1").unwrap();
+    assert!(matches!(gone.read_link_types(), Err(ClientError::Transport(_))));
+}
+
+#[test]
+fn export_the_site() {
+    let client = start_server("site");
+    client.create_source(&novel("Spice and Wolf", &[])).unwrap();
+    let report = client.export_site(Lang::English).unwrap();
+    assert_eq!(report.sources, 1);
+    assert_eq!(report.trackers, 1);
+    assert!(test_folder("site").join("site").join("index.html").is_file());
+}
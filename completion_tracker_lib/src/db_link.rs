@@ -1,7 +1,15 @@
-use std::sync::{Mutex, MutexGuard};
+use std::{
+    path::{Path},
+    sync::{Mutex, MutexGuard},
+};
 
 use rusqlite::{Connection};
 
+use crate::{
+    error::{Error, ResultExt},
+    sources::api,
+};
+
 pub struct ConnectionHolder {
     connection: Mutex<Connection>,
 }
@@ -13,4 +21,42 @@ impl ConnectionHolder {
     }
 
     pub fn lock(&self) -> MutexGuard<Connection> { self.connection.lock().unwrap() }
+
+    /// Everything that's wrong with the DB file, like corrupted pages or foreign keys that
+    /// point nowhere. It's empty when the DB is fine.
+    pub fn check(&self) -> Result<Vec<String>, Error> {
+        let db = self.lock();
+        let mut integrity_statement = db.prepare("PRAGMA integrity_check")?;
+        let mut problems: Vec<String> = api::collect_query_map(
+            integrity_statement.query_map(rusqlite::NO_PARAMS, |row| row.get(0))
+        )?;
+        // It's a single "ok" when nothing is wrong
+        problems.retain(|problem| problem != "ok");
+
+        let mut foreign_key_statement = db.prepare("PRAGMA foreign_key_check")?;
+        let foreign_key_problems = api::collect_query_map(
+            foreign_key_statement.query_map(rusqlite::NO_PARAMS, |row| {
+                let table: String = row.get(0)?;
+                let row_id: Option<i64> = row.get(1)?;
+                let parent: String = row.get(2)?;
+                Ok(format!("Row {} in {} points to a missing row in {}",
+                    row_id.map_or("?".to_string(), |id| id.to_string()), table, parent))
+            })
+        )?;
+        problems.extend(foreign_key_problems);
+
+        Ok(problems)
+    }
+
+    /// Copies the whole DB into a new file. The DB can still be used while it's copied.
+    pub fn backup_to(&self, file: impl AsRef<Path>) -> Result<(), Error> {
+        let file = file.as_ref();
+        if file.exists() {
+            return Err(Error::Conflict(
+                format!("The backup file {} already exists", file.display())));
+        }
+        self.lock().execute("VACUUM INTO ?", &[file.to_string_lossy()])
+            .with_context(|| format!("Failed to back up the DB into {}", file.display()))?;
+        Ok(())
+    }
 }
@@ -0,0 +1,116 @@
+use std::fmt;
+
+use rusqlite::{ErrorCode};
+
+/// Everything that can go wrong in the library, split up by what the caller can do about it
+#[derive(Debug)]
+pub enum Error {
+    /// The item (or tracker, or file) doesn't exist
+    NotFound(String),
+    /// The change goes against something that's already there, like a duplicate link
+    Conflict(String),
+    /// The input is bad in some way
+    Validation(String),
+    /// The DB failed for a reason that isn't the caller's fault
+    Database(String),
+    /// Reading or writing a file failed
+    Io(String),
+}
+impl Error {
+    /// The kind of error, for anything that needs to tell them apart without matching
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound(_) => "NotFound",
+            Self::Conflict(_) => "Conflict",
+            Self::Validation(_) => "Validation",
+            Self::Database(_) => "Database",
+            Self::Io(_) => "Io",
+        }
+    }
+
+    /// The error with the kind from code(), like when it comes back from the server
+    pub fn from_code(code: &str, message: String) -> Option<Error> {
+        match code {
+            "NotFound" => Some(Self::NotFound(message)),
+            "Conflict" => Some(Self::Conflict(message)),
+            "Validation" => Some(Self::Validation(message)),
+            "Database" => Some(Self::Database(message)),
+            "Io" => Some(Self::Io(message)),
+            _ => None,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::NotFound(message) | Self::Conflict(message) | Self::Validation(message)
+                | Self::Database(message) | Self::Io(message) => message,
+        }
+    }
+
+    /// Puts what was happening in front of the message, keeping the kind
+    pub fn context(self, context: impl fmt::Display) -> Error {
+        let with_context = |message: String| format!("{}: {}", context, message);
+        match self {
+            Self::NotFound(message) => Self::NotFound(with_context(message)),
+            Self::Conflict(message) => Self::Conflict(with_context(message)),
+            Self::Validation(message) => Self::Validation(with_context(message)),
+            Self::Database(message) => Self::Database(with_context(message)),
+            Self::Io(message) => Self::Io(with_context(message)),
+        }
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+impl std::error::Error for Error {}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Error {
+        match &e {
+            rusqlite::Error::QueryReturnedNoRows => Self::NotFound(e.to_string()),
+            rusqlite::Error::SqliteFailure(failure, _)
+                if failure.code == ErrorCode::ConstraintViolation => Self::Conflict(e.to_string()),
+            _ => Self::Database(e.to_string()),
+        }
+    }
+}
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound(e.to_string()),
+            _ => Self::Io(e.to_string()),
+        }
+    }
+}
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        if e.is_io() {
+            Self::Io(e.to_string())
+        } else {
+            Self::Validation(e.to_string())
+        }
+    }
+}
+impl From<url::ParseError> for Error {
+    fn from(e: url::ParseError) -> Error { Self::Validation(e.to_string()) }
+}
+impl From<std::fmt::Error> for Error {
+    fn from(e: std::fmt::Error) -> Error { Self::Io(e.to_string()) }
+}
+
+/// Adds context to any error that can become an Error
+pub trait ResultExt<T> {
+    fn context(self, context: impl fmt::Display) -> Result<T, Error>;
+    /// Only makes the context when there's an error
+    fn with_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T, Error>;
+}
+impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
+    fn context(self, context: impl fmt::Display) -> Result<T, Error> {
+        self.map_err(|e| e.into().context(context))
+    }
+    fn with_context<C: fmt::Display>(self, context: impl FnOnce() -> C) -> Result<T, Error> {
+        self.map_err(|e| e.into().context(context()))
+    }
+}
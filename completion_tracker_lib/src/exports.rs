@@ -0,0 +1,213 @@
+pub mod anilist;
+pub mod atom;
+pub mod calendar;
+pub mod csv;
+pub mod graph;
+pub mod my_anime_list;
+pub mod site;
+
+use rusqlite::{Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    sources::{
+        api,
+        source_types::{Lang, LangMap, SourceType},
+    },
+    tracking::entry::{self, TrackerEntry},
+};
+
+/// The way that the anime sites split up their lists
+#[derive(Copy, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub enum MediaType {
+    Anime,
+    /// This also has the novels, since that's where the anime sites put them
+    Manga,
+}
+impl MediaType {
+    pub fn of(source_type: SourceType) -> MediaType {
+        match source_type {
+            SourceType::TVShow | SourceType::Movie => Self::Anime,
+            SourceType::Comic | SourceType::ComicRunning |
+            SourceType::Novel | SourceType::WebNovel => Self::Manga,
+        }
+    }
+}
+
+/// A tracker's entry with the parts of its Source that the exports need
+pub(crate) struct ExportEntry {
+    pub entry: TrackerEntry,
+    pub names: LangMap,
+    pub source_type: SourceType,
+    /// The (link type, external ID) of the Source's links that have an ID, in the order they
+    /// were added
+    pub external_ids: Vec<(String, String)>,
+}
+impl ExportEntry {
+    /// The English name if there is one, since the sites are easier to search in English
+    pub fn title(&self) -> &str {
+        lang_map_get(&self.names, Lang::English)
+    }
+
+    /// The site's ID for the Source, from the first link to that site.
+    /// The sites that are exported to all use numbers for their IDs.
+    pub fn external_id(&self, link_type: &str) -> Option<u64> {
+        self.external_ids.iter()
+            .filter(|(id_link_type, _)| id_link_type == link_type)
+            .find_map(|(_, external_id)| external_id.parse().ok())
+    }
+}
+
+/// The string in the language, or in any other language when it's missing
+pub(crate) fn lang_map_get(lang_map: &LangMap, lang: Lang) -> &str {
+    lang_map.get(&lang)
+        .or_else(|| lang_map.values().next())
+        .map(String::as_str)
+        .unwrap_or("")
+}
+
+/// Escapes the text for an XML attribute or element
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escapes the text for HTML, which also needs the single quotes escaped for its attributes
+pub(crate) fn escape_html(text: &str) -> String {
+    escape_xml(text).replace('\'', "&#39;")
+}
+
+/// Reads every entry of the tracker, only keeping the Sources of the media type if there is one.
+/// Entries that point to a Source that doesn't exist anymore are left out.
+pub(crate) fn read_export_entries(sources_db: &ConnectionHolder, tracker_db: &ConnectionHolder,
+media_type: Option<MediaType>) -> Result<Vec<ExportEntry>, Error> {
+    let entries = entry::read_all(tracker_db)?;
+
+    let db = sources_db.lock();
+    let mut export_entries = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if let Some((names, source_type)) = read_source(&db, entry.source_id)? {
+            if media_type.is_some_and(|media_type| MediaType::of(source_type) != media_type) {
+                continue;
+            }
+            export_entries.push(ExportEntry {
+                external_ids: read_external_ids(&db, entry.source_id)?,
+                entry,
+                names,
+                source_type,
+            });
+        }
+    }
+    Ok(export_entries)
+}
+
+fn read_source(db: &Connection, source_id: u64) -> Result<Option<(LangMap, SourceType)>, Error> {
+    let mut statement = db.prepare("
+        SELECT Strings.english, Strings.japanese, Sources.source_type FROM Sources
+            JOIN Strings ON Strings.id = Sources.names
+            WHERE Sources.id=?
+    ")?;
+    let mut found = api::collect_query_map(statement.query_map([source_id as i64], |row| {
+        let source_type: SourceType = row.get(2)?;
+        Ok( (api::make_lang_strings_from_row(row), source_type) )
+    }))?;
+    Ok(found.pop())
+}
+
+/// The IDs that were found when the links were saved.
+/// The links aren't made again, since an old link might not be valid anymore
+/// (like when the link types changed) and that shouldn't stop the whole export.
+fn read_external_ids(db: &Connection, source_id: u64) -> Result<Vec<(String, String)>, Error> {
+    let mut statement = db.prepare("
+        SELECT link_type, external_id FROM SourceRelatedLinks
+            WHERE source_id=? AND external_id IS NOT NULL
+            ORDER BY id
+    ")?;
+    let external_ids = statement.query_map([source_id as i64], |row| {
+        Ok( (row.get(0)?, row.get(1)?) )
+    })?
+        // A row that can't be read is left out too, instead of stopping the export
+        .filter_map(Result::ok)
+        .collect();
+    Ok(external_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap};
+
+    use chrono::{NaiveDate};
+
+    use super::*;
+    use crate::sources::source_types::{link_types};
+
+    fn make_export_entry(external_ids: &[(&str, &str)]) -> ExportEntry {
+        ExportEntry {
+            entry: TrackerEntry {
+                source_id: 1,
+                description: String::new(),
+                play_time: None,
+                times_repeated: 0,
+                last_updated: NaiveDate::from_ymd_opt(2020, 1, 2).unwrap(),
+                status_updates: BTreeMap::new(),
+            },
+            names: LangMap::new(),
+            source_type: SourceType::TVShow,
+            external_ids: external_ids.iter()
+                .map(|(link_type, external_id)| (link_type.to_string(), external_id.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn external_ids_come_from_the_links() {
+        let export_entry = make_export_entry(&[
+            (link_types::WIKIPEDIA, "en:Spice_and_Wolf"),
+            (link_types::MY_ANIME_LIST, "2966"),
+            (link_types::ANI_LIST, "2966"),
+            (link_types::MY_ANIME_LIST, "5341"),
+        ]);
+        // The first link to the site wins
+        assert_eq!(export_entry.external_id(link_types::MY_ANIME_LIST), Some(2966));
+        assert_eq!(export_entry.external_id(link_types::ANI_LIST), Some(2966));
+        // Only numbers can be used
+        assert_eq!(export_entry.external_id(link_types::WIKIPEDIA), None);
+    }
+
+    #[test]
+    fn sources_without_a_link_have_no_id() {
+        let export_entry = make_export_entry(&[(link_types::WIKIPEDIA, "en:Spice_and_Wolf")]);
+        assert_eq!(export_entry.external_id(link_types::MY_ANIME_LIST), None);
+        assert_eq!(export_entry.external_id(link_types::ANI_LIST), None);
+        let export_entry = make_export_entry(&[]);
+        assert_eq!(export_entry.external_id(link_types::MY_ANIME_LIST), None);
+    }
+
+    #[test]
+    fn xml_is_escaped() {
+        assert_eq!(escape_xml("<a href=\"x\">Tom & Jerry</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&lt;/a&gt;");
+        // Anything that's already escaped is escaped again
+        assert_eq!(escape_xml("&amp;"), "&amp;amp;");
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        assert_eq!(escape_html("<a title='Holo'>Spice & Wolf</a>"),
+            "&lt;a title=&#39;Holo&#39;&gt;Spice &amp; Wolf&lt;/a&gt;");
+    }
+
+    #[test]
+    fn titles_fall_back_to_any_language() {
+        let mut export_entry = make_export_entry(&[]);
+        assert_eq!(export_entry.title(), "");
+        export_entry.names.insert(Lang::Japanese, "狼と香辛料".to_string());
+        assert_eq!(export_entry.title(), "狼と香辛料");
+        export_entry.names.insert(Lang::English, "Spice and Wolf".to_string());
+        assert_eq!(export_entry.title(), "Spice and Wolf");
+    }
+}
@@ -0,0 +1,140 @@
+//! Exports a tracker in the shape of AniList's MediaListCollection.
+//! AniList keeps anime and manga in separate collections, so there's one export for each.
+
+use chrono::{Datelike, NaiveDate};
+use schemars::{JsonSchema};
+use serde::{Serialize};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    exports::{self, ExportEntry, MediaType},
+    sources::source_types::{Lang, SourceType, link_types},
+    tracking::{CompletionStatus},
+};
+
+#[derive(Serialize, JsonSchema)]
+pub struct MediaListCollection {
+    pub lists: Vec<MediaListGroup>,
+}
+
+/// All of the entries with the same status
+#[derive(Serialize, JsonSchema)]
+pub struct MediaListGroup {
+    pub name: &'static str,
+    pub status: &'static str,
+    pub entries: Vec<MediaList>,
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaList {
+    /// The AniList ID, if the Source links to AniList
+    pub media_id: Option<u64>,
+    pub status: &'static str,
+    pub repeat: u32,
+    pub notes: String,
+    pub started_at: FuzzyDate,
+    pub completed_at: FuzzyDate,
+    pub media: Media,
+}
+
+/// AniList's dates can be missing any part
+#[derive(Serialize, JsonSchema)]
+pub struct FuzzyDate {
+    pub year: Option<i32>,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+}
+impl From<Option<NaiveDate>> for FuzzyDate {
+    fn from(date: Option<NaiveDate>) -> FuzzyDate {
+        FuzzyDate {
+            year: date.map(|date| date.year()),
+            month: date.map(|date| date.month()),
+            day: date.map(|date| date.day()),
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Media {
+    pub id: Option<u64>,
+    pub id_mal: Option<u64>,
+    #[serde(rename = "type")]
+    pub media_type: &'static str,
+    pub format: &'static str,
+    pub title: MediaTitle,
+}
+
+#[derive(Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaTitle {
+    pub english: Option<String>,
+    pub native: Option<String>,
+    pub user_preferred: String,
+}
+
+pub fn export(sources_db: &ConnectionHolder, tracker_db: &ConnectionHolder, media_type: MediaType)
+-> Result<MediaListCollection, Error> {
+    let mut current = Vec::new();
+    let mut completed = Vec::new();
+    for export_entry in exports::read_export_entries(sources_db, tracker_db, Some(media_type))? {
+        match export_entry.entry.current_status() {
+            Some(CompletionStatus::InProgress) =>
+                current.push(make_media_list(&export_entry, "CURRENT")),
+            Some(CompletionStatus::Complete) | Some(CompletionStatus::GameComplete) =>
+                completed.push(make_media_list(&export_entry, "COMPLETED")),
+            None => (),
+        }
+    }
+
+    let current_name = match media_type {
+        MediaType::Anime => "Watching",
+        MediaType::Manga => "Reading",
+    };
+    Ok(MediaListCollection {
+        lists: vec![
+            MediaListGroup { name: current_name, status: "CURRENT", entries: current },
+            MediaListGroup { name: "Completed", status: "COMPLETED", entries: completed },
+        ],
+    })
+}
+
+fn make_media_list(export_entry: &ExportEntry, status: &'static str) -> MediaList {
+    let entry = &export_entry.entry;
+    let anilist_id = export_entry.external_id(link_types::ANI_LIST);
+    let started_at = entry.status_updates.get(&CompletionStatus::InProgress)
+        .and_then(|time| time.date);
+    let completed_at = entry.status_updates.iter()
+        .find(|(status, _)| status.is_finished())
+        .and_then(|(_, time)| time.date);
+
+    MediaList {
+        media_id: anilist_id,
+        status,
+        repeat: entry.times_repeated,
+        notes: entry.description.clone(),
+        started_at: started_at.into(),
+        completed_at: completed_at.into(),
+        media: Media {
+            id: anilist_id,
+            id_mal: export_entry.external_id(link_types::MY_ANIME_LIST),
+            media_type: match MediaType::of(export_entry.source_type) {
+                MediaType::Anime => "ANIME",
+                MediaType::Manga => "MANGA",
+            },
+            format: match export_entry.source_type {
+                SourceType::TVShow => "TV",
+                SourceType::Movie => "MOVIE",
+                SourceType::Comic | SourceType::ComicRunning => "MANGA",
+                SourceType::Novel | SourceType::WebNovel => "NOVEL",
+            },
+            title: MediaTitle {
+                english: export_entry.names.get(&Lang::English).cloned(),
+                native: export_entry.names.get(&Lang::Japanese).cloned(),
+                user_preferred: export_entry.title().to_string(),
+            },
+        },
+    }
+}
@@ -0,0 +1,117 @@
+//! Publishes the recent completion events of a tracker as an Atom (RFC 4287) feed.
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use rusqlite::{Connection};
+use url::form_urlencoded;
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    exports,
+    simple_enum::{SimpleEnum},
+    sources::{
+        api,
+        source_types::{Lang},
+    },
+    tracking::{CompletionStatus},
+};
+
+/// How many events are in the feed
+const FEED_LENGTH: u32 = 50;
+/// The tag URIs (RFC 4151) need a date, and this one must never change
+const TAG_PREFIX: &str = "tag:completion-tracker,2019";
+
+struct FeedEvent {
+    source_id: u64,
+    status: CompletionStatus,
+    date: Option<NaiveDate>,
+    updated: DateTime<Utc>,
+}
+
+/// The last time that a status changed in the tracker.
+/// This is cheap enough to check before making the whole feed.
+pub fn last_modified(tracker_db: &ConnectionHolder) -> Result<Option<DateTime<Utc>>, Error> {
+    let db = tracker_db.lock();
+    Ok(read_events(&db, 1)?.into_iter()
+        .next()
+        .map(|event| event.updated))
+}
+
+/// Makes the whole feed, with the newest events first
+pub fn export(sources_db: &ConnectionHolder, tracker_db: &ConnectionHolder, tracker_name: &str,
+lang: Lang) -> Result<String, Error> {
+    let events = {
+        let db = tracker_db.lock();
+        read_events(&db, FEED_LENGTH)?
+    };
+    // An empty feed still needs a time
+    let feed_updated = events.first()
+        .map_or_else(|| Utc.timestamp(0, 0), |event| event.updated);
+    let tracker_id: String = form_urlencoded::byte_serialize(tracker_name.as_bytes()).collect();
+
+    let mut feed = String::new();
+    feed.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    feed.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    feed.push_str(&format!("  <id>{}:tracker/{}</id>\n", TAG_PREFIX, tracker_id));
+    feed.push_str(&format!("  <title>{}</title>\n", exports::escape_xml(tracker_name)));
+    feed.push_str(&format!("  <updated>{}</updated>\n", feed_updated.to_rfc3339()));
+    feed.push_str(&format!("  <author><name>{}</name></author>\n",
+        exports::escape_xml(tracker_name)));
+
+    let db = sources_db.lock();
+    for event in events {
+        // The Source might have been deleted since
+        let (names, source_type) = match exports::read_source(&db, event.source_id)? {
+            Some(source) => source,
+            None => continue,
+        };
+        let name = exports::lang_map_get(&names, lang);
+        let content = match event.date {
+            Some(date) => format!("{} ({}) reached {} on {}",
+                name, source_type.as_str(), event.status.as_str(), date.format("%Y-%m-%d")),
+            None => format!("{} ({}) reached {}",
+                name, source_type.as_str(), event.status.as_str()),
+        };
+
+        feed.push_str("  <entry>\n");
+        // A status is only reached once for each Source, so this will always be the same event
+        feed.push_str(&format!("    <id>{}:tracker/{}/source/{}/{}</id>\n",
+            TAG_PREFIX, tracker_id, event.source_id, event.status.as_str()));
+        feed.push_str(&format!("    <title>{}: {}</title>\n",
+            event.status.as_str(), exports::escape_xml(name)));
+        feed.push_str(&format!("    <updated>{}</updated>\n", event.updated.to_rfc3339()));
+        feed.push_str(&format!("    <category term=\"{}\"/>\n", source_type.as_str()));
+        feed.push_str(&format!("    <content type=\"text\">{}</content>\n",
+            exports::escape_xml(&content)));
+        feed.push_str("  </entry>\n");
+    }
+    feed.push_str("</feed>\n");
+    Ok(feed)
+}
+
+/// Reads the newest events. The ones from before recorded_at was kept use the day they were
+/// reached instead, or the day the entry was last updated.
+fn read_events(db: &Connection, limit: u32) -> Result<Vec<FeedEvent>, Error> {
+    let mut statement = db.prepare("
+        SELECT TrackerEntries.source_id, TrackerEntryStatusUpdates.status,
+            TrackerEntryStatusUpdates.date,
+            COALESCE(
+                TrackerEntryStatusUpdates.recorded_at,
+                TrackerEntryStatusUpdates.date || 'T00:00:00+00:00',
+                TrackerEntries.last_updated || 'T00:00:00+00:00'
+            ) AS updated
+            FROM TrackerEntryStatusUpdates
+            INNER JOIN TrackerEntries ON TrackerEntries.id=TrackerEntryStatusUpdates.entry_id
+            ORDER BY updated DESC, TrackerEntryStatusUpdates.id DESC
+            LIMIT ?
+    ")?;
+    api::collect_query_map(statement.query_map([limit], |row| {
+        let source_id: i64 = row.get(0)?;
+        Ok(FeedEvent {
+            source_id: source_id as u64,
+            status: row.get(1)?,
+            date: row.get(2)?,
+            updated: row.get(3)?,
+        })
+    })).map_err(Error::from)
+}
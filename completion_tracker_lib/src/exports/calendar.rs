@@ -0,0 +1,193 @@
+//! Exports the SourceDates as an iCalendar (RFC 5545) feed, so calendar apps can subscribe to it.
+
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    exports,
+    sources::{
+        api,
+        source_types::{Lang, LangMap, SourceType},
+    },
+};
+
+/// The longest a line can be (in bytes), without the CRLF
+const MAX_LINE_LENGTH: usize = 75;
+
+#[derive(Deserialize)]
+pub struct CalendarFilter {
+    /// Only the Sources in this Universe Tag (or any of its children)
+    #[serde(default)]
+    pub universe_tag_id: Option<u64>,
+    #[serde(default)]
+    pub source_type: Option<SourceType>,
+    /// The language for the summaries. Anything missing in it uses another language.
+    pub lang: Lang,
+}
+
+struct CalendarEvent {
+    id: u64,
+    date: NaiveDate,
+    source_names: LangMap,
+    descriptions: LangMap,
+}
+
+/// Makes a whole-day event for every SourceDate that passes the filter
+pub fn export(sources_db: &ConnectionHolder, filter: &CalendarFilter) -> Result<String, Error> {
+    let events = read_events(sources_db, filter)?;
+
+    // Every event gets the same timestamp, since they're all made now
+    let timestamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut calendar = String::new();
+    push_line(&mut calendar, "BEGIN:VCALENDAR");
+    push_line(&mut calendar, "VERSION:2.0");
+    push_line(&mut calendar, "PRODID:-//Completion Tracker//Source Dates//EN");
+    push_line(&mut calendar, "CALSCALE:GREGORIAN");
+    for event in events {
+        let summary = format!("{}: {}",
+            exports::lang_map_get(&event.source_names, filter.lang),
+            exports::lang_map_get(&event.descriptions, filter.lang),
+        );
+
+        push_line(&mut calendar, "BEGIN:VEVENT");
+        push_line(&mut calendar, &format!("UID:source-date-{}@completion-tracker", event.id));
+        push_line(&mut calendar, &format!("DTSTAMP:{}", timestamp));
+        push_line(&mut calendar, &format!("DTSTART;VALUE=DATE:{}", event.date.format("%Y%m%d")));
+        // The end of a whole-day event is the next day
+        push_line(&mut calendar, &format!("DTEND;VALUE=DATE:{}",
+            (event.date + Duration::days(1)).format("%Y%m%d")));
+        push_line(&mut calendar, &format!("SUMMARY:{}", escape_text(&summary)));
+        push_line(&mut calendar, "END:VEVENT");
+    }
+    push_line(&mut calendar, "END:VCALENDAR");
+    Ok(calendar)
+}
+
+fn read_events(sources_db: &ConnectionHolder, filter: &CalendarFilter)
+-> Result<Vec<CalendarEvent>, Error> {
+    let db = sources_db.lock();
+    let mut statement = db.prepare("
+        WITH RECURSIVE FilterTags(id) AS (
+            SELECT ?1
+            UNION
+            SELECT UniverseTagChildren.child FROM UniverseTagChildren
+                INNER JOIN FilterTags ON UniverseTagChildren.parent=FilterTags.id
+        )
+        SELECT SourceDates.id, SourceDates.date,
+            NameStrings.english, NameStrings.japanese,
+            DescriptionStrings.english, DescriptionStrings.japanese
+            FROM SourceDates
+            INNER JOIN Sources ON Sources.id=SourceDates.source_id
+            INNER JOIN Strings AS NameStrings ON NameStrings.id=Sources.names
+            INNER JOIN Strings AS DescriptionStrings
+                ON DescriptionStrings.id=SourceDates.descriptions
+            WHERE (?1 IS NULL OR Sources.id IN (
+                SELECT source_id FROM SourceUniverseTags
+                    WHERE universe_tag_id IN (SELECT id FROM FilterTags)
+            ))
+            AND (?2 IS NULL OR Sources.source_type=?2)
+            ORDER BY SourceDates.date, SourceDates.id
+    ")?;
+
+    let universe_tag_id = filter.universe_tag_id.map(|id| id as i64);
+    let events = api::collect_query_map(
+        statement.query_map(rusqlite::params![universe_tag_id, filter.source_type], |row| {
+            let id: i64 = row.get(0)?;
+            Ok(CalendarEvent {
+                id: id as u64,
+                date: row.get(1)?,
+                source_names: api::make_lang_strings_from_row_at(row, 2),
+                descriptions: api::make_lang_strings_from_row_at(row, 4),
+            })
+        })
+    )?;
+    Ok(events)
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Adds the line with a CRLF, folding it when it's too long.
+/// The folds can't split up a character, since that would break the UTF-8.
+fn push_line(calendar: &mut String, line: &str) {
+    let mut line_length = 0;
+    for c in line.chars() {
+        if line_length + c.len_utf8() > MAX_LINE_LENGTH {
+            // The space at the start of the next line counts towards its length
+            calendar.push_str("\r\n ");
+            line_length = 1;
+        }
+        calendar.push(c);
+        line_length += c.len_utf8();
+    }
+    calendar.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The physical lines, without the CRLF at the end
+    fn physical_lines(calendar: &str) -> Vec<&str> {
+        assert!(calendar.ends_with("\r\n"));
+        calendar[..calendar.len() - 2].split("\r\n").collect()
+    }
+
+    /// Undoes the folding, which is a CRLF followed by a space
+    fn unfold(calendar: &str) -> String {
+        calendar.replace("\r\n ", "")
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(escape_text("a\\b"), "a\\\\b");
+        assert_eq!(escape_text("Spice and Wolf; Vol. 1, 2"), "Spice and Wolf\\; Vol. 1\\, 2");
+        assert_eq!(escape_text("one\r\ntwo\nthree"), "one\\ntwo\\nthree");
+        // The backslash from the escapes isn't escaped again
+        assert_eq!(escape_text("\\;"), "\\\\\\;");
+    }
+
+    #[test]
+    fn short_lines_are_not_folded() {
+        let mut calendar = String::new();
+        let line = "x".repeat(MAX_LINE_LENGTH);
+        push_line(&mut calendar, &line);
+        assert_eq!(calendar, format!("{}\r\n", line));
+    }
+
+    #[test]
+    fn long_lines_are_folded_at_75_octets() {
+        let mut calendar = String::new();
+        let line = format!("SUMMARY:{}", "x".repeat(200));
+        push_line(&mut calendar, &line);
+
+        let lines = physical_lines(&calendar);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), MAX_LINE_LENGTH);
+        assert_eq!(lines[1].len(), MAX_LINE_LENGTH);
+        assert!(lines[1..].iter().all(|line| line.starts_with(' ')));
+        assert_eq!(unfold(&calendar), format!("{}\r\n", line));
+    }
+
+    #[test]
+    fn folds_do_not_split_characters() {
+        let mut calendar = String::new();
+        // 3 bytes for each character, so the folds don't line up with 75 bytes after the "SUMMARY:"
+        let line = format!("SUMMARY:{}", "狼と香辛料".repeat(20));
+        push_line(&mut calendar, &line);
+
+        // Every line is still whole characters, since the String would've panicked otherwise
+        let lines = physical_lines(&calendar);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(lines.iter().any(|line| line.len() < MAX_LINE_LENGTH - 1));
+        assert_eq!(unfold(&calendar), format!("{}\r\n", line));
+    }
+}
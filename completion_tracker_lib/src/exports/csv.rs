@@ -0,0 +1,204 @@
+//! Exports every completion event of a tracker as CSV (RFC 4180), for spreadsheets.
+
+use std::{
+    io::{self, Read, Write},
+    vec,
+};
+
+use rusqlite::{Connection};
+use serde::{Deserialize};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    exports::{self, ExportEntry},
+    simple_enum::{SimpleEnum},
+    sources::{
+        api,
+        source_types::{Lang, LangMap},
+    },
+    tracking::{
+        CompletionStatus,
+        entry::{StatusTime},
+    },
+};
+
+const HEADER: &[&str] = &[
+    "Source", "Source Type", "Universe Tags", "Status", "Date", "Play Time", "Notes",
+];
+
+#[derive(Deserialize)]
+pub struct CsvExportOptions {
+    /// The language for the names. Anything missing in it uses another language.
+    pub lang: Lang,
+    /// Starts the file with a UTF-8 BOM, which Excel needs to show Japanese properly
+    #[serde(default)]
+    pub bom: bool,
+}
+
+/// One row for every status that a Source has reached, ordered by the date it was reached.
+/// The ones without a date go at the end.
+/// The rows are only made as the CSV is read, so the whole file is never in memory.
+pub fn export(sources_db: &ConnectionHolder, tracker_db: &ConnectionHolder,
+options: &CsvExportOptions) -> Result<CsvExport, Error> {
+    let export_entries = exports::read_export_entries(sources_db, tracker_db, None)?;
+    let universe_tags = {
+        let db = sources_db.lock();
+        let mut universe_tags = Vec::with_capacity(export_entries.len());
+        for export_entry in export_entries.iter() {
+            universe_tags.push(read_universe_tag_names(&db, export_entry.entry.source_id)?);
+        }
+        universe_tags
+    };
+
+    let mut events = Vec::new();
+    for (entry_index, export_entry) in export_entries.iter().enumerate() {
+        for (status, time) in export_entry.entry.status_updates.iter() {
+            events.push(Event { entry_index, status: *status, time: *time });
+        }
+    }
+    events.sort_by_key(|event| {
+        let source_id = export_entries[event.entry_index].entry.source_id;
+        (event.time.date.is_none(), event.time.date, source_id, event.status)
+    });
+
+    let mut buffer = Vec::new();
+    if options.bom {
+        buffer.extend_from_slice("\u{feff}".as_bytes());
+    }
+    write_row(&mut buffer, HEADER.iter().map(|column| column.to_string()))?;
+
+    Ok(CsvExport {
+        lang: options.lang,
+        export_entries,
+        universe_tags,
+        events: events.into_iter(),
+        buffer,
+        position: 0,
+    })
+}
+
+/// A status that was reached, which is a single row
+struct Event {
+    /// The index into CsvExport::export_entries
+    entry_index: usize,
+    status: CompletionStatus,
+    time: StatusTime,
+}
+
+/// The CSV from export(), which writes the next row whenever it's read past the last one
+pub struct CsvExport {
+    lang: Lang,
+    export_entries: Vec<ExportEntry>,
+    /// The names of the Universe Tags of each entry
+    universe_tags: Vec<Vec<LangMap>>,
+    events: vec::IntoIter<Event>,
+    /// The row that's being read
+    buffer: Vec<u8>,
+    position: usize,
+}
+impl CsvExport {
+    /// Writes the next row into the buffer, returning false when there aren't any left
+    fn next_row(&mut self) -> io::Result<bool> {
+        let event = match self.events.next() {
+            Some(event) => event,
+            None => return Ok(false),
+        };
+        let export_entry = &self.export_entries[event.entry_index];
+        let universe_tag_names: Vec<&str> = self.universe_tags[event.entry_index].iter()
+            .map(|names| exports::lang_map_get(names, self.lang))
+            .collect();
+
+        self.buffer.clear();
+        self.position = 0;
+        write_row(&mut self.buffer, vec![
+            exports::lang_map_get(&export_entry.names, self.lang).to_string(),
+            export_entry.source_type.as_str().to_string(),
+            universe_tag_names.join("; "),
+            event.status.as_str().to_string(),
+            event.time.date.map(|date| date.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+            event.time.play_time.map(format_play_time).unwrap_or_default(),
+            export_entry.entry.description.clone(),
+        ])?;
+        Ok(true)
+    }
+}
+impl Read for CsvExport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.buffer.len() && !self.next_row()? {
+            return Ok(0);
+        }
+        let length = buf.len().min(self.buffer.len() - self.position);
+        buf[..length].copy_from_slice(&self.buffer[self.position..(self.position + length)]);
+        self.position += length;
+        Ok(length)
+    }
+}
+
+fn read_universe_tag_names(db: &Connection, source_id: u64) -> Result<Vec<LangMap>, Error> {
+    let mut statement = db.prepare("
+        SELECT Strings.english, Strings.japanese FROM SourceUniverseTags
+            JOIN UniverseTags ON UniverseTags.id = SourceUniverseTags.universe_tag_id
+            JOIN Strings ON Strings.id = UniverseTags.names
+            WHERE SourceUniverseTags.source_id=?
+            ORDER BY UniverseTags.id
+    ")?;
+    api::collect_query_map(statement.query_map([source_id as i64], |row| {
+        Ok(api::make_lang_strings_from_row(row))
+    })).map_err(Error::from)
+}
+
+/// Spreadsheets understand H:MM:SS as a duration
+fn format_play_time(seconds: u64) -> String {
+    format!("{}:{:02}:{:02}", seconds / 3600, (seconds / 60) % 60, seconds % 60)
+}
+
+/// Writes the row, ending it with a CRLF
+fn write_row(writer: &mut impl Write, fields: impl IntoIterator<Item = String>)
+-> io::Result<()> {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i != 0 {
+            writer.write_all(b",")?;
+        }
+        // Only quote the fields that need it
+        if field.contains([',', '"', '\r', '\n']) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(field.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[&str]) -> String {
+        let mut csv = Vec::new();
+        write_row(&mut csv, fields.iter().map(|field| field.to_string())).unwrap();
+        String::from_utf8(csv).unwrap()
+    }
+
+    #[test]
+    fn plain_fields_are_not_quoted() {
+        assert_eq!(row(&["Spice and Wolf", "Novel", ""]), "Spice and Wolf,Novel,\r\n");
+        assert_eq!(row(&["狼と香辛料", "支倉凍砂"]), "狼と香辛料,支倉凍砂\r\n");
+    }
+
+    #[test]
+    fn fields_are_quoted_when_they_need_it() {
+        assert_eq!(row(&["Fate/Zero, Vol. 1", "x"]), "\"Fate/Zero, Vol. 1\",x\r\n");
+        assert_eq!(row(&["5\" disk"]), "\"5\"\" disk\"\r\n");
+        assert_eq!(row(&["\"\""]), "\"\"\"\"\"\"\r\n");
+        assert_eq!(row(&["Line one\nLine two", "a\r\nb"]),
+            "\"Line one\nLine two\",\"a\r\nb\"\r\n");
+    }
+
+    #[test]
+    fn play_times_are_durations() {
+        assert_eq!(format_play_time(0), "0:00:00");
+        assert_eq!(format_play_time(3599), "0:59:59");
+        assert_eq!(format_play_time(90061), "25:01:01");
+    }
+}
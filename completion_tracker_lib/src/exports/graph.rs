@@ -0,0 +1,232 @@
+//! Exports the relations between the items as a graph, for tools like Graphviz, Gephi or yEd.
+//! Any of the subgraphs can be exported by themselves, or together as one graph.
+
+use std::fmt::Write;
+
+use serde::{Deserialize};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    exports,
+    simple_enum::{SimpleEnum},
+    sources::{
+        backup,
+        source_types::{Lang, LangMap, Relation},
+    },
+};
+
+#[derive(Copy, Clone, Deserialize, Eq, PartialEq)]
+pub enum Subgraph {
+    /// The Universe Tags, with an edge from every parent to each of its children
+    UniverseTagHierarchy,
+    UniverseTagRelations,
+    SourceRelations,
+    CharacterRelations,
+}
+impl SimpleEnum for Subgraph {
+    fn all() -> &'static [Subgraph] {
+        &[
+            Self::UniverseTagHierarchy,
+            Self::UniverseTagRelations,
+            Self::SourceRelations,
+            Self::CharacterRelations,
+        ]
+    }
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::UniverseTagHierarchy => "UniverseTagHierarchy",
+            Self::UniverseTagRelations => "UniverseTagRelations",
+            Self::SourceRelations => "SourceRelations",
+            Self::CharacterRelations => "CharacterRelations",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Deserialize)]
+pub enum GraphFormat {
+    Dot,
+    GraphML,
+}
+
+#[derive(Deserialize)]
+pub struct GraphOptions {
+    /// Every subgraph is exported when this is empty
+    #[serde(default)]
+    pub subgraphs: Vec<Subgraph>,
+    /// The language for the labels. Anything missing in it uses another language.
+    pub lang: Lang,
+    pub format: GraphFormat,
+}
+
+struct Node {
+    id: String,
+    kind: &'static str,
+    label: String,
+}
+
+struct Edge {
+    source: String,
+    target: String,
+    label: String,
+    /// Alternates go both ways, so they don't get an arrow
+    directed: bool,
+    subgraph: Subgraph,
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+pub fn export(sources_db: &ConnectionHolder, options: &GraphOptions) -> Result<String, Error> {
+    let graph = read_graph(sources_db, options)?;
+    match options.format {
+        GraphFormat::Dot => write_dot(&graph),
+        GraphFormat::GraphML => write_graphml(&graph),
+    }.map_err(Error::from)
+}
+
+fn universe_tag_node_id(id: u64) -> String { format!("universeTag{}", id) }
+fn source_node_id(id: u64) -> String { format!("source{}", id) }
+fn character_node_id(id: u64) -> String { format!("character{}", id) }
+
+/// The Relations are all from the item in the 1st spot, so they're the same as the
+/// DBSourceRelation that's stored
+fn read_graph(sources_db: &ConnectionHolder, options: &GraphOptions) -> Result<Graph, Error> {
+    let wants = |subgraph: Subgraph| options.subgraphs.is_empty()
+        || options.subgraphs.contains(&subgraph);
+    let label = |names: &LangMap| exports::lang_map_get(names, options.lang).to_string();
+    let backup = backup::export(sources_db)?;
+    let mut graph = Graph::default();
+
+    if wants(Subgraph::UniverseTagHierarchy) || wants(Subgraph::UniverseTagRelations) {
+        for universe_tag in backup.universe_tags.iter() {
+            graph.nodes.push(Node {
+                id: universe_tag_node_id(universe_tag.id),
+                kind: "UniverseTag",
+                label: label(&universe_tag.names),
+            });
+            if wants(Subgraph::UniverseTagHierarchy) {
+                for child_id in universe_tag.children.iter() {
+                    graph.edges.push(Edge {
+                        source: universe_tag_node_id(universe_tag.id),
+                        target: universe_tag_node_id(*child_id),
+                        label: "Child".to_string(),
+                        directed: true,
+                        subgraph: Subgraph::UniverseTagHierarchy,
+                    });
+                }
+            }
+            if wants(Subgraph::UniverseTagRelations) {
+                for (related_id, relation) in universe_tag.related_universe_tags.iter() {
+                    graph.edges.push(Edge {
+                        source: universe_tag_node_id(universe_tag.id),
+                        target: universe_tag_node_id(*related_id),
+                        label: relation.as_str().to_string(),
+                        directed: is_directed(*relation),
+                        subgraph: Subgraph::UniverseTagRelations,
+                    });
+                }
+            }
+        }
+    }
+    if wants(Subgraph::SourceRelations) {
+        for source in backup.sources.iter() {
+            graph.nodes.push(Node {
+                id: source_node_id(source.id),
+                kind: "Source",
+                label: label(&source.names),
+            });
+            for (related_id, relation) in source.related_sources.iter() {
+                graph.edges.push(Edge {
+                    source: source_node_id(source.id),
+                    target: source_node_id(*related_id),
+                    label: relation.as_str().to_string(),
+                    directed: is_directed(*relation),
+                    subgraph: Subgraph::SourceRelations,
+                });
+            }
+        }
+    }
+    if wants(Subgraph::CharacterRelations) {
+        for character in backup.characters.iter() {
+            graph.nodes.push(Node {
+                id: character_node_id(character.id),
+                kind: "Character",
+                label: label(&character.names),
+            });
+            // The descriptions of a Character relation work from both sides
+            for (related_id, descriptions) in character.related_characters.iter() {
+                graph.edges.push(Edge {
+                    source: character_node_id(character.id),
+                    target: character_node_id(*related_id),
+                    label: label(descriptions),
+                    directed: false,
+                    subgraph: Subgraph::CharacterRelations,
+                });
+            }
+        }
+    }
+    Ok(graph)
+}
+
+fn is_directed(relation: Relation) -> bool {
+    match relation {
+        Relation::Before | Relation::After => true,
+        Relation::Alternate => false,
+    }
+}
+
+fn write_dot(graph: &Graph) -> Result<String, std::fmt::Error> {
+    let mut dot = String::new();
+    dot.push_str("digraph CompletionTracker {\n");
+    for node in graph.nodes.iter() {
+        writeln!(dot, "    {} [label=\"{}\", kind=\"{}\"];",
+            node.id, escape_dot(&node.label), node.kind)?;
+    }
+    for edge in graph.edges.iter() {
+        writeln!(dot, "    {} -> {} [label=\"{}\", subgraph=\"{}\"{}];",
+            edge.source, edge.target, escape_dot(&edge.label), edge.subgraph.as_str(),
+            if edge.directed { "" } else { ", dir=none" })?;
+    }
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+fn write_graphml(graph: &Graph) -> Result<String, std::fmt::Error> {
+    let mut graphml = String::new();
+    graphml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    graphml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    graphml.push_str(
+        "  <key id=\"label\" for=\"all\" attr.name=\"label\" attr.type=\"string\"/>\n");
+    graphml.push_str("  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n");
+    graphml.push_str(
+        "  <key id=\"subgraph\" for=\"edge\" attr.name=\"subgraph\" attr.type=\"string\"/>\n");
+    graphml.push_str("  <graph id=\"CompletionTracker\" edgedefault=\"directed\">\n");
+    for node in graph.nodes.iter() {
+        writeln!(graphml, "    <node id=\"{}\">", node.id)?;
+        writeln!(graphml, "      <data key=\"label\">{}</data>",
+            exports::escape_xml(&node.label))?;
+        writeln!(graphml, "      <data key=\"kind\">{}</data>", node.kind)?;
+        graphml.push_str("    </node>\n");
+    }
+    for (i, edge) in graph.edges.iter().enumerate() {
+        writeln!(graphml, "    <edge id=\"e{}\" source=\"{}\" target=\"{}\" directed=\"{}\">",
+            i, edge.source, edge.target, edge.directed)?;
+        writeln!(graphml, "      <data key=\"label\">{}</data>",
+            exports::escape_xml(&edge.label))?;
+        writeln!(graphml, "      <data key=\"subgraph\">{}</data>", edge.subgraph.as_str())?;
+        graphml.push_str("    </edge>\n");
+    }
+    graphml.push_str("  </graph>\n");
+    graphml.push_str("</graphml>\n");
+    Ok(graphml)
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
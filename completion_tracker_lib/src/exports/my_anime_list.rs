@@ -0,0 +1,93 @@
+//! Exports a tracker in the same XML that MyAnimeList exports (and imports).
+//! The anime and manga go into separate files, the same as MAL.
+
+use std::fmt::Write;
+
+use chrono::{NaiveDate};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    exports::{self, ExportEntry, MediaType},
+    sources::source_types::{SourceType, link_types},
+    tracking::{CompletionStatus},
+};
+
+/// Makes the whole XML file (animelist.xml or mangalist.xml)
+pub fn export(sources_db: &ConnectionHolder, tracker_db: &ConnectionHolder, media_type: MediaType)
+-> Result<String, Error> {
+    let export_type = match media_type {
+        MediaType::Anime => 1,
+        MediaType::Manga => 2,
+    };
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\n");
+    xml.push_str("<myanimelist>\n");
+    xml.push_str("\t<myinfo>\n");
+    writeln!(xml, "\t\t<user_export_type>{}</user_export_type>", export_type)?;
+    xml.push_str("\t</myinfo>\n");
+    for export_entry in exports::read_export_entries(sources_db, tracker_db, Some(media_type))? {
+        if let Some(status) = export_entry.entry.current_status() {
+            write_entry(&mut xml, &export_entry, media_type, status)?;
+        }
+    }
+    xml.push_str("</myanimelist>\n");
+    Ok(xml)
+}
+
+fn write_entry(xml: &mut String, export_entry: &ExportEntry, media_type: MediaType,
+status: CompletionStatus) -> std::fmt::Result {
+    let entry = &export_entry.entry;
+    // MAL uses 0 for anything that it doesn't have an ID for
+    let mal_id = export_entry.external_id(link_types::MY_ANIME_LIST).unwrap_or(0);
+    let start_date = entry.status_updates.get(&CompletionStatus::InProgress)
+        .and_then(|time| time.date);
+    let finish_date = entry.status_updates.iter()
+        .find(|(status, _)| status.is_finished())
+        .and_then(|(_, time)| time.date);
+    let finished = status.is_finished();
+
+    match media_type {
+        MediaType::Anime => {
+            xml.push_str("\t<anime>\n");
+            writeln!(xml, "\t\t<series_animedb_id>{}</series_animedb_id>", mal_id)?;
+            writeln!(xml, "\t\t<series_title>{}</series_title>", cdata(export_entry.title()))?;
+            writeln!(xml, "\t\t<series_type>{}</series_type>", match export_entry.source_type {
+                SourceType::Movie => "Movie",
+                _ => "TV",
+            })?;
+            writeln!(xml, "\t\t<my_status>{}</my_status>",
+                if finished { "Completed" } else { "Watching" })?;
+            writeln!(xml, "\t\t<my_times_watched>{}</my_times_watched>", entry.times_repeated)?;
+        },
+        MediaType::Manga => {
+            xml.push_str("\t<manga>\n");
+            writeln!(xml, "\t\t<manga_mangadb_id>{}</manga_mangadb_id>", mal_id)?;
+            writeln!(xml, "\t\t<manga_title>{}</manga_title>", cdata(export_entry.title()))?;
+            writeln!(xml, "\t\t<my_status>{}</my_status>",
+                if finished { "Completed" } else { "Reading" })?;
+            writeln!(xml, "\t\t<my_times_read>{}</my_times_read>", entry.times_repeated)?;
+        },
+    }
+    writeln!(xml, "\t\t<my_start_date>{}</my_start_date>", mal_date(start_date))?;
+    writeln!(xml, "\t\t<my_finish_date>{}</my_finish_date>", mal_date(finish_date))?;
+    writeln!(xml, "\t\t<my_comments>{}</my_comments>", cdata(&entry.description))?;
+    xml.push_str("\t\t<update_on_import>1</update_on_import>\n");
+    match media_type {
+        MediaType::Anime => xml.push_str("\t</anime>\n"),
+        MediaType::Manga => xml.push_str("\t</manga>\n"),
+    }
+    Ok(())
+}
+
+/// MAL writes the days that it doesn't know as all zeros
+fn mal_date(date: Option<NaiveDate>) -> String {
+    date.map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "0000-00-00".to_string())
+}
+
+/// Wraps the text in CDATA, splitting up anything that would end it early
+fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
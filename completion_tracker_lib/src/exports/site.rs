@@ -0,0 +1,714 @@
+//! Renders the whole catalogue and the trackers' completions into static HTML pages.
+//! Every link between the pages is relative, so the folder can be browsed straight from the disk.
+
+use std::{
+    collections::{BTreeMap},
+    fmt::Write,
+    fs,
+    path::{Path},
+};
+
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    TrackerDBs,
+    db_link::{ConnectionHolder},
+    error::{Error, ResultExt},
+    exports::{self, escape_html},
+    simple_enum::{SimpleEnum},
+    sources::{
+        api::{DBSourceRelation},
+        backup::{self, SourcesBackup},
+        source_types::{Lang, LangMap, LangMapList, PersonRole, Relation},
+    },
+};
+
+/// The folder of every kind of page, which is also the first part of its path
+const UNIVERSE_TAGS_FOLDER: &str = "universeTags";
+const SOURCES_FOLDER: &str = "sources";
+const PEOPLE_FOLDER: &str = "people";
+const CHARACTERS_FOLDER: &str = "characters";
+const COMPANIES_FOLDER: &str = "companies";
+const TRACKERS_FOLDER: &str = "trackers";
+
+/// How many pages of each kind were written
+#[derive(Default, Deserialize, Serialize, JsonSchema)]
+pub struct SiteReport {
+    pub universe_tags: usize,
+    pub sources: usize,
+    pub people: usize,
+    pub characters: usize,
+    pub companies: usize,
+    pub trackers: usize,
+}
+
+/// Writes every page into the folder, along with the CSS that they all share.
+/// The pages use the language for their names, or another language when it's missing.
+pub fn export(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs, lang: Lang, css: &str,
+site_folder: impl AsRef<Path>) -> Result<SiteReport, Error> {
+    let site_folder = site_folder.as_ref();
+    let site = Site::new(backup::export(sources_db)?, lang);
+
+    for folder in &[UNIVERSE_TAGS_FOLDER, SOURCES_FOLDER, PEOPLE_FOLDER, CHARACTERS_FOLDER,
+        COMPANIES_FOLDER, TRACKERS_FOLDER] {
+        fs::create_dir_all(site_folder.join(folder))
+            .with_context(|| format!("Failed to make the {} folder", folder))?;
+    }
+    write_file(&site_folder.join("style.css"), css)?;
+
+    let mut report = SiteReport::default();
+    for universe_tag in site.backup.universe_tags.iter() {
+        let page = site.universe_tag_page(universe_tag.id)?;
+        write_page(site_folder, UNIVERSE_TAGS_FOLDER, universe_tag.id, &page)?;
+        report.universe_tags += 1;
+    }
+    for source in site.backup.sources.iter() {
+        let page = site.source_page(source.id)?;
+        write_page(site_folder, SOURCES_FOLDER, source.id, &page)?;
+        report.sources += 1;
+    }
+    for person in site.backup.people.iter() {
+        let page = site.person_page(person.id)?;
+        write_page(site_folder, PEOPLE_FOLDER, person.id, &page)?;
+        report.people += 1;
+    }
+    for character in site.backup.characters.iter() {
+        let page = site.character_page(character.id)?;
+        write_page(site_folder, CHARACTERS_FOLDER, character.id, &page)?;
+        report.characters += 1;
+    }
+    for company in site.backup.companies.iter() {
+        let page = site.company_page(company.id)?;
+        write_page(site_folder, COMPANIES_FOLDER, company.id, &page)?;
+        report.companies += 1;
+    }
+    // The tracker names can have anything in them, so the pages are numbered instead
+    for (tracker_number, (tracker_name, tracker_db)) in tracker_dbs.iter().enumerate() {
+        let page = site.tracker_page(sources_db, tracker_db, tracker_name)?;
+        write_page(site_folder, TRACKERS_FOLDER, tracker_number as u64 + 1, &page)?;
+        report.trackers += 1;
+    }
+
+    let index_page = site.index_page(tracker_dbs)?;
+    write_file(&site_folder.join("index.html"), &index_page)?;
+    Ok(report)
+}
+
+fn write_file(file: &Path, data: &str) -> Result<(), Error> {
+    fs::write(file, data)
+        .with_context(|| format!("Failed to write {}", file.display()))
+}
+
+fn write_page(site_folder: &Path, folder: &str, id: u64, page: &str) -> Result<(), Error> {
+    write_file(&site_folder.join(folder).join(format!("{}.html", id)), page)
+}
+
+/// Everything that a page could link to, found by its ID
+struct Site {
+    backup: SourcesBackup,
+    lang: Lang,
+    universe_tag_names: BTreeMap<u64, LangMap>,
+    source_names: BTreeMap<u64, LangMap>,
+    person_names: BTreeMap<u64, LangMap>,
+    character_names: BTreeMap<u64, LangMap>,
+    company_names: BTreeMap<u64, LangMap>,
+}
+impl Site {
+    fn new(backup: SourcesBackup, lang: Lang) -> Site {
+        Site {
+            universe_tag_names: backup.universe_tags.iter()
+                .map(|universe_tag| (universe_tag.id, universe_tag.names.clone()))
+                .collect(),
+            source_names: backup.sources.iter()
+                .map(|source| (source.id, source.names.clone()))
+                .collect(),
+            person_names: backup.people.iter()
+                .map(|person| (person.id, person.names.clone()))
+                .collect(),
+            character_names: backup.characters.iter()
+                .map(|character| (character.id, character.names.clone()))
+                .collect(),
+            company_names: backup.companies.iter()
+                .map(|company| (company.id, company.names.clone()))
+                .collect(),
+            backup,
+            lang,
+        }
+    }
+
+    fn name<'a>(&self, names: &'a LangMap) -> &'a str {
+        exports::lang_map_get(names, self.lang)
+    }
+
+    /// A link from a page in one of the folders to another page
+    fn link(&self, folder: &str, id: u64, names: &BTreeMap<u64, LangMap>) -> String {
+        match names.get(&id) {
+            Some(item_names) => format!("<a href=\"../{}/{}.html\">{}</a>",
+                folder, id, escape_html(self.name(item_names))),
+            // Only the pages that exist get linked to
+            None => format!("#{}", id),
+        }
+    }
+    fn universe_tag_link(&self, id: u64) -> String {
+        self.link(UNIVERSE_TAGS_FOLDER, id, &self.universe_tag_names)
+    }
+    fn source_link(&self, id: u64) -> String {
+        self.link(SOURCES_FOLDER, id, &self.source_names)
+    }
+    fn person_link(&self, id: u64) -> String {
+        self.link(PEOPLE_FOLDER, id, &self.person_names)
+    }
+    fn character_link(&self, id: u64) -> String {
+        self.link(CHARACTERS_FOLDER, id, &self.character_names)
+    }
+    fn company_link(&self, id: u64) -> String {
+        self.link(COMPANIES_FOLDER, id, &self.company_names)
+    }
+
+    fn index_page(&self, tracker_dbs: &TrackerDBs) -> Result<String, std::fmt::Error> {
+        let mut page = Page::new("Completion Tracker", "", self.lang);
+
+        page.heading("Trackers");
+        let tracker_links = tracker_dbs.keys().enumerate()
+            .map(|(tracker_number, tracker_name)| format!("<a href=\"{}/{}.html\">{}</a>",
+                TRACKERS_FOLDER, tracker_number + 1, escape_html(tracker_name)))
+            .collect();
+        page.list(tracker_links)?;
+
+        // The index is at the top, so its links don't go up a folder like the others
+        let index_links = |folder: &str, names: &BTreeMap<u64, LangMap>| names.iter()
+            .map(|(id, item_names)| format!("<a href=\"{}/{}.html\">{}</a>",
+                folder, id, escape_html(self.name(item_names))))
+            .collect::<Vec<String>>();
+        page.heading("Universe Tags");
+        page.list(index_links(UNIVERSE_TAGS_FOLDER, &self.universe_tag_names))?;
+        page.heading("Sources");
+        page.list(index_links(SOURCES_FOLDER, &self.source_names))?;
+        page.heading("People");
+        page.list(index_links(PEOPLE_FOLDER, &self.person_names))?;
+        page.heading("Characters");
+        page.list(index_links(CHARACTERS_FOLDER, &self.character_names))?;
+        page.heading("Companies");
+        page.list(index_links(COMPANIES_FOLDER, &self.company_names))?;
+        Ok(page.finish())
+    }
+
+    fn universe_tag_page(&self, id: u64) -> Result<String, std::fmt::Error> {
+        let universe_tag = self.backup.universe_tags.iter()
+            .find(|universe_tag| universe_tag.id == id)
+            .expect("The Universe Tag came from the backup");
+        let mut page = Page::new(self.name(&universe_tag.names), "../", self.lang);
+        page.item_details(&universe_tag.names, &universe_tag.descriptions, None)?;
+
+        let parents = self.backup.universe_tags.iter()
+            .filter(|parent| parent.children.contains(&id))
+            .map(|parent| self.universe_tag_link(parent.id))
+            .collect();
+        page.section("Parents", parents)?;
+        let children = universe_tag.children.iter()
+            .map(|child_id| self.universe_tag_link(*child_id))
+            .collect();
+        page.section("Children", children)?;
+
+        let mut related = Vec::new();
+        for (related_id, relation) in universe_tag.related_universe_tags.iter() {
+            related.push(format!("{}: {}", relation.as_str(), self.universe_tag_link(*related_id)));
+        }
+        for other in self.backup.universe_tags.iter() {
+            for (related_id, relation) in other.related_universe_tags.iter() {
+                if *related_id == id {
+                    related.push(format!("{}: {}",
+                        other_side(*relation).as_str(), self.universe_tag_link(other.id)));
+                }
+            }
+        }
+        page.section("Related Universe Tags", related)?;
+
+        let sources = self.backup.sources.iter()
+            .filter(|source| source.universe_tags.contains(&id))
+            .map(|source| self.source_link(source.id))
+            .collect();
+        page.section("Sources", sources)?;
+        page.related_links(&universe_tag.related_links, self.lang)?;
+        Ok(page.finish())
+    }
+
+    fn source_page(&self, id: u64) -> Result<String, std::fmt::Error> {
+        let source = self.backup.sources.iter()
+            .find(|source| source.id == id)
+            .expect("The Source came from the backup");
+        let mut page = Page::new(self.name(&source.names), "../", self.lang);
+        page.item_details(&source.names, &source.descriptions, None)?;
+        page.field("Source Type", source.source_type.as_str())?;
+
+        let universe_tags = source.universe_tags.iter()
+            .map(|universe_tag_id| self.universe_tag_link(*universe_tag_id))
+            .collect();
+        page.section("Universe Tags", universe_tags)?;
+        let dates = source.dates.iter()
+            .map(|(date, descriptions)| format!("{}: {}",
+                date.format("%Y-%m-%d"), escape_html(self.name(descriptions))))
+            .collect();
+        page.section("Dates", dates)?;
+
+        let mut related = Vec::new();
+        for (related_id, relation) in source.related_sources.iter() {
+            related.push(format!("{}: {}", relation.as_str(), self.source_link(*related_id)));
+        }
+        for other in self.backup.sources.iter() {
+            for (related_id, relation) in other.related_sources.iter() {
+                if *related_id == id {
+                    related.push(format!("{}: {}",
+                        other_side(*relation).as_str(), self.source_link(other.id)));
+                }
+            }
+        }
+        page.section("Related Sources", related)?;
+
+        let people = source.people.iter()
+            .map(|(person_id, role)| format!("{}: {}",
+                self.role_text(role), self.person_link(*person_id)))
+            .collect();
+        page.section("People", people)?;
+        let characters = source.characters.iter()
+            .map(|character_id| self.character_link(*character_id))
+            .collect();
+        page.section("Characters", characters)?;
+        let companies = source.companies.iter()
+            .map(|(company_id, role)| match role {
+                Some(role) => format!("{}: {}", role.as_str(), self.company_link(*company_id)),
+                None => self.company_link(*company_id),
+            })
+            .collect();
+        page.section("Companies", companies)?;
+        page.related_links(&source.related_links, self.lang)?;
+        Ok(page.finish())
+    }
+
+    fn person_page(&self, id: u64) -> Result<String, std::fmt::Error> {
+        let person = self.backup.people.iter()
+            .find(|person| person.id == id)
+            .expect("The Person came from the backup");
+        let mut page = Page::new(self.name(&person.names), "../", self.lang);
+        page.item_details(&person.names, &person.descriptions, Some(&person.aliases))?;
+        if let Some(birth_country) = person.birth_country {
+            page.field("Birth Country", birth_country.as_str())?;
+        }
+        if let Some(birth_date) = person.birth_date {
+            page.field("Birth Date", &birth_date.format("%Y-%m-%d").to_string())?;
+        }
+        if let Some(death_date) = person.death_date {
+            page.field("Death Date", &death_date.format("%Y-%m-%d").to_string())?;
+        }
+
+        let mut sources = Vec::new();
+        for source in self.backup.sources.iter() {
+            for (person_id, role) in source.people.iter() {
+                if *person_id == id {
+                    sources.push(format!("{}: {}",
+                        self.role_text(role), self.source_link(source.id)));
+                }
+            }
+        }
+        page.section("Sources", sources)?;
+        Ok(page.finish())
+    }
+
+    fn character_page(&self, id: u64) -> Result<String, std::fmt::Error> {
+        let character = self.backup.characters.iter()
+            .find(|character| character.id == id)
+            .expect("The Character came from the backup");
+        let mut page = Page::new(self.name(&character.names), "../", self.lang);
+        page.item_details(&character.names, &character.descriptions, Some(&character.aliases))?;
+
+        // The relations are described the same way from both sides
+        let mut related = Vec::new();
+        for (related_id, descriptions) in character.related_characters.iter() {
+            related.push(format!("{}: {}",
+                escape_html(self.name(descriptions)), self.character_link(*related_id)));
+        }
+        for other in self.backup.characters.iter() {
+            for (related_id, descriptions) in other.related_characters.iter() {
+                if *related_id == id {
+                    related.push(format!("{}: {}",
+                        escape_html(self.name(descriptions)), self.character_link(other.id)));
+                }
+            }
+        }
+        page.section("Related Characters", related)?;
+
+        let sources = self.backup.sources.iter()
+            .filter(|source| source.characters.contains(&id))
+            .map(|source| self.source_link(source.id))
+            .collect();
+        page.section("Sources", sources)?;
+        let mut voice_actors = Vec::new();
+        for source in self.backup.sources.iter() {
+            for (person_id, role) in source.people.iter() {
+                if let PersonRole::VoiceActor(character_id, lang) = role {
+                    if *character_id == id {
+                        voice_actors.push(format!("{} ({}) in {}", self.person_link(*person_id),
+                            lang.as_str(), self.source_link(source.id)));
+                    }
+                }
+            }
+        }
+        page.section("Voice Actors", voice_actors)?;
+        Ok(page.finish())
+    }
+
+    fn company_page(&self, id: u64) -> Result<String, std::fmt::Error> {
+        let company = self.backup.companies.iter()
+            .find(|company| company.id == id)
+            .expect("The Company came from the backup");
+        let mut page = Page::new(self.name(&company.names), "../", self.lang);
+        page.item_details(&company.names, &company.descriptions, None)?;
+        if let Some(country) = company.country {
+            page.field("Country", country.as_str())?;
+        }
+
+        let mut sources = Vec::new();
+        for source in self.backup.sources.iter() {
+            for (company_id, role) in source.companies.iter() {
+                if *company_id == id {
+                    sources.push(match role {
+                        Some(role) => format!("{}: {}", role.as_str(), self.source_link(source.id)),
+                        None => self.source_link(source.id),
+                    });
+                }
+            }
+        }
+        page.section("Sources", sources)?;
+        Ok(page.finish())
+    }
+
+    /// Every entry of the tracker as a table, with the most recently updated first
+    fn tracker_page(&self, sources_db: &ConnectionHolder, tracker_db: &ConnectionHolder,
+    tracker_name: &str) -> Result<String, Error> {
+        let mut export_entries = exports::read_export_entries(sources_db, tracker_db, None)?;
+        export_entries.sort_by(|entry1, entry2| entry2.entry.last_updated
+            .cmp(&entry1.entry.last_updated)
+            .then(entry1.entry.source_id.cmp(&entry2.entry.source_id))
+        );
+
+        let mut page = Page::new(tracker_name, "../", self.lang);
+        let mut rows = Vec::with_capacity(export_entries.len());
+        for export_entry in export_entries.iter() {
+            let entry = &export_entry.entry;
+            let status_dates: Vec<String> = entry.status_updates.iter()
+                .map(|(status, time)| match time.date {
+                    Some(date) => format!("{} {}", status.as_str(), date.format("%Y-%m-%d")),
+                    None => status.as_str().to_string(),
+                })
+                .collect();
+            rows.push(vec![
+                self.source_link(entry.source_id),
+                export_entry.source_type.as_str().to_string(),
+                entry.current_status().map(|status| status.as_str()).unwrap_or("").to_string(),
+                escape_html(&status_dates.join(", ")),
+                entry.times_repeated.to_string(),
+                escape_html(&entry.description),
+            ]);
+        }
+        page.table(
+            &["Source", "Source Type", "Status", "Dates", "Times Repeated", "Notes"],
+            rows,
+        )?;
+        Ok(page.finish())
+    }
+
+    fn role_text(&self, role: &PersonRole) -> String {
+        match role {
+            PersonRole::VoiceActor(character_id, lang) => format!("{} of {} ({})",
+                role.db_name(), self.character_link(*character_id), lang.as_str()),
+            _ => role.db_name().to_string(),
+        }
+    }
+}
+
+/// The relation as seen from the item in the 2nd spot
+fn other_side(relation: Relation) -> Relation {
+    let (first_spot, db_relation) = DBSourceRelation::normalize(relation);
+    db_relation.denormalize(!first_spot)
+}
+
+/// Builds up the HTML of a single page
+struct Page {
+    html: String,
+}
+impl Page {
+    /// root is how to get from the page's folder back to the top of the site
+    fn new(title: &str, root: &str, lang: Lang) -> Page {
+        let html_lang = match lang {
+            Lang::English => "en",
+            Lang::Japanese => "ja",
+        };
+        let title = escape_html(title);
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n");
+        html.push_str(&format!("<html lang=\"{}\">\n", html_lang));
+        html.push_str("<head>\n");
+        html.push_str("<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>{}</title>\n", title));
+        html.push_str(&format!("<link rel=\"stylesheet\" href=\"{}style.css\">\n", root));
+        html.push_str("</head>\n");
+        html.push_str("<body>\n");
+        html.push_str(
+            &format!("<nav><a href=\"{}index.html\">Completion Tracker</a></nav>\n", root));
+        html.push_str(&format!("<h1>{}</h1>\n", title));
+        Page { html }
+    }
+
+    fn heading(&mut self, heading: &str) {
+        self.html.push_str(&format!("<h2 class=\"tableTitle\">{}</h2>\n", escape_html(heading)));
+    }
+
+    /// The items must already be HTML
+    fn list(&mut self, items: Vec<String>) -> std::fmt::Result {
+        self.html.push_str("<ul>\n");
+        for item in items {
+            writeln!(self.html, "<li>{}</li>", item)?;
+        }
+        self.html.push_str("</ul>\n");
+        Ok(())
+    }
+
+    /// A heading with its list, which is left out when there's nothing in the list
+    fn section(&mut self, heading: &str, items: Vec<String>) -> std::fmt::Result {
+        if items.is_empty() {
+            return Ok(());
+        }
+        self.heading(heading);
+        self.list(items)
+    }
+
+    fn field(&mut self, name: &str, value: &str) -> std::fmt::Result {
+        writeln!(self.html, "<div class=\"formGrid2Col\"><b>{}</b><span>{}</span></div>",
+            escape_html(name), escape_html(value))
+    }
+
+    /// The names in every language, the aliases and the description
+    fn item_details(&mut self, names: &LangMap, descriptions: &LangMap,
+    aliases: Option<&LangMapList>) -> std::fmt::Result {
+        self.html.push_str("<div class=\"formGrid2Col\">\n");
+        for (lang, name) in names.iter() {
+            writeln!(self.html, "<b>{}</b><span>{}</span>", lang.as_str(), escape_html(name))?;
+        }
+        for (lang, lang_aliases) in aliases.into_iter().flatten() {
+            writeln!(self.html, "<b>{} Aliases</b><span>{}</span>",
+                lang.as_str(), escape_html(&lang_aliases.join(", ")))?;
+        }
+        self.html.push_str("</div>\n");
+        for description in descriptions.values() {
+            writeln!(self.html, "<p>{}</p>", escape_html(description))?;
+        }
+        Ok(())
+    }
+
+    fn related_links(&mut self, related_links: &[(String, LangMap)], lang: Lang)
+    -> std::fmt::Result {
+        let links = related_links.iter()
+            .map(|(url, descriptions)| {
+                let description = exports::lang_map_get(descriptions, lang);
+                let text = if description.is_empty() { url.as_str() } else { description };
+                format!("<a href=\"{}\">{}</a>", escape_html(url), escape_html(text))
+            })
+            .collect();
+        self.section("Related Links", links)
+    }
+
+    /// The cells must already be HTML
+    fn table(&mut self, headers: &[&str], rows: Vec<Vec<String>>) -> std::fmt::Result {
+        self.html.push_str("<table>\n<tr>");
+        for header in headers {
+            write!(self.html, "<th>{}</th>", escape_html(header))?;
+        }
+        self.html.push_str("</tr>\n");
+        for row in rows {
+            self.html.push_str("<tr>");
+            for cell in row {
+                write!(self.html, "<td>{}</td>", cell)?;
+            }
+            self.html.push_str("</tr>\n");
+        }
+        self.html.push_str("</table>\n");
+        Ok(())
+    }
+
+    fn finish(mut self) -> String {
+        self.html.push_str("</body>\n</html>\n");
+        self.html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{Connection};
+
+    use super::*;
+    use crate::{
+        sources::{
+            backup::{
+                BACKUP_VERSION, CharacterBackup, CompanyBackup, PersonBackup, SourceBackup,
+                UniverseTagBackup,
+            },
+            source_types::{CompanyRole, SourceType},
+        },
+        tracking,
+    };
+
+    fn names(english: &str) -> LangMap {
+        let mut names = LangMap::new();
+        names.insert(Lang::English, english.to_string());
+        names
+    }
+
+    /// Spice and Wolf has everything linked to it, and its anime is after it.
+    /// Its tags are Fate and a tag (99) that isn't there.
+    fn make_site() -> Site {
+        let universe_tag = |id: u64, name: &str, related_universe_tags| UniverseTagBackup {
+            id,
+            names: names(name),
+            descriptions: LangMap::new(),
+            related_links: Vec::new(),
+            children: Vec::new(),
+            related_universe_tags,
+        };
+        let source = |id: u64, name: &str, related_sources| SourceBackup {
+            id,
+            names: names(name),
+            descriptions: LangMap::new(),
+            source_type: SourceType::Novel,
+            related_links: Vec::new(),
+            dates: Vec::new(),
+            universe_tags: Vec::new(),
+            related_sources,
+            people: Vec::new(),
+            characters: Vec::new(),
+            companies: Vec::new(),
+        };
+
+        let mut spice_and_wolf = source(1, "Spice & Wolf <Novel>", Vec::new());
+        spice_and_wolf.descriptions = names("Holo's \"journey\"");
+        spice_and_wolf.universe_tags = vec![1, 99];
+        spice_and_wolf.people = vec![(1, PersonRole::Writer)];
+        spice_and_wolf.characters = vec![1];
+        spice_and_wolf.companies = vec![(1, Some(CompanyRole::Publisher))];
+        let anime = source(2, "Spice and Wolf", vec![(1, Relation::After)]);
+
+        Site::new(SourcesBackup {
+            version: BACKUP_VERSION,
+            universe_tags: vec![
+                universe_tag(1, "Fate", Vec::new()),
+                universe_tag(2, "Tsukihime", vec![(1, Relation::Alternate)]),
+            ],
+            people: vec![PersonBackup {
+                id: 1,
+                names: names("Isuna Hasekura"),
+                descriptions: LangMap::new(),
+                aliases: LangMapList::new(),
+                birth_country: None,
+                birth_date: None,
+                death_date: None,
+            }],
+            companies: vec![CompanyBackup {
+                id: 1,
+                names: names("ASCII Media Works"),
+                descriptions: LangMap::new(),
+                country: None,
+            }],
+            characters: vec![
+                CharacterBackup {
+                    id: 1,
+                    names: names("Holo"),
+                    descriptions: LangMap::new(),
+                    aliases: LangMapList::new(),
+                    related_characters: vec![(2, names("Partner"))],
+                },
+                CharacterBackup {
+                    id: 2,
+                    names: names("Lawrence"),
+                    descriptions: LangMap::new(),
+                    aliases: LangMapList::new(),
+                    related_characters: Vec::new(),
+                },
+            ],
+            sources: vec![spice_and_wolf, anime],
+        }, Lang::English)
+    }
+
+    fn make_tracker_db() -> ConnectionHolder {
+        let connection = Connection::open_in_memory().unwrap();
+        tracking::create_tables(&connection).unwrap();
+        ConnectionHolder::new(connection)
+    }
+
+    #[test]
+    fn names_and_descriptions_are_escaped() {
+        let site = make_site();
+        let page = site.source_page(1).unwrap();
+        assert!(page.contains("<title>Spice &amp; Wolf &lt;Novel&gt;</title>"));
+        assert!(page.contains("<h1>Spice &amp; Wolf &lt;Novel&gt;</h1>"));
+        assert!(page.contains("<p>Holo&#39;s &quot;journey&quot;</p>"));
+        assert!(!page.contains("<Novel>"));
+
+        // The links to it are escaped too
+        let page = site.source_page(2).unwrap();
+        assert!(page.contains(">Spice &amp; Wolf &lt;Novel&gt;</a>"));
+    }
+
+    #[test]
+    fn tracker_names_are_escaped() {
+        let site = make_site();
+        let mut tracker_dbs = TrackerDBs::new();
+        tracker_dbs.insert("Books & <Anime>".to_string(), make_tracker_db());
+
+        let page = site.index_page(&tracker_dbs).unwrap();
+        assert!(page.contains("<a href=\"trackers/1.html\">Books &amp; &lt;Anime&gt;</a>"));
+        let sources_db = crate::init_source_db().unwrap();
+        let page = site.tracker_page(&sources_db, &tracker_dbs["Books & <Anime>"],
+            "Books & <Anime>").unwrap();
+        assert!(page.contains("<h1>Books &amp; &lt;Anime&gt;</h1>"));
+    }
+
+    #[test]
+    fn pages_link_to_every_kind_of_item() {
+        let site = make_site();
+        let page = site.source_page(1).unwrap();
+        assert!(page.contains("<li><a href=\"../universeTags/1.html\">Fate</a></li>"));
+        assert!(page.contains("<li>Writer: <a href=\"../people/1.html\">Isuna Hasekura</a></li>"));
+        assert!(page.contains("<li><a href=\"../characters/1.html\">Holo</a></li>"));
+        assert!(page.contains(
+            "<li>Publisher: <a href=\"../companies/1.html\">ASCII Media Works</a></li>"));
+
+        // And back again
+        let spice_and_wolf = "<a href=\"../sources/1.html\">Spice &amp; Wolf &lt;Novel&gt;</a>";
+        assert!(site.universe_tag_page(1).unwrap().contains(spice_and_wolf));
+        assert!(site.person_page(1).unwrap().contains(spice_and_wolf));
+        assert!(site.character_page(1).unwrap().contains(spice_and_wolf));
+        assert!(site.company_page(1).unwrap().contains(spice_and_wolf));
+    }
+
+    #[test]
+    fn relations_are_shown_from_both_sides() {
+        let site = make_site();
+        assert!(site.source_page(2).unwrap().contains("<li>After: <a href=\"../sources/1.html\">"));
+        // The Source in the 2nd spot sees the other side of the relation
+        assert!(site.source_page(1).unwrap()
+            .contains("<li>Before: <a href=\"../sources/2.html\">Spice and Wolf</a></li>"));
+
+        assert!(site.universe_tag_page(1).unwrap()
+            .contains("<li>Alternate: <a href=\"../universeTags/2.html\">Tsukihime</a></li>"));
+        assert!(site.character_page(2).unwrap()
+            .contains("<li>Partner: <a href=\"../characters/1.html\">Holo</a></li>"));
+    }
+
+    #[test]
+    fn missing_items_are_not_linked() {
+        let site = make_site();
+        let page = site.source_page(1).unwrap();
+        assert!(page.contains("<li>#99</li>"));
+        assert!(!page.contains("universeTags/99.html"));
+    }
+}
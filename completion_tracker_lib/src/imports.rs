@@ -0,0 +1,221 @@
+pub mod csv;
+pub mod my_anime_list;
+pub mod narou;
+pub mod wikipedia;
+
+use std::fmt;
+
+use rusqlite::{Connection, params};
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error},
+    simple_enum::{SimpleEnum},
+    sources::{
+        api,
+        source_types::{Lang, LangMap, SourceType},
+    },
+};
+
+/// What happened to every entry in an import
+#[derive(Default, Deserialize, Serialize, JsonSchema)]
+pub struct ImportReport {
+    /// Entries that were found in the sources DB
+    pub matched: Vec<ReportEntry>,
+    /// Entries that needed a new (stub) Source
+    pub created: Vec<ReportEntry>,
+    pub skipped: Vec<SkippedEntry>,
+}
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Matched {} entries", self.matched.len())?;
+        for entry in self.matched.iter() {
+            writeln!(f, "    {} (Source {})", entry.title, entry.source_id)?;
+        }
+        writeln!(f, "Created {} entries", self.created.len())?;
+        for entry in self.created.iter() {
+            writeln!(f, "    {} (Source {})", entry.title, entry.source_id)?;
+        }
+        writeln!(f, "Skipped {} entries", self.skipped.len())?;
+        for entry in self.skipped.iter() {
+            writeln!(f, "    {}: {}", entry.title, entry.reason)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct ReportEntry {
+    pub title: String,
+    pub source_id: u64,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct SkippedEntry {
+    pub title: String,
+    pub reason: String,
+}
+
+/// Finds the first Source that has the name (in any language) and one of the types.
+/// The names are compared without caring about ASCII case.
+pub(crate) fn find_source_by_name(db: &Connection, name: &str, source_types: &[SourceType])
+-> Result<Option<u64>, Error> {
+    let mut statement = db.prepare("
+        SELECT Sources.id, Sources.source_type FROM Sources
+            JOIN Strings ON Strings.id = Sources.names
+            WHERE lower(Strings.english) = lower(?1) OR lower(Strings.japanese) = lower(?1)
+            ORDER BY Sources.id
+    ")?;
+    let found = api::collect_query_map(statement.query_map(&[name], |row| {
+        let id: i64 = row.get(0)?;
+        let source_type: SourceType = row.get(1)?;
+        Ok( (id as u64, source_type) )
+    }))?;
+
+    Ok(found.into_iter()
+        .find(|(_, source_type)| source_types.iter()
+            .any(|wanted| wanted.as_str() == source_type.as_str())
+        )
+        .map(|(id, _)| id))
+}
+
+/// Creates a Source with only a name, so that it can be filled out later
+pub(crate) fn create_stub_source(db: &Connection, name: &str, source_type: SourceType,
+description: &str) -> Result<u64, Error> {
+    let mut names = LangMap::new();
+    names.insert(Lang::English, name.to_string());
+    let mut descriptions = LangMap::new();
+    descriptions.insert(Lang::English, description.to_string());
+
+    api::insert_new_lang_map(&names, db)?;
+    let names_id = db.last_insert_rowid();
+    api::insert_new_lang_map(&descriptions, db)?;
+    let descriptions_id = db.last_insert_rowid();
+
+    db.execute("INSERT INTO Sources (names, descriptions, source_type) VALUES (?, ?, ?)",
+        params![names_id, descriptions_id, source_type])?;
+    Ok(db.last_insert_rowid() as u64)
+}
+
+/// Finds the Person with the name or alias (in any language), without caring about ASCII case
+pub(crate) fn find_person(db: &Connection, name: &str) -> Result<Option<u64>, Error> {
+    let mut statement = db.prepare("
+        SELECT People.id FROM People
+            JOIN Strings ON Strings.id = People.names
+            WHERE lower(Strings.english) = lower(?1) OR lower(Strings.japanese) = lower(?1)
+        UNION
+        SELECT person_id FROM PersonAliases WHERE lower(alias) = lower(?1)
+        ORDER BY 1 LIMIT 1
+    ")?;
+    let found = api::collect_query_map(statement.query_map(&[name], |row| {
+        let id: i64 = row.get(0)?;
+        Ok(id as u64)
+    }))?;
+    Ok(found.into_iter().next())
+}
+
+/// Finds the Company with the name (in any language), without caring about ASCII case
+pub(crate) fn find_company(db: &Connection, name: &str) -> Result<Option<u64>, Error> {
+    let mut statement = db.prepare("
+        SELECT Companies.id FROM Companies
+            JOIN Strings ON Strings.id = Companies.names
+            WHERE lower(Strings.english) = lower(?1) OR lower(Strings.japanese) = lower(?1)
+            ORDER BY Companies.id LIMIT 1
+    ")?;
+    let found = api::collect_query_map(statement.query_map(&[name], |row| {
+        let id: i64 = row.get(0)?;
+        Ok(id as u64)
+    }))?;
+    Ok(found.into_iter().next())
+}
+
+/// Takes out everything from each start to the next end, including them
+pub(crate) fn remove_between(text: &str, start: &str, end: &str) -> String {
+    let mut cleaned = String::new();
+    let mut rest = text;
+    while let Some(start_index) = rest.find(start) {
+        cleaned.push_str(&rest[..start_index]);
+        rest = match rest[start_index..].find(end) {
+            Some(end_index) => &rest[start_index + end_index + end.len()..],
+            None => "",
+        };
+    }
+    cleaned.push_str(rest);
+    cleaned
+}
+
+/// The inside of the first tag (which can have attributes), without the tag itself
+pub(crate) fn find_tag<'a>(text: &'a str, open: &str, close: &str) -> Option<&'a str> {
+    let start = text.find(open)?;
+    let inside_start = start + text[start..].find('>')? + 1;
+    let inside_end = inside_start + text[inside_start..].find(close)?;
+    Some(&text[inside_start..inside_end])
+}
+
+/// Turns the HTML into plain text, with a line for every line break or list item
+pub(crate) fn html_to_text(html: &str) -> String {
+    let html = remove_between(html, "<sup", "</sup>");
+    let html = remove_between(&html, "<style", "</style>");
+    let mut text = String::new();
+    let mut rest = html.as_str();
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = rest[start + 1..end].to_lowercase();
+        if tag.starts_with("br") || tag.starts_with("li") || tag.starts_with("div") {
+            text.push('\n');
+        }
+        rest = &rest[end + 1..];
+    }
+    if !rest.contains('<') {
+        text.push_str(rest);
+    }
+
+    let text = text.replace("&nbsp;", " ")
+        .replace("&#160;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_lists_every_entry() {
+        let report = ImportReport {
+            matched: vec![ReportEntry { title: "Spice and Wolf".to_string(), source_id: 1 }],
+            created: vec![
+                ReportEntry { title: "Horizon".to_string(), source_id: 2 },
+                ReportEntry { title: "Mushishi".to_string(), source_id: 3 },
+            ],
+            skipped: vec![SkippedEntry {
+                title: "Unknown".to_string(),
+                reason: "There isn't a Source with that name".to_string(),
+            }],
+        };
+        assert_eq!(report.to_string(), concat!(
+            "Matched 1 entries\n",
+            "    Spice and Wolf (Source 1)\n",
+            "Created 2 entries\n",
+            "    Horizon (Source 2)\n",
+            "    Mushishi (Source 3)\n",
+            "Skipped 1 entries\n",
+            "    Unknown: There isn't a Source with that name\n",
+        ));
+        assert_eq!(ImportReport::default().to_string(),
+            "Matched 0 entries\nCreated 0 entries\nSkipped 0 entries\n");
+    }
+}
@@ -0,0 +1,391 @@
+//! Imports books from any CSV file, with a mapping that says which column is which.
+//! Goodreads exports are common enough that they have a built-in mapping.
+
+use std::collections::{BTreeMap};
+
+use chrono::{NaiveDate};
+use rusqlite::{Connection, params};
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    imports::{self, ImportReport, ReportEntry, SkippedEntry},
+    sources::{
+        api,
+        source_types::{Lang, LangMap, PersonRole, SourceType},
+    },
+    tracking::{
+        CompletionStatus,
+        entry::{self, StatusUpdate},
+    },
+};
+
+const STUB_DESCRIPTION: &str = "Imported from a CSV file";
+
+/// The mappings that are built in
+#[derive(Copy, Clone, Deserialize, Serialize, JsonSchema)]
+pub enum CsvPreset {
+    /// The "Export Library" CSV from https://www.goodreads.com/
+    Goodreads,
+}
+impl CsvPreset {
+    pub fn mapping(self) -> CsvColumnMapping {
+        match self {
+            Self::Goodreads => {
+                let mut status_values = BTreeMap::new();
+                status_values.insert("read".to_string(), CompletionStatus::Complete);
+                status_values.insert("currently-reading".to_string(), CompletionStatus::InProgress);
+
+                CsvColumnMapping {
+                    title: "Title".to_string(),
+                    strip_title_suffix: true,
+                    author: Some("Author".to_string()),
+                    additional_authors: Some("Additional Authors".to_string()),
+                    status: Some("Exclusive Shelf".to_string()),
+                    status_values,
+                    start_date: None,
+                    finish_date: Some("Date Read".to_string()),
+                    read_count: Some("Read Count".to_string()),
+                    notes: Some("Private Notes".to_string()),
+                    date_formats: default_date_formats(),
+                }
+            },
+        }
+    }
+}
+
+/// The names of the columns in the CSV header that have each part of a book.
+/// Any column that isn't mapped is left alone.
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
+pub struct CsvColumnMapping {
+    pub title: String,
+    /// Removes a "(...)" from the end of the titles, which is where Goodreads puts the series
+    #[serde(default)]
+    pub strip_title_suffix: bool,
+    pub author: Option<String>,
+    /// A column with more authors that are separated by commas
+    #[serde(default)]
+    pub additional_authors: Option<String>,
+    /// Rows are imported as Complete when there's no status column
+    #[serde(default)]
+    pub status: Option<String>,
+    /// The status that each value in the status column means.
+    /// Rows with any other value are skipped.
+    #[serde(default)]
+    pub status_values: BTreeMap<String, CompletionStatus>,
+    #[serde(default)]
+    pub start_date: Option<String>,
+    #[serde(default)]
+    pub finish_date: Option<String>,
+    /// How many times the book has been finished
+    #[serde(default)]
+    pub read_count: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// The chrono formats that the dates can be in. The first one that works is used.
+    #[serde(default = "default_date_formats")]
+    pub date_formats: Vec<String>,
+}
+
+fn default_date_formats() -> Vec<String> {
+    vec!["%Y/%m/%d".to_string(), "%Y-%m-%d".to_string()]
+}
+
+/// The index of each mapped column in the CSV
+struct ColumnIndices {
+    title: usize,
+    author: Option<usize>,
+    additional_authors: Option<usize>,
+    status: Option<usize>,
+    start_date: Option<usize>,
+    finish_date: Option<usize>,
+    read_count: Option<usize>,
+    notes: Option<usize>,
+}
+impl ColumnIndices {
+    fn new(header: &[String], mapping: &CsvColumnMapping) -> Result<ColumnIndices, Error> {
+        let find = |column: &Option<String>| -> Result<Option<usize>, Error> {
+            match column {
+                Some(column) => header.iter()
+                    .position(|name| name.trim() == column)
+                    .map(Some)
+                    .ok_or_else(|| Error::Validation(
+                        format!("The CSV doesn't have a {} column", column))),
+                None => Ok(None),
+            }
+        };
+
+        Ok(ColumnIndices {
+            title: find(&Some(mapping.title.clone()))?.unwrap(),
+            author: find(&mapping.author)?,
+            additional_authors: find(&mapping.additional_authors)?,
+            status: find(&mapping.status)?,
+            start_date: find(&mapping.start_date)?,
+            finish_date: find(&mapping.finish_date)?,
+            read_count: find(&mapping.read_count)?,
+            notes: find(&mapping.notes)?,
+        })
+    }
+}
+
+/// Imports every book in the CSV into the tracker.
+/// A book matches a Novel when the title and one of the authors are the same.
+/// If the tracker can't be saved after the new books were, importing again matches the books.
+pub fn import(sources_db: &ConnectionHolder, tracker_db: &ConnectionHolder, csv: &str,
+mapping: &CsvColumnMapping) -> Result<ImportReport, Error> {
+    let mut rows = parse_csv(csv)?.into_iter();
+    let header = rows.next()
+        .ok_or_else(|| Error::Validation("The CSV is empty".to_string()))?;
+    let columns = ColumnIndices::new(&header, mapping)?;
+
+    let mut sources_db = sources_db.lock();
+    let mut tracker_db = tracker_db.lock();
+    let sources_transaction = sources_db.transaction()?;
+    let tracker_transaction = tracker_db.transaction()?;
+
+    let mut report = ImportReport::default();
+    for row in rows {
+        let cell = |index: Option<usize>| -> &str {
+            index.and_then(|index| row.get(index))
+                .map(|value| value.trim())
+                .unwrap_or("")
+        };
+        let date = |index: Option<usize>| -> Option<NaiveDate> {
+            let value = cell(index);
+            mapping.date_formats.iter()
+                .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+        };
+
+        let mut title = cell(Some(columns.title)).to_string();
+        if mapping.strip_title_suffix && title.ends_with(')') {
+            if let Some(start) = title.rfind(" (") {
+                title.truncate(start);
+            }
+        }
+        if title.is_empty() {
+            report.skipped.push(SkippedEntry {
+                title,
+                reason: "There isn't a title".to_string(),
+            });
+            continue;
+        }
+
+        let status = match columns.status {
+            Some(_) => match mapping.status_values.get(cell(columns.status)) {
+                Some(status) => *status,
+                None => {
+                    report.skipped.push(SkippedEntry {
+                        reason: format!("The status {} isn't mapped", cell(columns.status)),
+                        title,
+                    });
+                    continue;
+                },
+            },
+            None => CompletionStatus::Complete,
+        };
+
+        let mut authors: Vec<&str> = Vec::new();
+        if !cell(columns.author).is_empty() {
+            authors.push(cell(columns.author));
+        }
+        authors.extend(cell(columns.additional_authors).split(',')
+            .map(str::trim)
+            .filter(|author| !author.is_empty()));
+
+        let source_id = match find_book(&sources_transaction, &title, &authors)? {
+            Some(source_id) => {
+                report.matched.push(ReportEntry { title, source_id });
+                source_id
+            },
+            None => {
+                let source_id = create_book(&sources_transaction, &title, &authors)?;
+                report.created.push(ReportEntry { title, source_id });
+                source_id
+            },
+        };
+
+        let start_date = date(columns.start_date);
+        if status.is_finished() && start_date.is_some() {
+            entry::update_status_with(&tracker_transaction, &StatusUpdate {
+                source_id,
+                status: CompletionStatus::InProgress,
+                date: start_date,
+                play_time: None,
+            })?;
+        }
+        entry::update_status_with(&tracker_transaction, &StatusUpdate {
+            source_id,
+            status,
+            date: if status.is_finished() { date(columns.finish_date) } else { start_date },
+            play_time: None,
+        })?;
+
+        let read_count: u32 = cell(columns.read_count).parse().unwrap_or(0);
+        if read_count > 1 {
+            entry::set_times_repeated(&tracker_transaction, source_id, read_count - 1)?;
+        }
+        let notes = cell(columns.notes);
+        if !notes.is_empty() {
+            entry::set_description(&tracker_transaction, source_id, notes)?;
+        }
+    }
+
+    // The tracker has to go last, so its entries never point at Sources that weren't saved
+    sources_transaction.commit()?;
+    tracker_transaction.commit()?;
+    Ok(report)
+}
+
+/// Finds a Novel with the title that was written by one of the authors.
+/// Without any authors, only the title needs to match.
+fn find_book(db: &Connection, title: &str, authors: &[&str]) -> Result<Option<u64>, Error> {
+    if authors.is_empty() {
+        return imports::find_source_by_name(db, title, &[SourceType::Novel]);
+    }
+
+    let mut statement = db.prepare("
+        SELECT Sources.id FROM Sources
+            JOIN Strings AS SourceNames ON SourceNames.id = Sources.names
+            JOIN SourcePeople ON SourcePeople.source_id = Sources.id
+            JOIN People ON People.id = SourcePeople.person_id
+            JOIN Strings AS PersonNames ON PersonNames.id = People.names
+            WHERE Sources.source_type = ?3 AND SourcePeople.role = ?4
+                AND (lower(SourceNames.english) = lower(?1)
+                    OR lower(SourceNames.japanese) = lower(?1))
+                AND (lower(PersonNames.english) = lower(?2)
+                    OR lower(PersonNames.japanese) = lower(?2))
+            ORDER BY Sources.id LIMIT 1
+    ")?;
+    for author in authors {
+        let book_params = params![title, author, SourceType::Novel, PersonRole::Writer.db_name()];
+        let found = api::collect_query_map(statement.query_map(book_params, |row| {
+            let id: i64 = row.get(0)?;
+            Ok(id as u64)
+        }))?;
+        if let Some(source_id) = found.into_iter().next() {
+            return Ok(Some(source_id));
+        }
+    }
+    Ok(None)
+}
+
+/// Creates a stub Novel, with a Writer role for every author
+fn create_book(db: &Connection, title: &str, authors: &[&str]) -> Result<u64, Error> {
+    let source_id = imports::create_stub_source(db, title, SourceType::Novel, STUB_DESCRIPTION)?;
+    for author in authors {
+        let person_id = match imports::find_person(db, author)? {
+            Some(person_id) => person_id,
+            None => create_stub_person(db, author)?,
+        };
+        db.execute("INSERT INTO SourcePeople (source_id, person_id, role) VALUES (?, ?, ?)",
+            params![source_id as i64, person_id as i64, PersonRole::Writer.db_name()])?;
+    }
+    Ok(source_id)
+}
+
+fn create_stub_person(db: &Connection, name: &str) -> Result<u64, Error> {
+    let mut names = LangMap::new();
+    names.insert(Lang::English, name.to_string());
+    let mut descriptions = LangMap::new();
+    descriptions.insert(Lang::English, STUB_DESCRIPTION.to_string());
+
+    api::insert_new_lang_map(&names, db)?;
+    let names_id = db.last_insert_rowid();
+    api::insert_new_lang_map(&descriptions, db)?;
+    let descriptions_id = db.last_insert_rowid();
+
+    db.execute("INSERT INTO People (names, descriptions) VALUES (?, ?)",
+        params![names_id, descriptions_id])?;
+    Ok(db.last_insert_rowid() as u64)
+}
+
+/// Splits up the CSV into rows of fields (RFC 4180).
+/// Quoted fields can have commas, newlines and doubled quotes in them.
+fn parse_csv(csv: &str) -> Result<Vec<Vec<String>>, Error> {
+    // Excel likes to start files with a BOM
+    let csv = csv.trim_start_matches('\u{feff}');
+
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                },
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(Error::Validation(
+            format!("A quoted field is never closed on row {}", rows.len() + 1)));
+    }
+    // The last line doesn't need to end with a newline
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(csv: &str) -> Vec<Vec<String>> { parse_csv(csv).unwrap() }
+
+    #[test]
+    fn plain_fields() {
+        assert_eq!(rows("Title,Author\nSpice and Wolf,Isuna Hasekura\n"), vec![
+            vec!["Title", "Author"],
+            vec!["Spice and Wolf", "Isuna Hasekura"],
+        ]);
+        // CRLF line endings and a missing newline at the end
+        assert_eq!(rows("a,b\r\nc,d"), vec![vec!["a", "b"], vec!["c", "d"]]);
+        assert_eq!(rows("a,,\n"), vec![vec!["a", "", ""]]);
+        assert!(rows("").is_empty());
+    }
+
+    #[test]
+    fn quoted_fields() {
+        assert_eq!(rows("\"Fate/Zero, Vol. 1\",Gen Urobuchi\n"),
+            vec![vec!["Fate/Zero, Vol. 1", "Gen Urobuchi"]]);
+        assert_eq!(rows("\"Line one\nLine two\r\nLine three\",x\n"),
+            vec![vec!["Line one\nLine two\r\nLine three", "x"]]);
+        assert_eq!(rows("\"She said \"\"Hi\"\"\",\"\"\"\"\n"),
+            vec![vec!["She said \"Hi\"", "\""]]);
+        // A quote in the middle of a field is just a quote
+        assert_eq!(rows("5\" disk,x\n"), vec![vec!["5\" disk", "x"]]);
+    }
+
+    #[test]
+    fn the_bom_is_ignored() {
+        assert_eq!(rows("\u{feff}Title\n狼と香辛料\n"), vec![vec!["Title"], vec!["狼と香辛料"]]);
+    }
+
+    #[test]
+    fn unclosed_quotes_are_errors() {
+        assert!(matches!(parse_csv("a,b\n\"c,d\n"), Err(Error::Validation(message))
+            if message.ends_with("row 2")));
+    }
+}
@@ -0,0 +1,232 @@
+//! Imports the lists that MyAnimeList exports (animelist.xml and mangalist.xml).
+//! Every entry is a flat element, so there's no need for a full XML parser.
+
+use chrono::{NaiveDate};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    imports::{self, ImportReport, ReportEntry, SkippedEntry},
+    sources::source_types::{SourceType},
+    tracking::{
+        CompletionStatus,
+        entry::{self, StatusUpdate},
+    },
+};
+
+const STUB_DESCRIPTION: &str = "Imported from MyAnimeList";
+
+/// The parts of a MAL entry that we care about
+struct MALEntry {
+    title: Option<String>,
+    source_type: SourceType,
+    /// The types that an existing Source can have to match this entry
+    match_types: &'static [SourceType],
+    status: Option<String>,
+    start_date: Option<NaiveDate>,
+    finish_date: Option<NaiveDate>,
+    times_repeated: u32,
+}
+impl MALEntry {
+    fn new_anime(element: &str) -> MALEntry {
+        let source_type = match element_text(element, "series_type").as_deref() {
+            Some("Movie") => SourceType::Movie,
+            _ => SourceType::TVShow,
+        };
+        MALEntry {
+            title: element_text(element, "series_title"),
+            source_type,
+            match_types: &[SourceType::TVShow, SourceType::Movie],
+            status: element_text(element, "my_status"),
+            start_date: element_date(element, "my_start_date"),
+            finish_date: element_date(element, "my_finish_date"),
+            times_repeated: element_number(element, "my_times_watched"),
+        }
+    }
+
+    fn new_manga(element: &str) -> MALEntry {
+        MALEntry {
+            title: element_text(element, "manga_title"),
+            source_type: SourceType::Comic,
+            match_types: &[SourceType::Comic, SourceType::ComicRunning],
+            status: element_text(element, "my_status"),
+            start_date: element_date(element, "my_start_date"),
+            finish_date: element_date(element, "my_finish_date"),
+            times_repeated: element_number(element, "my_times_read"),
+        }
+    }
+
+    /// The statuses that this entry has reached, with the day that it was reached.
+    /// Returns an Err with the reason when there aren't any.
+    fn status_updates(&self) -> Result<Vec<(CompletionStatus, Option<NaiveDate>)>, String> {
+        // Older exports use numbers instead of names
+        match self.status.as_deref() {
+            Some("Watching") | Some("Reading") | Some("1") |
+            Some("On-Hold") | Some("3") |
+            Some("Dropped") | Some("4") => Ok(vec![
+                (CompletionStatus::InProgress, self.start_date),
+            ]),
+            Some("Completed") | Some("2") => {
+                let mut updates = Vec::with_capacity(2);
+                if self.start_date.is_some() {
+                    updates.push( (CompletionStatus::InProgress, self.start_date) );
+                }
+                updates.push( (CompletionStatus::Complete, self.finish_date) );
+                Ok(updates)
+            },
+            Some("Plan to Watch") | Some("Plan to Read") | Some("6") =>
+                Err("It hasn't been started".to_string()),
+            Some(status) => Err(format!("Unknown status {}", status)),
+            None => Err("There isn't a status".to_string()),
+        }
+    }
+}
+
+/// Imports every entry in the list into the tracker.
+/// Entries are matched to Sources by their title, and a stub Source is made when nothing matches.
+/// If the tracker can't be saved after the stubs were, importing the list again matches the stubs.
+pub fn import(sources_db: &ConnectionHolder, tracker_db: &ConnectionHolder, xml: &str)
+-> Result<ImportReport, Error> {
+    let mut entries: Vec<MALEntry> = elements(xml, "anime").into_iter()
+        .map(MALEntry::new_anime)
+        .collect();
+    entries.extend(elements(xml, "manga").into_iter().map(MALEntry::new_manga));
+    if entries.is_empty() && !xml.contains("<myanimelist>") {
+        return Err(Error::Validation("This isn't a MyAnimeList export".to_string()));
+    }
+
+    let mut sources_db = sources_db.lock();
+    let mut tracker_db = tracker_db.lock();
+    let sources_transaction = sources_db.transaction()?;
+    let tracker_transaction = tracker_db.transaction()?;
+
+    let mut report = ImportReport::default();
+    for mal_entry in entries {
+        let title = match mal_entry.title {
+            Some(ref title) if !title.is_empty() => title.clone(),
+            _ => {
+                report.skipped.push(SkippedEntry {
+                    title: String::new(),
+                    reason: "There isn't a title".to_string(),
+                });
+                continue;
+            },
+        };
+        let status_updates = match mal_entry.status_updates() {
+            Ok(status_updates) => status_updates,
+            Err(reason) => {
+                report.skipped.push(SkippedEntry { title, reason });
+                continue;
+            },
+        };
+
+        let source_id = match imports::find_source_by_name(&sources_transaction, &title,
+            mal_entry.match_types)? {
+            Some(source_id) => {
+                report.matched.push(ReportEntry { title, source_id });
+                source_id
+            },
+            None => {
+                let source_id = imports::create_stub_source(&sources_transaction, &title,
+                    mal_entry.source_type, STUB_DESCRIPTION)?;
+                report.created.push(ReportEntry { title, source_id });
+                source_id
+            },
+        };
+
+        for (status, date) in status_updates {
+            entry::update_status_with(&tracker_transaction, &StatusUpdate {
+                source_id,
+                status,
+                date,
+                play_time: None,
+            })?;
+        }
+        if mal_entry.times_repeated > 0 {
+            entry::set_times_repeated(&tracker_transaction, source_id,
+                mal_entry.times_repeated)?;
+        }
+    }
+
+    // The tracker has to go last, so its entries never point at Sources that weren't saved
+    sources_transaction.commit()?;
+    tracker_transaction.commit()?;
+    Ok(report)
+}
+
+/// Finds the contents of every element with the tag name
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let start_tag = format!("<{}>", tag);
+    let end_tag = format!("</{}>", tag);
+
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&start_tag) {
+        let contents_start = start + start_tag.len();
+        match rest[contents_start..].find(&end_tag) {
+            Some(length) => {
+                found.push(&rest[contents_start..(contents_start + length)]);
+                rest = &rest[(contents_start + length + end_tag.len())..];
+            },
+            None => break,
+        }
+    }
+    found
+}
+
+/// The text inside of the first element with the tag name, without any CDATA wrapping
+fn element_text(xml: &str, tag: &str) -> Option<String> {
+    let contents = elements(xml, tag).into_iter().next()?.trim();
+    if contents.starts_with("<![CDATA[") && contents.ends_with("]]>") {
+        Some(contents["<![CDATA[".len()..(contents.len() - "]]>".len())].to_string())
+    } else {
+        Some(decode_entities(contents))
+    }
+}
+
+/// MAL uses 0000-00-00 for days that weren't filled in, which won't parse
+fn element_date(xml: &str, tag: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(&element_text(xml, tag)?, "%Y-%m-%d").ok()
+}
+
+fn element_number(xml: &str, tag: &str) -> u32 {
+    element_text(xml, tag)
+        .and_then(|text| text.parse().ok())
+        .unwrap_or(0)
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest.find(';').map(|end| &rest[1..end]);
+        let character = match entity {
+            Some("amp") => Some('&'),
+            Some("lt") => Some('<'),
+            Some("gt") => Some('>'),
+            Some("quot") => Some('"'),
+            Some("apos") => Some('\''),
+            Some(number) if number.starts_with("#x") =>
+                u32::from_str_radix(&number[2..], 16).ok().and_then(std::char::from_u32),
+            Some(number) if number.starts_with('#') =>
+                number[1..].parse().ok().and_then(std::char::from_u32),
+            _ => None,
+        };
+        match (character, entity) {
+            (Some(character), Some(entity)) => {
+                decoded.push(character);
+                rest = &rest[(entity.len() + 2)..];
+            },
+            _ => {
+                // Leave anything we don't know about alone
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
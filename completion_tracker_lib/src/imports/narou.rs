@@ -0,0 +1,258 @@
+//! Reads a web novel from Shousetsuka ni Narou (syosetu.com) into a SourceCreator.
+//! The novel can come from the Narou API's JSON, or from a saved copy of its table of contents.
+//! Nothing gets created here, so the result can be looked over before it goes into the DB.
+
+use chrono::{NaiveDate};
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+use url::{Url};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    imports,
+    sources::{
+        api::source::{self, SourceCreator},
+        source_types::{Lang, LangMap, PersonRole, RelatedLink, SourceType, link_types},
+    },
+};
+
+/// The classes for the parts of the table of contents, from the current design and the old one
+const TITLE_CLASSES: &[&str] = &["p-novel__title", "novel_title"];
+const AUTHOR_CLASSES: &[&str] = &["p-novel__author", "novel_writername"];
+const EPISODE_CLASSES: &[&str] = &["p-eplist__sublist", "novel_sublist2"];
+const EPISODE_UPDATE_CLASSES: &[&str] = &["p-eplist__update", "long_update"];
+
+/// The Source from the novel, with everything that couldn't be put into it
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct NarouImport {
+    pub ncode: String,
+    pub source_creator: SourceCreator,
+    pub episode_count: Option<u32>,
+    pub last_updated: Option<NaiveDate>,
+    /// The author, when nobody in the DB has their name
+    pub unmatched_people: Vec<(String, PersonRole)>,
+    /// The Sources that already link to the novel, so it might not need to be created
+    pub existing_sources: Vec<u64>,
+}
+
+/// One novel from the API. Every other field in the response is left out.
+#[derive(Deserialize)]
+struct ApiNovel {
+    title: String,
+    ncode: String,
+    writer: String,
+    story: String,
+    general_all_no: u32,
+    /// These are "YYYY-MM-DD hh:mm:ss"
+    general_firstup: Option<String>,
+    general_lastup: Option<String>,
+}
+
+/// What was read from the page, before it gets matched up with the DB
+struct NarouNovel {
+    title: String,
+    author: String,
+    synopsis: String,
+    episode_count: Option<u32>,
+    first_published: Option<NaiveDate>,
+    last_updated: Option<NaiveDate>,
+}
+
+/// The ncode of the novel in the URL, in lowercase like the site's own links.
+/// Any page of the novel works, and so does an API URL with an ncode parameter.
+pub fn parse_ncode(url: &str) -> Result<String, Error> {
+    let url = Url::parse(url)?;
+    let is_narou = RelatedLink::new(url.as_str(), LangMap::new())
+        .map(|link| link.link_type().as_str() == link_types::SHOUSETSUKA_NAROU)
+        .unwrap_or(false);
+    if !is_narou {
+        return Err(Error::Validation(format!("{} isn't a Shousetsuka ni Narou URL", url)));
+    }
+
+    url.path_segments().into_iter().flatten()
+        .map(str::to_string)
+        .chain(url.query_pairs()
+            .filter(|(key, _)| key == "ncode")
+            .map(|(_, value)| value.into_owned()))
+        .map(|part| part.to_lowercase())
+        .find(|part| is_ncode(part))
+        .ok_or_else(|| Error::Validation(format!("Failed to find an ncode in {}", url)))
+}
+
+/// An "n", 4 digits and then 1 or more letters (like n9669bk)
+fn is_ncode(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() >= 6
+        && bytes[0] == b'n'
+        && bytes[1..5].iter().all(u8::is_ascii_digit)
+        && bytes[5..].iter().all(u8::is_ascii_lowercase)
+}
+
+/// The data is either the API's JSON or the HTML of the novel's table of contents.
+/// It has to be for the same novel as the URL.
+pub fn import(sources_db: &ConnectionHolder, url: &str, data: &str)
+-> Result<NarouImport, Error> {
+    let ncode = parse_ncode(url)?;
+    let trimmed = data.trim_start();
+    let novel = if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        read_api_json(data, &ncode)?
+    } else {
+        read_html(data, &ncode)?
+    };
+
+    let mut names = LangMap::new();
+    names.insert(Lang::Japanese, novel.title.clone());
+    let mut descriptions = LangMap::new();
+    descriptions.insert(Lang::Japanese, novel.synopsis.clone());
+    let mut link_descriptions = LangMap::new();
+    link_descriptions.insert(Lang::Japanese, "小説家になろう".to_string());
+    let novel_url = format!("https://ncode.syosetu.com/{}/", ncode);
+    let existing_sources = source::find_by_link(sources_db, &novel_url)?;
+    let related_links = vec![(novel_url, link_descriptions)];
+
+    let date_description = |japanese: &str| {
+        let mut descriptions = LangMap::new();
+        descriptions.insert(Lang::Japanese, japanese.to_string());
+        descriptions
+    };
+    let mut dates = Vec::new();
+    if let Some(first_published) = novel.first_published {
+        dates.push( (first_published, date_description("掲載開始")) );
+    }
+    if let Some(last_updated) = novel.last_updated {
+        dates.push( (last_updated, date_description("最終掲載")) );
+    }
+
+    let mut people = Vec::new();
+    let mut unmatched_people = Vec::new();
+    {
+        let db = sources_db.lock();
+        match imports::find_person(&db, &novel.author)? {
+            Some(person_id) => people.push( (person_id, PersonRole::Writer) ),
+            None => unmatched_people.push( (novel.author.clone(), PersonRole::Writer) ),
+        }
+    }
+
+    Ok(NarouImport {
+        ncode,
+        source_creator: SourceCreator {
+            names,
+            descriptions,
+            source_type: SourceType::WebNovel,
+            related_links,
+            dates,
+            universe_tags: Vec::new(),
+            related_sources: Vec::new(),
+            people,
+            characters: Vec::new(),
+            companies: Vec::new(),
+        },
+        episode_count: novel.episode_count,
+        last_updated: novel.last_updated,
+        unmatched_people,
+        existing_sources,
+    })
+}
+
+/// The API gives a list that starts with {"allcount": n}, and then has the novels
+fn read_api_json(json: &str, ncode: &str) -> Result<NarouNovel, Error> {
+    let values: Vec<serde_json::Value> = match serde_json::from_str(json)? {
+        serde_json::Value::Array(values) => values,
+        value => vec![value],
+    };
+    let mut found = None;
+    for value in values {
+        // The count at the start doesn't have an ncode
+        if value.get("ncode").is_none() {
+            continue;
+        }
+        let api_novel: ApiNovel = serde_json::from_value(value)?;
+        if api_novel.ncode.to_lowercase() == ncode {
+            found = Some(api_novel);
+            break;
+        }
+    }
+    let api_novel = found
+        .ok_or_else(|| Error::Validation(format!("The API response doesn't have {}", ncode)))?;
+
+    Ok(NarouNovel {
+        title: api_novel.title,
+        author: api_novel.writer,
+        synopsis: api_novel.story,
+        episode_count: Some(api_novel.general_all_no),
+        first_published: api_novel.general_firstup.as_ref().and_then(|date| find_date(date)),
+        last_updated: api_novel.general_lastup.as_ref().and_then(|date| find_date(date)),
+    })
+}
+
+/// Long novels have their table of contents split over a few pages, and any of them works.
+/// The episode count comes from the highest episode number, so the last page has the right count.
+/// The first date is only known when the page has the first episode on it.
+fn read_html(html: &str, ncode: &str) -> Result<NarouNovel, Error> {
+    // The episode links and the canonical link are the only places with the ncode
+    let is_same_novel = html.contains(&format!("href=\"/{}/", ncode))
+        || html.contains(&format!("syosetu.com/{}/", ncode));
+    if !is_same_novel {
+        return Err(Error::Validation(format!("The page isn't for {}", ncode)));
+    }
+
+    let find_class = |classes: &[&str], close: &str| classes.iter()
+        .find_map(|class| imports::find_tag(html, &format!("class=\"{}", class), close))
+        .map(imports::html_to_text);
+
+    let title = find_class(TITLE_CLASSES, "</")
+        .ok_or_else(|| Error::Validation("Failed to find the title of the novel".to_string()))?;
+    let author = find_class(AUTHOR_CLASSES, "</div>")
+        .ok_or_else(|| Error::Validation("Failed to find the author of the novel".to_string()))?;
+    let author = author.trim_start_matches("作者：").trim().to_string();
+    let synopsis = imports::find_tag(html, "id=\"novel_ex\"", "</div>")
+        .map(imports::html_to_text)
+        .unwrap_or_default();
+
+    // Each episode has a link to it and the date that it was posted
+    let mut episodes: Vec<(u32, Option<NaiveDate>)> = Vec::new();
+    for class in EPISODE_CLASSES {
+        for episode in html.split(&format!("class=\"{}\"", class)).skip(1) {
+            let link = format!("href=\"/{}/", ncode);
+            let number = episode.find(&link)
+                .and_then(|start| episode[start + link.len()..].split('/').next())
+                .and_then(|number| number.parse().ok());
+            if let Some(number) = number {
+                episodes.push( (number, find_update_date(episode)) );
+            }
+        }
+    }
+    let first_published = episodes.iter()
+        .find(|(number, _)| *number == 1)
+        .and_then(|(_, date)| *date);
+
+    Ok(NarouNovel {
+        title,
+        author,
+        synopsis,
+        episode_count: episodes.iter().map(|(number, _)| *number).max(),
+        first_published,
+        last_updated: episodes.iter().filter_map(|(_, date)| *date).max(),
+    })
+}
+
+/// The date inside the first element with one of the update classes
+fn find_update_date(episode: &str) -> Option<NaiveDate> {
+    EPISODE_UPDATE_CLASSES.iter().find_map(|class| {
+        let start = episode.find(&format!("class=\"{}\"", class))?;
+        let inside_start = start + episode[start..].find('>')? + 1;
+        let inside_end = inside_start + episode[inside_start..].find("</")?;
+        find_date(&episode[inside_start..inside_end])
+    })
+}
+
+/// The first date at the start of the text, as "2020/01/31" or "2020-01-31"
+fn find_date(text: &str) -> Option<NaiveDate> {
+    let mut numbers = text.split(|c: char| !c.is_ascii_digit())
+        .filter(|number| !number.is_empty());
+    let year = numbers.next().filter(|year| year.len() == 4)?.parse().ok()?;
+    let month = numbers.next()?.parse().ok()?;
+    let day = numbers.next()?.parse().ok()?;
+    NaiveDate::from_ymd_opt(year, month, day)
+}
@@ -0,0 +1,513 @@
+//! Reads the infobox of a saved Wikipedia page into a SourceCreator.
+//! The page can be English or Japanese, and either the wikitext (from "Edit source") or the HTML.
+//! Nothing gets created here, so the result can be looked over before it goes into the DB.
+
+use chrono::{NaiveDate};
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    imports,
+    sources::{
+        api::source::{self, SourceCreator},
+        source_types::{CompanyRole, Lang, LangMap, PersonRole, SourceType},
+    },
+};
+
+const TITLE_KEYS: &[&str] = &["title", "name", "タイトル", "題名", "書名"];
+const AUTHOR_KEYS: &[&str] = &["author", "authors", "written by", "writer", "作者", "著者", "原作"];
+const ILLUSTRATOR_KEYS: &[&str] = &["illustrator", "illustrated by", "イラスト", "作画", "挿絵"];
+const PUBLISHER_KEYS: &[&str] = &["publisher", "published by", "出版社", "発行元"];
+const DATE_KEYS: &[&str] = &[
+    "first", "last", "published", "release_date", "pub_date", "original run", "release date",
+    "publication date", "発売日", "発行日", "発表期間", "刊行期間", "連載期間", "出版日",
+];
+const VOLUME_KEYS: &[&str] = &["volumes", "巻数"];
+const TYPE_KEYS: &[&str] = &["type", "種類"];
+
+const MONTHS: &[&str] = &[
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+/// The Source from the page, with everything that couldn't be put into it
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct WikipediaImport {
+    pub source_creator: SourceCreator,
+    pub volume_count: Option<u32>,
+    /// The names that nobody in the DB has, so they need to be made (or found) by hand
+    pub unmatched_people: Vec<(String, PersonRole)>,
+    pub unmatched_companies: Vec<(String, CompanyRole)>,
+    /// The Sources that already link to the page, so it might not need to be created
+    pub existing_sources: Vec<u64>,
+}
+
+/// The fields in the order that they're in the page, with the keys in lowercase
+struct Infobox {
+    fields: Vec<(String, String)>,
+    /// The template names or section headers, which say what kind of Source it is
+    type_hints: Vec<String>,
+    /// The page's own title, for when the infobox doesn't have one
+    page_title: Option<String>,
+}
+impl Infobox {
+    /// The first value for any of the keys
+    fn get(&self, keys: &[&str]) -> Option<&str> {
+        self.fields.iter()
+            .find(|(key, value)| keys.contains(&key.as_str()) && !value.is_empty())
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every value for any of the keys
+    fn get_all(&self, keys: &[&str]) -> Vec<&str> {
+        self.fields.iter()
+            .filter(|(key, value)| keys.contains(&key.as_str()) && !value.is_empty())
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+}
+
+/// The lang is the language of the page, which is what the names go into.
+/// The URL of the page is added as a related link when it's given.
+pub fn import(sources_db: &ConnectionHolder, page: &str, lang: Lang, url: Option<&str>)
+-> Result<WikipediaImport, Error> {
+    let infobox = if page.trim_start().starts_with('<') {
+        read_html_infobox(page)?
+    } else {
+        read_wikitext_infobox(page)?
+    };
+
+    let title = infobox.get(TITLE_KEYS)
+        .map(str::to_string)
+        .or_else(|| infobox.page_title.clone())
+        .ok_or_else(|| Error::Validation("The page doesn't have a title".to_string()))?;
+    let mut names = LangMap::new();
+    names.insert(lang, first_line(&title).to_string());
+    let mut descriptions = LangMap::new();
+    descriptions.insert(lang, match lang {
+        Lang::English => "Imported from Wikipedia",
+        Lang::Japanese => "ウィキペディアから取り込み",
+    }.to_string());
+
+    let mut related_links = Vec::new();
+    if let Some(url) = url {
+        let mut link_descriptions = LangMap::new();
+        link_descriptions.insert(lang, match lang {
+            Lang::English => "Wikipedia",
+            Lang::Japanese => "ウィキペディア",
+        }.to_string());
+        related_links.push( (url.to_string(), link_descriptions) );
+    }
+    let existing_sources = match url {
+        Some(url) => source::find_by_link(sources_db, url)?,
+        None => Vec::new(),
+    };
+
+    let db = sources_db.lock();
+    let mut people = Vec::new();
+    let mut unmatched_people = Vec::new();
+    let person_roles = [(AUTHOR_KEYS, PersonRole::Writer), (ILLUSTRATOR_KEYS, PersonRole::Illustrator)];
+    for (keys, role) in person_roles.iter() {
+        for name in infobox.get(keys).map(split_names).unwrap_or_default() {
+            match imports::find_person(&db, &name)? {
+                Some(person_id) => people.push( (person_id, *role) ),
+                None => unmatched_people.push( (name, *role) ),
+            }
+        }
+    }
+    let mut companies = Vec::new();
+    let mut unmatched_companies = Vec::new();
+    for name in infobox.get(PUBLISHER_KEYS).map(split_names).unwrap_or_default() {
+        match imports::find_company(&db, &name)? {
+            Some(company_id) => companies.push( (company_id, Some(CompanyRole::Publisher)) ),
+            None => unmatched_companies.push( (name, CompanyRole::Publisher) ),
+        }
+    }
+
+    Ok(WikipediaImport {
+        source_creator: SourceCreator {
+            names,
+            descriptions,
+            source_type: read_source_type(&infobox),
+            related_links,
+            dates: read_dates(&infobox, lang),
+            universe_tags: Vec::new(),
+            related_sources: Vec::new(),
+            people,
+            characters: Vec::new(),
+            companies,
+        },
+        volume_count: infobox.get(VOLUME_KEYS)
+            .and_then(|volumes| words(volumes).into_iter().find_map(|word| word.parse().ok())),
+        unmatched_people,
+        unmatched_companies,
+        existing_sources,
+    })
+}
+
+/// Manga are Comics, and everything else is taken to be a Novel since it's a book
+fn read_source_type(infobox: &Infobox) -> SourceType {
+    let hints = infobox.get_all(TYPE_KEYS).into_iter()
+        .map(str::to_string)
+        .chain(infobox.type_hints.iter().cloned());
+    for hint in hints {
+        // Every one of the anime and manga infoboxes starts with "animanga"
+        let hint = hint.to_lowercase().replace("animanga", "");
+        if hint.contains("manga") || hint.contains("漫画") {
+            return SourceType::Comic;
+        } else if hint.contains("novel") || hint.contains("小説") {
+            return SourceType::Novel;
+        }
+    }
+    SourceType::Novel
+}
+
+/// Only the first and last release go in, since the ones in-between are rarely all listed
+fn read_dates(infobox: &Infobox, lang: Lang) -> Vec<(NaiveDate, LangMap)> {
+    let mut dates: Vec<NaiveDate> = infobox.get_all(DATE_KEYS).into_iter()
+        .flat_map(find_dates)
+        .collect();
+    dates.sort();
+    dates.dedup();
+
+    let description = |english: &str, japanese: &str| {
+        let mut descriptions = LangMap::new();
+        descriptions.insert(lang, match lang {
+            Lang::English => english,
+            Lang::Japanese => japanese,
+        }.to_string());
+        descriptions
+    };
+    match (dates.first(), dates.last()) {
+        (Some(first), Some(last)) if first != last => vec![
+            (*first, description("First release", "初版発売")),
+            (*last, description("Latest release", "最新巻発売")),
+        ],
+        (Some(first), _) => vec![(*first, description("Release", "発売"))],
+        _ => Vec::new(),
+    }
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or("").trim()
+}
+
+/// Splits up a list of names, leaving off anything in brackets like "(story)"
+fn split_names(value: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for line in value.split(['\n', ',', '、', '/']) {
+        for name in line.split(" and ") {
+            let mut name = name.to_string();
+            for (open, close) in &[('(', ')'), ('（', '）')] {
+                while let (Some(start), Some(end)) = (name.find(*open), name.find(*close)) {
+                    if end < start {
+                        break;
+                    }
+                    name.replace_range(start..end + close.len_utf8(), "");
+                }
+            }
+            let name = name.trim().trim_start_matches('*').trim();
+            if !name.is_empty() && !names.iter().any(|other| other == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Splits the text into its runs of ASCII letters and digits
+fn words(text: &str) -> Vec<&str> {
+    text.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+fn parse_month(word: &str) -> Option<u32> {
+    let word = word.to_lowercase();
+    MONTHS.iter()
+        .position(|month| *month == word || (word.len() == 3 && month.starts_with(&word)))
+        .map(|position| position as u32 + 1)
+}
+
+/// Finds every date in the text, in the order that they're in.
+/// This understands "2009-03-31", "2009年3月31日", "March 31, 2009" and "31 March 2009".
+fn find_dates(text: &str) -> Vec<NaiveDate> {
+    let words = words(text);
+    let mut dates = Vec::new();
+    let mut i = 0;
+    while i + 2 < words.len() {
+        let (first, second, third) = (words[i], words[i + 1], words[i + 2]);
+        let date = if first.len() == 4 {
+            match (first.parse(), second.parse(), third.parse()) {
+                (Ok(year), Ok(month), Ok(day)) => NaiveDate::from_ymd_opt(year, month, day),
+                _ => None,
+            }
+        } else if let Some(month) = parse_month(first) {
+            match (second.parse(), third.parse()) {
+                (Ok(day), Ok(year)) => NaiveDate::from_ymd_opt(year, month, day),
+                _ => None,
+            }
+        } else if let Some(month) = parse_month(second) {
+            match (first.parse(), third.parse()) {
+                (Ok(day), Ok(year)) => NaiveDate::from_ymd_opt(year, month, day),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        match date {
+            Some(date) => {
+                dates.push(date);
+                i += 3;
+            },
+            None => i += 1,
+        }
+    }
+    dates
+}
+
+fn read_wikitext_infobox(page: &str) -> Result<Infobox, Error> {
+    let mut infobox = Infobox {
+        fields: Vec::new(),
+        type_hints: Vec::new(),
+        page_title: None,
+    };
+    // The animanga infoboxes are split up into a few templates in a row
+    let mut rest = page;
+    while let Some(start) = rest.find("{{") {
+        let end = match find_template_end(&rest[start..]) {
+            Some(end) => start + end,
+            None => break,
+        };
+        let params = split_params(&rest[start + 2..end - 2]);
+        let template_name = params.first().map(|name| name.trim().to_lowercase())
+            .unwrap_or_default();
+        if template_name.starts_with("infobox") || template_name.starts_with("基礎情報") {
+            infobox.type_hints.push(template_name);
+            for param in params.iter().skip(1) {
+                if let Some(equals) = param.find('=') {
+                    let key = param[..equals].trim().to_lowercase();
+                    let value = clean_wikitext(&param[equals + 1..]);
+                    infobox.fields.push( (key, value) );
+                }
+            }
+        }
+        rest = &rest[end..];
+    }
+
+    if infobox.type_hints.is_empty() {
+        Err(Error::Validation("The page doesn't have an infobox".to_string()))
+    } else {
+        Ok(infobox)
+    }
+}
+
+/// The index just after the "}}" that closes the template at the start of the text
+fn find_template_end(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < text.len() {
+        if text[i..].starts_with("{{") {
+            depth += 1;
+            i += 2;
+        } else if text[i..].starts_with("}}") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return Some(i);
+            }
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    None
+}
+
+/// Splits up the inside of a template by the "|" that aren't in a link or another template
+fn split_params(inside: &str) -> Vec<&str> {
+    let mut params = Vec::new();
+    let mut depth = 0;
+    let mut param_start = 0;
+    let mut i = 0;
+    while i < inside.len() {
+        let rest = &inside[i..];
+        if rest.starts_with("{{") || rest.starts_with("[[") {
+            depth += 1;
+            i += 2;
+        } else if rest.starts_with("}}") || rest.starts_with("]]") {
+            depth -= 1;
+            i += 2;
+        } else {
+            if rest.starts_with('|') && depth == 0 {
+                params.push(&inside[param_start..i]);
+                param_start = i + 1;
+            }
+            i += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    params.push(&inside[param_start..]);
+    params
+}
+
+/// Turns the wikitext into plain text, with a line for every item in a list
+fn clean_wikitext(wikitext: &str) -> String {
+    let text = imports::remove_between(wikitext, "<!--", "-->");
+    let text = remove_references(&text);
+    let text = expand_templates(&text);
+    let text = replace_links(&text);
+    let text = text.replace("'''", "").replace("''", "");
+    imports::html_to_text(&text)
+}
+
+/// Takes out the <ref>...</ref> and <ref name="..."/> tags
+fn remove_references(text: &str) -> String {
+    let mut cleaned = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("<ref") {
+        cleaned.push_str(&rest[..start]);
+        let tag_end = match rest[start..].find('>') {
+            Some(tag_end) => start + tag_end,
+            None => return cleaned,
+        };
+        rest = if rest[..tag_end].ends_with('/') {
+            &rest[tag_end + 1..]
+        } else {
+            match rest[tag_end..].find("</ref>") {
+                Some(end) => &rest[tag_end + end + "</ref>".len()..],
+                None => "",
+            }
+        };
+    }
+    cleaned.push_str(rest);
+    cleaned
+}
+
+/// Replaces the templates that have something worth keeping, and drops the rest
+fn expand_templates(text: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        expanded.push_str(&rest[..start]);
+        let end = match find_template_end(&rest[start..]) {
+            Some(end) => start + end,
+            None => return expanded,
+        };
+        let params = split_params(&rest[start + 2..end - 2]);
+        let name = params[0].trim().to_lowercase();
+        // Only the unnamed params are wanted
+        let values: Vec<String> = params.iter().skip(1)
+            .filter(|param| !param.contains('='))
+            .map(|param| expand_templates(param).trim().to_string())
+            .collect();
+
+        if name.contains("date") || name == "dts" {
+            let numbers: Vec<u32> = values.iter().filter_map(|value| value.parse().ok()).collect();
+            if let [year, month, day, ..] = numbers.as_slice() {
+                expanded.push_str(&format!("{}-{:02}-{:02}", year, month, day));
+            }
+        } else if name == "plainlist" || name == "plain list" {
+            expanded.push_str(&values.join("\n").replace('*', "\n"));
+        } else if name == "ubl" || name.contains("unbulleted list") || name.contains("flatlist")
+            || name == "hlist" {
+            expanded.push_str(&values.join("\n"));
+        } else if name == "nihongo" || name == "ruby" || name == "読み仮名" {
+            expanded.push_str(values.first().map(String::as_str).unwrap_or(""));
+        } else if name == "lang" {
+            expanded.push_str(values.last().map(String::as_str).unwrap_or(""));
+        }
+        rest = &rest[end..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// [[Target|Text]] becomes Text, [[Target]] becomes Target and [https://url Text] becomes Text
+fn replace_links(text: &str) -> String {
+    let mut replaced = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        replaced.push_str(&rest[..start]);
+        if rest[start..].starts_with("[[") {
+            match rest[start..].find("]]") {
+                Some(end) => {
+                    let inside = &rest[start + 2..start + end];
+                    replaced.push_str(inside.rsplit('|').next().unwrap_or(inside));
+                    rest = &rest[start + end + 2..];
+                },
+                None => rest = &rest[start + 2..],
+            }
+        } else {
+            match rest[start..].find(']') {
+                Some(end) => {
+                    let inside = &rest[start + 1..start + end];
+                    replaced.push_str(inside.split_once(' ').map_or("", |(_, text)| text));
+                    rest = &rest[start + end + 1..];
+                },
+                None => rest = &rest[start + 1..],
+            }
+        }
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
+fn read_html_infobox(page: &str) -> Result<Infobox, Error> {
+    let page_title = imports::find_tag(page, "<h1", "</h1>").map(imports::html_to_text);
+
+    let class_index = page.find("class=\"infobox")
+        .ok_or_else(|| Error::Validation("The page doesn't have an infobox".to_string()))?;
+    let table_start = page[..class_index].rfind("<table")
+        .ok_or_else(|| Error::Validation("The infobox isn't a table".to_string()))?;
+    let table = &page[table_start..table_start + find_table_end(&page[table_start..])];
+
+    let mut infobox = Infobox {
+        fields: Vec::new(),
+        type_hints: Vec::new(),
+        page_title,
+    };
+    if let Some(caption) = imports::find_tag(table, "<caption", "</caption>") {
+        infobox.fields.push( ("title".to_string(), imports::html_to_text(caption)) );
+    }
+    for row in table.split("<tr").skip(1) {
+        let header = imports::find_tag(row, "<th", "</th>").map(imports::html_to_text);
+        let data = imports::find_tag(row, "<td", "</td>").map(imports::html_to_text);
+        match (header, data) {
+            (Some(header), Some(data)) => infobox.fields.push( (header.to_lowercase(), data) ),
+            // A row with only a header is the name of a section, or the title at the top
+            (Some(header), None) => {
+                if row.contains("infobox-above") {
+                    infobox.fields.push( ("title".to_string(), header) );
+                } else {
+                    infobox.type_hints.push(header);
+                }
+            },
+            _ => (),
+        }
+    }
+    Ok(infobox)
+}
+
+/// The length of the table at the start of the text, with any tables inside of it
+fn find_table_end(text: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while let Some(next) = text[i..].find("<table").into_iter()
+        .chain(text[i..].find("</table>"))
+        .min() {
+        i += next;
+        if text[i..].starts_with("</table>") {
+            depth -= 1;
+            i += "</table>".len();
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            depth += 1;
+            i += "<table".len();
+        }
+    }
+    text.len()
+}
@@ -1,32 +1,94 @@
 // Define this first for the macro
-mod simple_enum;
+pub mod simple_enum;
 
 pub mod db_link;
+pub mod error;
+pub mod exports;
+pub mod imports;
+pub mod link_check;
 pub mod sources;
 pub mod tracking;
 pub mod utils;
 
+use std::{
+    collections::{BTreeMap},
+    path::{Path},
+};
+
 use rusqlite::{Connection};
 
-use crate::db_link::{ConnectionHolder};
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error, ResultExt},
+    tracking::{Tracker},
+};
+
+/// Every tracker's DB, found by the tracker's name
+pub type TrackerDBs = BTreeMap<String, ConnectionHolder>;
+
+/// A sources DB that only lives in memory, which is gone when it's dropped
+pub fn init_source_db() -> Result<ConnectionHolder, Error> {
+    setup_source_db(Connection::open_in_memory()?)
+}
 
-pub fn init_source_db() -> Result<ConnectionHolder, String> {
-    // TODO Open a real file for the DB
-    let connection = Connection::open_in_memory()
-        .map_err(|e| e.to_string())?;
-    connection.execute_batch("PRAGMA foreign_keys = ON")
-        .map_err(|e| e.to_string())?;
+/// Opens the sources DB in the file, creating it if it isn't there yet
+pub fn open_source_db(file: impl AsRef<Path>) -> Result<ConnectionHolder, Error> {
+    let file = file.as_ref();
+    let connection = Connection::open(file)
+        .with_context(|| format!("Failed to open the sources DB at {}", file.display()))?;
+    setup_source_db(connection)
+}
+
+fn setup_source_db(connection: Connection) -> Result<ConnectionHolder, Error> {
+    connection.execute_batch("PRAGMA foreign_keys = ON")?;
     connection.create_scalar_function("is_empty", 1, true, |ctx| {
         let string_option: Option<String> = ctx.get(0)?;
         Ok(
             string_option.map_or(false, |string| string.is_empty())
         )
-    }).map_err(|e| e.to_string())?;
+    })?;
+    // LIKE only ignores the case of ASCII letters, so the suggestions match with this instead
+    connection.create_scalar_function("has_word_prefix", 2, true, |ctx| {
+        let string_option: Option<String> = ctx.get(0)?;
+        let lowercase_prefix: String = ctx.get(1)?;
+        Ok(
+            string_option.is_some_and(|string| {
+                sources::api::search::has_word_prefix(&string, &lowercase_prefix)
+            })
+        )
+    })?;
 
     sources::create_tables(&connection)?;
+    link_check::create_tables(&connection)?;
 
     Ok(ConnectionHolder::new(connection))
 }
 
-// pub fn init_tracker_dbs(trackers: Vec<Tracker>) -> Result {
-// }
+/// Finds the tracker's DB with the name.
+/// The name can only be left out if there's a single tracker.
+pub fn find_tracker_db<'a>(tracker_dbs: &'a TrackerDBs, name: Option<&String>)
+-> Result<&'a ConnectionHolder, Error> {
+    if let Some(name) = name {
+        tracker_dbs.get(name)
+            .ok_or_else(|| Error::NotFound(format!("There isn't a tracker called {}", name)))
+    } else if tracker_dbs.len() == 1 {
+        Ok(tracker_dbs.values().next().unwrap())
+    } else {
+        Err(Error::Validation(
+            "A tracker name is needed since there is more than one tracker".to_string()))
+    }
+}
+
+pub fn init_tracker_dbs(trackers: &[Tracker]) -> Result<TrackerDBs, Error> {
+    let mut tracker_dbs = TrackerDBs::new();
+    for tracker in trackers {
+        let connection = Connection::open(tracker.file())
+            .with_context(|| format!("Failed to open the DB for {}", tracker.name()))?;
+        connection.execute_batch("PRAGMA foreign_keys = ON")?;
+
+        tracking::create_tables(&connection)?;
+
+        tracker_dbs.insert(tracker.name().to_string(), ConnectionHolder::new(connection));
+    }
+    Ok(tracker_dbs)
+}
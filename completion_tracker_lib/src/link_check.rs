@@ -0,0 +1,325 @@
+//! Checks that the related links of the Universe Tags and Sources still work.
+//! Every URL is only checked once, even when more than one item links to it,
+//! and the last result for each URL is kept in LinkChecks.
+
+use std::{
+    sync::{Mutex},
+    thread,
+    time::{Duration},
+};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, params};
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+use url::{Url};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error, ResultExt},
+    sources::{
+        api::{self, search::{ItemType}},
+        source_types::{LangMap},
+    },
+};
+
+pub(crate) fn create_tables(db: &Connection) -> Result<(), Error> {
+    db.execute_batch("
+        CREATE TABLE IF NOT EXISTS LinkChecks (
+            id INTEGER PRIMARY KEY,
+            url TEXT NOT NULL UNIQUE,
+            status INTEGER,
+            redirect_url TEXT,
+            error TEXT,
+            last_checked TEXT NOT NULL
+        );
+    ").context("Error creating the link check tables")
+}
+
+/// The limits on the options, since they come from the requests
+const MAX_CONCURRENCY: usize = 16;
+const MAX_TIMEOUT_MILLIS: u64 = 60_000;
+const MAX_REDIRECTS: usize = 10;
+
+/// What came back for a single request. Redirects aren't followed by the client.
+pub struct HttpResponse {
+    pub status: u16,
+    /// The Location header
+    pub location: Option<String>,
+}
+
+/// Makes the requests for the checker, so it can be pointed somewhere else (like in the tests)
+pub trait HttpClient: Sync {
+    fn request(&self, method: &str, url: &str, timeout: Duration) -> Result<HttpResponse, String>;
+}
+
+/// The client that goes out to the real sites
+pub struct UreqClient {
+    agent: ureq::Agent,
+}
+impl UreqClient {
+    pub fn new() -> UreqClient {
+        UreqClient {
+            agent: ureq::AgentBuilder::new()
+                .redirects(0)
+                .user_agent(concat!("CompletionTracker/", env!("CARGO_PKG_VERSION")))
+                .build(),
+        }
+    }
+}
+impl Default for UreqClient {
+    fn default() -> UreqClient { UreqClient::new() }
+}
+impl HttpClient for UreqClient {
+    fn request(&self, method: &str, url: &str, timeout: Duration) -> Result<HttpResponse, String> {
+        let response = match self.agent.request(method, url).timeout(timeout).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(ureq::Error::Transport(e)) => return Err(e.to_string()),
+        };
+        Ok(HttpResponse {
+            status: response.status(),
+            location: response.header("Location").map(str::to_string),
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct LinkCheckOptions {
+    /// How many links get checked at the same time (1 to 16)
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// How long each request can take (up to 60 seconds)
+    #[serde(default = "default_timeout_millis")]
+    pub timeout_millis: u64,
+    /// Up to 10
+    #[serde(default = "default_max_redirects")]
+    pub max_redirects: usize,
+}
+impl LinkCheckOptions {
+    fn validate(&self) -> Result<(), Error> {
+        if !(1..=MAX_CONCURRENCY).contains(&self.concurrency) {
+            return Err(Error::Validation(format!("The concurrency must be from 1 to {}, not {}",
+                MAX_CONCURRENCY, self.concurrency)));
+        }
+        if !(1..=MAX_TIMEOUT_MILLIS).contains(&self.timeout_millis) {
+            return Err(Error::Validation(format!(
+                "The timeout must be from 1 to {} milliseconds, not {}",
+                MAX_TIMEOUT_MILLIS, self.timeout_millis)));
+        }
+        if self.max_redirects > MAX_REDIRECTS {
+            return Err(Error::Validation(format!("There can't be more than {} redirects, not {}",
+                MAX_REDIRECTS, self.max_redirects)));
+        }
+        Ok(())
+    }
+}
+impl Default for LinkCheckOptions {
+    fn default() -> LinkCheckOptions {
+        LinkCheckOptions {
+            concurrency: default_concurrency(),
+            timeout_millis: default_timeout_millis(),
+            max_redirects: default_max_redirects(),
+        }
+    }
+}
+fn default_concurrency() -> usize { 4 }
+fn default_timeout_millis() -> u64 { 10_000 }
+fn default_max_redirects() -> usize { 5 }
+
+/// The last check of a URL
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
+pub struct LinkCheck {
+    pub url: String,
+    /// The status after following the redirects. There isn't one when the request failed.
+    pub status: Option<u16>,
+    /// Where the link ended up, when it was redirected
+    pub redirect_url: Option<String>,
+    pub error: Option<String>,
+    pub last_checked: DateTime<Utc>,
+}
+impl LinkCheck {
+    pub fn is_broken(&self) -> bool {
+        self.error.is_some() || self.status.is_none_or(|status| status >= 400)
+    }
+}
+
+#[derive(Default, Deserialize, Serialize, JsonSchema)]
+pub struct LinkCheckSummary {
+    pub checked: usize,
+    pub broken: usize,
+    pub redirected: usize,
+}
+
+/// The broken links of a single Universe Tag or Source
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct ItemBrokenLinks {
+    /// UniverseTag or Source
+    pub item_type: ItemType,
+    pub id: u64,
+    pub names: LangMap,
+    pub links: Vec<LinkCheck>,
+}
+
+/// Checks every related link and saves the results
+pub fn check_links(sources_db: &ConnectionHolder, client: &dyn HttpClient,
+options: &LinkCheckOptions) -> Result<LinkCheckSummary, Error> {
+    options.validate()?;
+    let urls = {
+        let db = sources_db.lock();
+        let mut statement = db.prepare("
+            SELECT url FROM UniverseTagRelatedLinks
+            UNION
+            SELECT url FROM SourceRelatedLinks
+            ORDER BY url
+        ")?;
+        api::collect_query_map(statement.query_map(rusqlite::NO_PARAMS, |row| row.get(0)))?
+    };
+
+    let checks = check_urls(client, urls, options);
+
+    let mut summary = LinkCheckSummary::default();
+    let mut db = sources_db.lock();
+    let transaction = db.transaction()?;
+    for check in checks.iter() {
+        transaction.execute("
+            INSERT INTO LinkChecks (url, status, redirect_url, error, last_checked)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT(url) DO UPDATE SET
+                    status=?2, redirect_url=?3, error=?4, last_checked=?5
+        ", params![check.url, check.status, check.redirect_url, check.error, check.last_checked])?;
+
+        summary.checked += 1;
+        if check.is_broken() {
+            summary.broken += 1;
+        }
+        if check.redirect_url.is_some() {
+            summary.redirected += 1;
+        }
+    }
+    transaction.commit()?;
+    Ok(summary)
+}
+
+/// Goes through the URLs with a few threads, giving back the checks in the same order
+fn check_urls(client: &dyn HttpClient, urls: Vec<String>, options: &LinkCheckOptions)
+-> Vec<LinkCheck> {
+    // There's no point in a thread without a URL to check
+    let thread_count = options.concurrency.min(urls.len());
+    let next = Mutex::new(urls.into_iter().enumerate());
+    let checks = Mutex::new(Vec::new());
+    thread::scope(|scope| {
+        for _ in 0..thread_count {
+            scope.spawn(|| loop {
+                // Only hold the lock long enough to take the next URL
+                let next_url = next.lock().unwrap().next();
+                let (index, url) = match next_url {
+                    Some(next_url) => next_url,
+                    None => break,
+                };
+                let check = check_url(client, url, options);
+                checks.lock().unwrap().push( (index, check) );
+            });
+        }
+    });
+
+    let mut checks = checks.into_inner().unwrap();
+    checks.sort_by_key(|(index, _)| *index);
+    checks.into_iter().map(|(_, check)| check).collect()
+}
+
+fn check_url(client: &dyn HttpClient, url: String, options: &LinkCheckOptions) -> LinkCheck {
+    let timeout = Duration::from_millis(options.timeout_millis);
+    let mut check = LinkCheck {
+        url: url.clone(),
+        status: None,
+        redirect_url: None,
+        error: None,
+        last_checked: Utc::now(),
+    };
+
+    let mut current = url;
+    for _ in 0..=options.max_redirects {
+        let mut response = client.request("HEAD", &current, timeout);
+        // Some sites don't allow HEAD, so they get a GET instead
+        if let Ok(HttpResponse { status: 403 | 405 | 501, .. }) = response {
+            response = client.request("GET", &current, timeout);
+        }
+
+        match response {
+            Ok(response) if (300..400).contains(&response.status) => {
+                let next = response.location.as_ref()
+                    .and_then(|location| Url::parse(&current).ok()?.join(location).ok());
+                match next {
+                    Some(next) => {
+                        current = next.into();
+                        check.redirect_url = Some(current.clone());
+                    },
+                    None => {
+                        check.status = Some(response.status);
+                        check.error = Some("The redirect didn't say where to go".to_string());
+                        return check;
+                    },
+                }
+            },
+            Ok(response) => {
+                check.status = Some(response.status);
+                return check;
+            },
+            Err(e) => {
+                check.error = Some(e);
+                return check;
+            },
+        }
+    }
+    check.error = Some(format!("There were more than {} redirects", options.max_redirects));
+    check
+}
+
+/// The items that have a link that was broken the last time it was checked
+pub fn broken_links(sources_db: &ConnectionHolder) -> Result<Vec<ItemBrokenLinks>, Error> {
+    let db = sources_db.lock();
+    let mut items: Vec<ItemBrokenLinks> = Vec::new();
+    for (item_type, links_table, item_table, id_column) in [
+        (ItemType::UniverseTag, "UniverseTagRelatedLinks", "UniverseTags", "universe_tag_id"),
+        (ItemType::Source, "SourceRelatedLinks", "Sources", "source_id"),
+    ].iter() {
+        let mut statement = db.prepare(&format!("
+            SELECT Strings.english, Strings.japanese, {links}.{id_column},
+                LinkChecks.url, LinkChecks.status, LinkChecks.redirect_url, LinkChecks.error,
+                LinkChecks.last_checked
+            FROM {links}
+                JOIN LinkChecks ON LinkChecks.url = {links}.url
+                JOIN {items} ON {items}.id = {links}.{id_column}
+                JOIN Strings ON Strings.id = {items}.names
+            WHERE LinkChecks.error IS NOT NULL OR LinkChecks.status IS NULL
+                OR LinkChecks.status >= 400
+            ORDER BY {links}.{id_column}, {links}.id
+        ", links = links_table, items = item_table, id_column = id_column))?;
+        let rows = api::collect_query_map(statement.query_map(rusqlite::NO_PARAMS, |row| {
+            let id: i64 = row.get(2)?;
+            let names = api::make_lang_strings_from_row(row);
+            Ok( (id as u64, names, LinkCheck {
+                url: row.get(3)?,
+                status: row.get(4)?,
+                redirect_url: row.get(5)?,
+                error: row.get(6)?,
+                last_checked: row.get(7)?,
+            }) )
+        }))?;
+
+        for (id, names, check) in rows {
+            match items.last_mut() {
+                Some(item) if item.item_type == *item_type && item.id == id =>
+                    item.links.push(check),
+                _ => items.push(ItemBrokenLinks {
+                    item_type: *item_type,
+                    id,
+                    names,
+                    links: vec![check],
+                }),
+            }
+        }
+    }
+    Ok(items)
+}
@@ -1,9 +1,12 @@
 pub mod api;
+pub mod backup;
 pub mod source_types;
 
 use rusqlite::{Connection};
 
-pub fn create_tables(db: &Connection) -> Result<(), String> {
+use crate::error::{Error, ResultExt};
+
+pub fn create_tables(db: &Connection) -> Result<(), Error> {
     // Future Notes:
     // - All of the relations are id1 -> id2
     //   So if id1 -> Before -> id2, then id2 -> After -> id1
@@ -16,7 +19,7 @@ pub fn create_tables(db: &Connection) -> Result<(), String> {
             japanese TEXT,
             CHECK(NOT is_empty(english) OR NOT is_empty(japanese))
         );
-    ").map_err(|e| format!("Error creating the Strings table: {}", e))?;
+    ").context("Error creating the Strings table")?;
     db.execute_batch("
         CREATE TABLE IF NOT EXISTS UniverseTags (
             id INTEGER PRIMARY KEY,
@@ -41,10 +44,14 @@ pub fn create_tables(db: &Connection) -> Result<(), String> {
             id INTEGER PRIMARY KEY,
             universe_tag_id INTEGER NOT NULL REFERENCES UniverseTags(id),
             url TEXT NOT NULL,
+            link_type TEXT NOT NULL,
+            external_id TEXT,
             descriptions INTEGER NOT NULL REFERENCES Strings(id),
             UNIQUE(universe_tag_id, url)
         );
-    ").map_err(|e| format!("Error creating the Universe Tag tables: {}", e))?;
+        CREATE INDEX IF NOT EXISTS UniverseTagRelatedLinksExternalID
+            ON UniverseTagRelatedLinks(link_type, external_id);
+    ").context("Error creating the Universe Tag tables")?;
     db.execute_batch("
         CREATE TABLE IF NOT EXISTS People (
             id INTEGER PRIMARY KEY,
@@ -61,7 +68,7 @@ pub fn create_tables(db: &Connection) -> Result<(), String> {
             alias TEXT NOT NULL,
             UNIQUE(person_id, lang, alias)
         );
-    ").map_err(|e| format!("Error creating the People tables: {}", e))?;
+    ").context("Error creating the People tables")?;
     db.execute_batch("
         CREATE TABLE IF NOT EXISTS Companies (
             id INTEGER PRIMARY KEY,
@@ -69,7 +76,7 @@ pub fn create_tables(db: &Connection) -> Result<(), String> {
             descriptions INTEGER NOT NULL REFERENCES Strings(id),
             country TEXT
         );
-    ").map_err(|e| format!("Error creating the Company tables: {}", e))?;
+    ").context("Error creating the Company tables")?;
     db.execute_batch("
         CREATE TABLE IF NOT EXISTS Characters (
             id INTEGER PRIMARY KEY,
@@ -91,7 +98,7 @@ pub fn create_tables(db: &Connection) -> Result<(), String> {
             UNIQUE(character_id1, character_id2),
             CHECK(character_id1 != character_id2)
         );
-    ").map_err(|e| format!("Error creating the Characters tables: {}", e))?;
+    ").context("Error creating the Characters tables")?;
     db.execute_batch("
         CREATE TABLE IF NOT EXISTS Sources (
             id INTEGER PRIMARY KEY,
@@ -103,9 +110,13 @@ pub fn create_tables(db: &Connection) -> Result<(), String> {
             id INTEGER PRIMARY KEY,
             source_id INTEGER NOT NULL REFERENCES Sources(id),
             url TEXT NOT NULL,
+            link_type TEXT NOT NULL,
+            external_id TEXT,
             descriptions INTEGER NOT NULL REFERENCES Strings(id),
             UNIQUE(source_id, url)
         );
+        CREATE INDEX IF NOT EXISTS SourceRelatedLinksExternalID
+            ON SourceRelatedLinks(link_type, external_id);
         CREATE TABLE IF NOT EXISTS SourceDates (
             id INTEGER PRIMARY KEY,
             source_id INTEGER NOT NULL REFERENCES Sources(id),
@@ -153,6 +164,6 @@ pub fn create_tables(db: &Connection) -> Result<(), String> {
             company_role TEXT,
             UNIQUE(source_id, company_id, company_role)
         );
-    ").map_err(|e| format!("Error creating the Sources tables: {}", e))?;
+    ").context("Error creating the Sources tables")?;
     Ok(())
 }
@@ -1,11 +1,13 @@
 pub mod character;
 pub mod people;
 pub mod search;
+pub mod source;
 pub mod universe_tag;
 
 use rusqlite::{Connection, Result as SqlResult, Row, Statement};
 
 use crate::{
+    error::{Error},
     impl_sql_simple_enum,
     simple_enum::{SimpleEnum},
     sources::source_types::{
@@ -19,7 +21,7 @@ use crate::{
 /// This is to normalize the database so that there is only a single way to express a relationship
 /// between two things.
 #[derive(Copy, Clone)]
-enum DBSourceRelation {
+pub(crate) enum DBSourceRelation {
     Alternate,
     Before,
 }
@@ -27,7 +29,7 @@ impl DBSourceRelation {
     /// This is with regards to the target
     /// Returns (placement, relation to put in DB).
     /// placement: true if the source id should be in the 1st spot, false if 2nd spot.
-    fn normalize(relation: Relation) -> (bool, DBSourceRelation) {
+    pub(crate) fn normalize(relation: Relation) -> (bool, DBSourceRelation) {
         match relation {
             // The source must become the target, so switch spots in the DB
             Relation::After => (false, Self::Before),
@@ -37,7 +39,7 @@ impl DBSourceRelation {
     }
     /// When a source is selected and pulled out of the DB, we will want to show a relationship
     /// that makes sense for it. Since we will show the relation with a link to the other source.
-    fn denormalize(self, first_spot: bool) -> Relation {
+    pub(crate) fn denormalize(self, first_spot: bool) -> Relation {
         match self {
             Self::Alternate => Relation::Alternate,
             Self::Before => if first_spot {
@@ -66,23 +68,28 @@ impl_sql_simple_enum!(DBSourceRelation);
 
 
 /// Inserts the LangMap into the database through the connection.
-fn insert_new_lang_map(map: &LangMap, db: &Connection) -> Result<(), String> {
+pub(crate) fn insert_new_lang_map(map: &LangMap, db: &Connection) -> Result<(), Error> {
     db.execute("INSERT INTO Strings (english, japanese) VALUES (?, ?)",
         &[
             map.get(&Lang::English),
             map.get(&Lang::Japanese)
         ]
-    ).map(|_| ()).map_err(|e| e.to_string())
+    ).map(|_| ()).map_err(Error::from)
 }
-fn prepare_strings_get(db: &Connection) -> Result<Statement, String> {
+pub(crate) fn prepare_strings_get(db: &Connection) -> Result<Statement, Error> {
     db.prepare("SELECT english, japanese FROM Strings WHERE id=?")
-        .map_err(|e| e.to_string())
+        .map_err(Error::from)
 }
 /// This assumes that the row was SELECTed from the prepare_strings_get()
-fn make_lang_strings_from_row(row: &Row) -> LangMap {
+pub(crate) fn make_lang_strings_from_row(row: &Row) -> LangMap {
+    make_lang_strings_from_row_at(row, 0)
+}
+/// The same as make_lang_strings_from_row(), with the english column at the index
+/// and the japanese column right after it
+pub(crate) fn make_lang_strings_from_row_at(row: &Row, index: usize) -> LangMap {
     let mut lang_map = LangMap::new();
-    let english: Option<String> = row.get_unwrap(0);
-    let japanese: Option<String> = row.get_unwrap(1);
+    let english: Option<String> = row.get_unwrap(index);
+    let japanese: Option<String> = row.get_unwrap(index + 1);
 
     if let Some(english) = english { lang_map.insert(Lang::English, english); }
     if let Some(japanese) = japanese { lang_map.insert(Lang::Japanese, japanese); }
@@ -92,7 +99,7 @@ fn make_lang_strings_from_row(row: &Row) -> LangMap {
 
 /// Make sure that we have good links that we can support (or want).
 /// Do this by verifying that they can
-fn transform_related_links(raw_links: Vec<(String, LangMap)>) -> Result<Vec<RelatedLink>, String> {
+pub(crate) fn transform_related_links(raw_links: Vec<(String, LangMap)>) -> Result<Vec<RelatedLink>, Error> {
     let mut links = Vec::with_capacity(raw_links.capacity());
 
     for (string_url, descriptions) in raw_links {
@@ -103,7 +110,7 @@ fn transform_related_links(raw_links: Vec<(String, LangMap)>) -> Result<Vec<Rela
     Ok(links)
 }
 
-fn collect_query_map<T, I>(iterator: SqlResult<I>) -> SqlResult< Vec<T> >
+pub(crate) fn collect_query_map<T, I>(iterator: SqlResult<I>) -> SqlResult< Vec<T> >
 where I: Iterator<Item = SqlResult<T>> {
     let iterator = iterator?;
     let mut new_vec = Vec::new();
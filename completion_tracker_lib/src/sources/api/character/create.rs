@@ -1,15 +1,15 @@
 use super::{CharacterCreator};
 use crate::db_link::{ConnectionHolder};
 use crate::sources::api;
+use crate::error::{Error};
 
-pub fn create(db: &ConnectionHolder, creator: CharacterCreator) -> Result<u64, String> {
+pub fn create(db: &ConnectionHolder, creator: CharacterCreator) -> Result<u64, Error> {
 
 
 unimplemented!();
     let db = db.lock();
 
-    let transaction = db.transaction()
-        .map_err(|e| e.to_string())?;
+    let transaction = db.transaction()?;
 
     transaction.execute_batch("
         CREATE TEMPORARY TABLE IF NOT EXISTS TempCharacterStrings (
@@ -21,12 +21,12 @@ unimplemented!();
         );
         DELETE FROM TempCharacterStrings;
         DELETE FROM TempCharacter;
-    ").map_err(|e| e.to_string())?;
+    ")?;
 
     api::insert_new_lang_map(&creator.names, &transaction)?;
     transaction.execute_batch("
         INSERT INTO TempCharacterStrings (names, descriptions) VALUES (last_insert_id(), NULL);
-    ").map_err(|e| e.to_string())?;
+    ")?;
     api::insert_new_lang_map(&creator.descriptions, &transaction)?;
     transaction.execute_batch("
         UPDATE TempCharacterStrings SET descriptions=last_insert_id();
@@ -35,12 +35,11 @@ unimplemented!();
             SELECT names, descriptions FROM TempCharacterStrings;
         INSERT INTO TempCharacter (character_id) VALUES (last_insert_id());
         DELETE FROM TempCharacterStrings;
-    ").map_err(|e| e.to_string())?;
+    ")?;
 
     // TODO The aliases and related characters
 
-    transaction.commit()
-        .map_err(|e| e.to_string())?;
+    transaction.commit()?;
 
     // TODO Get the created ID
 }
@@ -1,14 +1,34 @@
+mod filter;
+pub use self::filter::{
+    DateRange, FilterParseError, Qualifier, SearchFilter, StatusFilter,
+    parse_filter,
+};
 mod search_score;
+mod suggest;
+pub use self::suggest::{SuggestQuery, Suggestion, suggest};
+pub(crate) use self::suggest::{has_word_prefix};
+
+use std::{
+    cmp::{Ordering},
+    collections::{BTreeMap},
+};
 
+use schemars::{JsonSchema};
 use serde::{Deserialize, Serialize};
-use rusqlite::{Connection};
+use rusqlite::{Connection, params};
 
 use self::{
     search_score::{SearchScore},
 };
-use crate::db_link::{ConnectionHolder};
+use crate::{
+    TrackerDBs,
+    error::{Error, ResultExt},
+    impl_sql_simple_enum,
+    db_link::{ConnectionHolder},
+    simple_enum::{SimpleEnum},
+};
 
-#[derive(Copy, Clone, Deserialize, Serialize)]
+#[derive(Copy, Clone, Deserialize, Serialize, Eq, Ord, PartialEq, PartialOrd, JsonSchema)]
 pub enum ItemType {
     Character,
     Company,
@@ -16,7 +36,7 @@ pub enum ItemType {
     UniverseTag,
     Source,
 }
-impl ItemType {
+impl SimpleEnum for ItemType {
     fn all() -> &'static [ItemType] {
         &[
             Self::Character,
@@ -26,6 +46,18 @@ impl ItemType {
             Self::Source,
         ]
     }
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Character => "Character",
+            Self::Company => "Company",
+            Self::Person => "Person",
+            Self::UniverseTag => "UniverseTag",
+            Self::Source => "Source",
+        }
+    }
+}
+impl_sql_simple_enum!(ItemType);
+impl ItemType {
     fn table_name(self) -> &'static str {
         match self {
             Self::Character => "Characters",
@@ -35,49 +67,184 @@ impl ItemType {
             Self::Source => "Sources",
         }
     }
+    /// The (table name, ID column) of the aliases for the item type, if it can have any
+    fn alias_table(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Character => Some(("CharacterAliases", "character_id")),
+            Self::Person => Some(("PersonAliases", "person_id")),
+            Self::Company | Self::UniverseTag | Self::Source => None,
+        }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct SearchQuery {
+    /// The raw query, which can have qualifiers (see SearchFilter)
     pub query: String,
     pub item_type: Option<ItemType>,
+    /// The tracker to use for any `status:` qualifiers.
+    /// This can be left out if there's only a single tracker.
+    #[serde(default)]
+    pub tracker: Option<String>,
+    /// Multiplies the scores of an item type before they're ranked together.
+    /// Any missing item type has a boost of 1. eg. `{ "Source": 1.5 }` to prefer Sources.
+    #[serde(default)]
+    pub type_boosts: BTreeMap<ItemType, f64>,
 }
 
 pub type SearchResults = Vec<SearchResult>;
-#[derive(Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct SearchResult {
     pub id: u64,
     pub item_type: ItemType,
-    // A score for how well-matched this result is to the query
+    // A score for how well-matched this result is to the query.
+    // The best result will always be 1, so scores can be compared between item types.
     pub search_score: f64,
 }
 
-pub fn search(db: &ConnectionHolder, query: SearchQuery) -> Result<SearchResults, String> {
-    let db = db.lock();
+/// Results that score less than this fraction of the top score are cut off
+const SEARCH_SCORE_CUTOFF: f64 = 0.50;
 
-    if let Some(item_type) = query.item_type {
-        search_names_and_descriptions(&db, &query.query, item_type)
-    } else {
-        let mut all_search_results = Vec::new();
-        for &item_type in ItemType::all() {
-            all_search_results.append(
-                &mut search_names_and_descriptions(&db, &query.query, item_type)?
-            );
+pub fn search(db: &ConnectionHolder, tracker_dbs: &TrackerDBs, query: SearchQuery)
+-> Result<SearchResults, Error> {
+    let filter = filter::parse_filter(&query.query)
+        .context("Failed to parse the query")?;
+    validate_type_boosts(&query.type_boosts)?;
+    // A query with only qualifiers finds everything they allow, but a blank one finds nothing
+    if filter.is_empty() {
+        return Ok(SearchResults::new());
+    }
+
+    // Look up the statuses first so that we don't need to hold both DBs at once
+    let mut status_source_ids = Vec::new();
+    if filter.status_filters().next().is_some() {
+        let tracker_db = crate::find_tracker_db(tracker_dbs, query.tracker.as_ref())?.lock();
+        for status_filter in filter.status_filters() {
+            let (tracker_sql, statuses) = status_filter.tracker_sql();
+            let mut statement = tracker_db.prepare(tracker_sql)?;
+            let source_ids = super::collect_query_map(statement.query_map(&statuses, |row| {
+                let source_id: i64 = row.get(0)?;
+                Ok(source_id)
+            }))?;
+            status_source_ids.push(source_ids);
+        }
+    }
+
+    let mut db = db.lock();
+    // Only a status qualifier needs the temp table, so most searches don't need to write
+    if !status_source_ids.is_empty() {
+        let transaction = db.transaction()?;
+
+        {
+            transaction.execute_batch("
+                CREATE TEMPORARY TABLE IF NOT EXISTS TempSearchStatus (
+                    qualifier INTEGER NOT NULL,
+                    source_id INTEGER NOT NULL
+                );
+                DELETE FROM TempSearchStatus;
+            ")?;
+            let mut insert_status_statement = transaction.prepare("
+                INSERT INTO TempSearchStatus (qualifier, source_id) VALUES (?, ?)
+            ")?;
+            for (qualifier, source_ids) in status_source_ids.into_iter().enumerate() {
+                for source_id in source_ids {
+                    insert_status_statement.execute(params![qualifier as i64, source_id])?;
+                }
+            }
+        }
+
+        transaction.commit()?;
+    }
+
+    let mut all_search_results = Vec::new();
+    for &item_type in ItemType::all() {
+        let is_wanted = query.item_type.is_none_or(|wanted_type| wanted_type == item_type);
+        if is_wanted && filter.allows_item_type(item_type) {
+            let boost = query.type_boosts.get(&item_type).copied().unwrap_or(1.0);
+            let mut search_results = search_names_and_descriptions(&db, &filter, item_type)?;
+            for search_result in search_results.iter_mut() {
+                search_result.search_score *= boost;
+            }
+            all_search_results.append(&mut search_results);
+        }
+    }
+
+    Ok(rank_search_results(all_search_results))
+}
+
+/// The boosts can't be negative, or they would flip the order of the scores
+pub(crate) fn validate_type_boosts(type_boosts: &BTreeMap<ItemType, f64>) -> Result<(), Error> {
+    for (item_type, boost) in type_boosts.iter() {
+        if !boost.is_finite() || *boost < 0.0 {
+            return Err(Error::Validation(format!(
+                "The boost for {} must be a number that isn't negative, not {}",
+                item_type.as_str(), boost)));
         }
+    }
+    Ok(())
+}
 
-        Ok(all_search_results)
+/// Ranks all of the results together, no matter their item type.
+/// The scores are normalized against the top score, then cut off at SEARCH_SCORE_CUTOFF.
+fn rank_search_results(search_results: SearchResults) -> SearchResults {
+    let top_score = search_results.iter()
+        .fold(0_f64, |top_score, search_result| top_score.max(search_result.search_score));
+    if top_score <= 0.0 {
+        return SearchResults::new();
     }
+
+    let mut search_results: SearchResults = search_results.into_iter()
+        .filter(|search_result| search_result.search_score > (top_score * SEARCH_SCORE_CUTOFF))
+        .map(|mut search_result| {
+            search_result.search_score /= top_score;
+            search_result
+        })
+        .collect();
+    // Ties are kept in a stable order so the same query always gives the same list
+    search_results.sort_by(|result1, result2| {
+        result2.search_score.partial_cmp(&result1.search_score)
+            .unwrap_or(Ordering::Equal)
+            .then(result1.item_type.cmp(&result2.item_type))
+            .then(result1.id.cmp(&result2.id))
+    });
+    search_results
 }
 
-fn search_names_and_descriptions(db: &Connection, query: &str, item_type: ItemType)
--> Result<SearchResults, String> {
+fn search_names_and_descriptions(db: &Connection, filter: &SearchFilter, item_type: ItemType)
+-> Result<SearchResults, Error> {
+    let query = filter.fuzzy_query();
+    let query = query.as_str();
+    // Only a Source can have a predicate (see SearchFilter::allows_item_type())
+    let (predicate, predicate_params) = match item_type {
+        ItemType::Source => filter.source_predicate(),
+        _ => ("1".to_string(), Vec::new()),
+    };
+
+    // Without any words to score, everything that made it through the qualifiers matches
+    if query.is_empty() {
+        let mut statement = db.prepare(&format!("
+            SELECT {0}.id FROM {0} WHERE {1}
+        ", item_type.table_name(), predicate))?;
+        let ids = super::collect_query_map(statement.query_map(&predicate_params, |row| {
+            let id: i64 = row.get(0)?;
+            Ok(id as u64)
+        }))?;
+
+        return Ok(ids.into_iter().map(|id| SearchResult {
+            id,
+            item_type,
+            search_score: 1.0,
+        }).collect());
+    }
+
     // TODO We will want to get aliases for sources
     let mut statement = db.prepare(&format!("
         SELECT {0}.id, Strings.english, Strings.japanese FROM Strings
             INNER JOIN {0}
             ON Strings.id={0}.names OR Strings.id={0}.descriptions
-    ", item_type.table_name())).map_err(|e| e.to_string())?;
-    let mapped_results = statement.query_map(rusqlite::NO_PARAMS, |row| {
+            WHERE {1}
+    ", item_type.table_name(), predicate))?;
+    let mapped_results = statement.query_map(&predicate_params, |row| {
         let id: i64 = row.get(0)?;
         let english: Option<String> = row.get(1)?;
         let japanese: Option<String> = row.get(2)?;
@@ -89,24 +256,26 @@ fn search_names_and_descriptions(db: &Connection, query: &str, item_type: ItemTy
             japanese.map(|japanese| make_search_score(&japanese, query)),
         ];
         let mut top_score = None;
-        for score in scores {
-            if let Some(score) = score {
-                if let Some(top_score) = top_score.as_mut() {
-                    if &score > top_score {
-                        *top_score = score;
-                    }
-                } else {
-                    top_score = Some(score);
+        for score in scores.into_iter().flatten() {
+            if let Some(top_score) = top_score.as_mut() {
+                if &score > top_score {
+                    *top_score = score;
                 }
+            } else {
+                top_score = Some(score);
             }
         }
 
-        Ok( (id as u64, top_score.unwrap()) )
-    }).map_err(|e| e.to_string())?;
+        Ok( (id as u64, top_score) )
+    })?;
 
     let mut search_results = SearchResults::new();
     for search_result in mapped_results {
-        let (id, search_score) = search_result.map_err(|e| e.to_string())?;
+        // An empty description won't have any strings to score
+        let (id, search_score) = match search_result? {
+            (id, Some(search_score)) => (id, search_score),
+            (_, None) => continue,
+        };
         // Search the ones that we have to see if we can combine any search results
         let try_is_dup = search_results.iter_mut()
         .find(|result| result.id == id);
@@ -121,76 +290,271 @@ fn search_names_and_descriptions(db: &Connection, query: &str, item_type: ItemTy
         }
     }
 
-    // Cutoff at 50% of the highest score
-    let top_score = search_results.iter()
-        .fold(0_f64, |top_score, search_result| {
-            if search_result.search_score > top_score {
-                search_result.search_score
-            } else {
-                top_score
-            }
-        });
-    let search_results = search_results.into_iter().filter(|search_result| {
-        search_result.search_score > (top_score * 0.50)
-    }).collect();
     Ok(search_results)
 }
 
+/// Escapes any LIKE wildcards in the value, for use with `ESCAPE '\'`.
+/// The values come from the user, so we can't let them use any wildcards themselves
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
 /// Good searching criteria:
 /// 1. Any haystack and query that match at the beginning should show up first
 /// 2. We should be able to match fragments of the query in the haystack
 /// 3. Matching a lot of characters should be able to out-score a small match
 /// 4. Matching the haystack entirely should come before the same full match but with a
 ///    bigger haystack
+/// 5. A query with a typo (a missing, extra, wrong or swapped character) should still match
+///    what it was meant to, as long as there aren't too many typos for the query's length
+///
+/// Characters are matched without caring about their case.
 ///
 /// Returns the search score. Will be 0 if there was no match
 fn make_search_score(haystack: &str, query: &str) -> SearchScore {
-    // Chain Score X Position
-    // Search incrementally from the front of the haystack and query
-    let query_chars: Vec<char> = query.chars().collect();
-    let haystack_chars: Vec<char> = haystack.chars().collect();
+    let query_chars: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let haystack_chars: Vec<char> = haystack.chars().flat_map(char::to_lowercase).collect();
 
     let query_len = query_chars.len();
     let haystack_len = haystack_chars.len();
 
-    // See if any character in the query can match the character
-    // Returns the Option<index> of the matched character in the query
-    let can_match_char = |c, start_index| -> Option<usize> {
-        for i in start_index..query_chars.len() {
-            if query_chars[i] == c {
-                return Some(i);
+    // Chain Score X Position
+    // Each exact fragment of the query that's found in the haystack (in order) makes a chain
+    let mut fragment_score = SearchScore::new(query_len, haystack_len);
+    // Single characters only make sense as a fragment if that's all there is in the query
+    let min_fragment_len = query_len.min(2);
+    for (haystack_start_index, matched_char_count) in
+        find_fragments(&haystack_chars, &query_chars, min_fragment_len) {
+        fragment_score.update(matched_char_count, haystack_start_index);
+    }
+
+    // Try to match the entire query in one go, allowing for some typos
+    let mut typo_score = SearchScore::new(query_len, haystack_len);
+    let max_edits = query_len / CHARS_PER_TYPO;
+    if max_edits > 0 {
+        if let Some(typo_match) = find_typo_match(&haystack_chars, &query_chars) {
+            if typo_match.edits <= max_edits {
+                typo_score.update_with_edits(
+                    typo_match.haystack_len - typo_match.edits.min(typo_match.haystack_len),
+                    typo_match.haystack_start_index,
+                    typo_match.edits,
+                );
             }
         }
-        None
-    };
+    }
 
-    let mut search_score = SearchScore::new(query_len, haystack_len);
-    // The number of consecutively matched chars (currently)
-    let mut matched_chars: Option<usize> = None;
-    // The character that we haven't found yet
-    let mut query_index = 0_usize;
-
-    for (haystack_index, c) in haystack_chars.into_iter().enumerate() {
-        if let Some(i) = can_match_char(c, query_index) {
-            query_index = i;
-            let matched_char_count = matched_chars.get_or_insert(0);
-            *matched_char_count += 1;
-
-            // Early termination if we've exhausted the query and won't be able to match again
-            if query_index + 1 == query_len {
-                break;
+    if typo_score > fragment_score {
+        typo_score
+    } else {
+        fragment_score
+    }
+}
+
+/// The number of query characters that are needed for every typo that we allow
+const CHARS_PER_TYPO: usize = 4;
+
+/// Finds the exact fragments of the query that are in the haystack.
+/// The longest common fragment is found first, then the same is done on either side of it, so
+/// the fragments will always be in the same order in both the haystack and the query.
+/// This way, a character can never be used in more than one fragment.
+///
+/// Returns a list of (haystack start index, fragment length)
+fn find_fragments(haystack: &[char], query: &[char], min_fragment_len: usize)
+-> Vec<(usize, usize)> {
+    let mut fragments = Vec::new();
+    // Work on (haystack start, haystack end, query start, query end) ranges
+    let mut ranges = vec![(0, haystack.len(), 0, query.len())];
+
+    while let Some((haystack_start, haystack_end, query_start, query_end)) = ranges.pop() {
+        let (haystack_index, query_index, fragment_len) = longest_common_fragment(
+            &haystack[haystack_start..haystack_end],
+            &query[query_start..query_end],
+        );
+        if fragment_len == 0 || fragment_len < min_fragment_len {
+            continue;
+        }
+        let haystack_index = haystack_start + haystack_index;
+        let query_index = query_start + query_index;
+        fragments.push((haystack_index, fragment_len));
+
+        ranges.push((haystack_start, haystack_index, query_start, query_index));
+        ranges.push((
+            haystack_index + fragment_len, haystack_end,
+            query_index + fragment_len, query_end,
+        ));
+    }
+
+    fragments.sort();
+    fragments
+}
+
+/// Finds the longest run of characters that are in both.
+/// An earlier match in the haystack wins when there's a tie.
+///
+/// Returns (haystack index, query index, length)
+fn longest_common_fragment(haystack: &[char], query: &[char]) -> (usize, usize, usize) {
+    let mut longest = (0, 0, 0);
+    // The length of the common run that ends at the previous haystack character
+    let mut previous_row = vec![0_usize; query.len() + 1];
+    let mut current_row = vec![0_usize; query.len() + 1];
+
+    for (haystack_index, haystack_char) in haystack.iter().enumerate() {
+        for (query_index, query_char) in query.iter().enumerate() {
+            current_row[query_index + 1] = if haystack_char == query_char {
+                previous_row[query_index] + 1
+            } else {
+                0
+            };
+
+            let run_len = current_row[query_index + 1];
+            if run_len > longest.2 {
+                longest = (haystack_index + 1 - run_len, query_index + 1 - run_len, run_len);
             }
-        } else {
-            if let Some(matched_char_count) = matched_chars.take() {
-                search_score.update(matched_char_count, haystack_index);
+        }
+        std::mem::swap(&mut previous_row, &mut current_row);
+    }
+
+    longest
+}
+
+struct TypoMatch {
+    /// The number of insertions, deletions, substitutions and swaps that were needed
+    edits: usize,
+    haystack_start_index: usize,
+    /// The length of the part of the haystack that was matched
+    haystack_len: usize,
+}
+
+/// Finds the part of the haystack that needs the fewest edits to become the query.
+/// A swap of two characters next to each other only counts as a single edit.
+/// An earlier match in the haystack wins when there's a tie.
+fn find_typo_match(haystack: &[char], query: &[char]) -> Option<TypoMatch> {
+    if query.is_empty() || haystack.is_empty() {
+        return None;
+    }
+
+    // Each cell is (edits, haystack start index) for the query up to i and the haystack up to j.
+    // The match is free to start anywhere in the haystack, so the first row costs nothing.
+    let row_len = haystack.len() + 1;
+    let mut rows: Vec<Vec<(usize, usize)>> = Vec::with_capacity(query.len() + 1);
+    rows.push((0..row_len).map(|j| (0, j)).collect());
+
+    for i in 1..=query.len() {
+        let mut row = Vec::with_capacity(row_len);
+        row.push((i, 0));
+        for j in 1..row_len {
+            let previous_row = &rows[i - 1];
+            let substitution_cost = if query[i - 1] == haystack[j - 1] { 0 } else { 1 };
+
+            let mut best = (previous_row[j - 1].0 + substitution_cost, previous_row[j - 1].1);
+            // The query has an extra character
+            if previous_row[j].0 + 1 < best.0 {
+                best = (previous_row[j].0 + 1, previous_row[j].1);
             }
+            // The query is missing a character
+            if row[j - 1].0 + 1 < best.0 {
+                best = (row[j - 1].0 + 1, row[j - 1].1);
+            }
+            // Two characters were swapped
+            if i > 1 && j > 1 &&
+                query[i - 1] == haystack[j - 2] && query[i - 2] == haystack[j - 1] {
+                let swapped = rows[i - 2][j - 2];
+                if swapped.0 + 1 < best.0 {
+                    best = (swapped.0 + 1, swapped.1);
+                }
+            }
+            row.push(best);
+        }
+        rows.push(row);
+    }
+
+    let last_row = rows.last().unwrap();
+    let mut best_match: Option<TypoMatch> = None;
+    for (j, &(edits, haystack_start_index)) in last_row.iter().enumerate().skip(1) {
+        let is_better = best_match.as_ref().is_none_or(|best_match| {
+            edits < best_match.edits ||
+                (edits == best_match.edits &&
+                    haystack_start_index < best_match.haystack_start_index)
+        });
+        if is_better {
+            best_match = Some(TypoMatch {
+                edits,
+                haystack_start_index,
+                haystack_len: j - haystack_start_index,
+            });
         }
     }
+    best_match
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(haystack: &str, query: &str) -> f64 { make_search_score(haystack, query).score() }
 
-    if let Some(matched_char_count) = matched_chars.take() {
-        // Using the length will ensure that the start_index can be 0 if the match starts at 0
-        search_score.update(matched_char_count, haystack_len);
+    #[test]
+    fn matching_at_the_beginning_comes_first() {
+        assert!(score("Fate Zero", "Fate") > score("Zero Fate", "Fate"));
+        assert!(score("Evangelion", "Eva") > score("Neon Genesis Evangelion", "Eva"));
     }
 
-    search_score
+    #[test]
+    fn fragments_of_the_query_match() {
+        assert!(score("Fate/Zero", "Fate Zero") > 0.0);
+        assert!(score("Fate/Zero", "Zero Fate") > 0.0);
+        assert!(score("Fate/Zero", "Fate Zero") > score("Fate/Zero", "Zero Fate"));
+    }
+
+    #[test]
+    fn matching_more_characters_wins() {
+        assert!(score("Evangelion: 1.0", "Evangelion") > score("Eva", "Evangelion"));
+        assert!(score("Sword Art Online", "Sword Art") > score("Art Online", "Sword Art"));
+    }
+
+    #[test]
+    fn matching_the_entire_haystack_wins() {
+        assert!(score("Fate", "Fate") > score("Fate/Zero", "Fate"));
+        assert!(score("Fate/Zero", "Fate") > score("Fate/stay night", "Fate"));
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(score("FATE/ZERO", "fate"), score("Fate/Zero", "Fate"));
+    }
+
+    #[test]
+    fn no_match_is_zero() {
+        assert_eq!(score("Fate/Zero", "Gundam"), 0.0);
+        assert_eq!(score("Fate/Zero", ""), 0.0);
+        assert_eq!(score("", "Fate"), 0.0);
+    }
+
+    #[test]
+    fn repeated_characters_are_only_matched_once() {
+        assert_eq!(score("aaaa", "ab"), 0.0);
+        assert!(score("aa", "aa") > score("aaaaaaaa", "aa"));
+    }
+
+    #[test]
+    fn typos_still_match() {
+        let exact = score("Evangelion", "Evangelion");
+        // Missing, extra, wrong and swapped characters
+        for typo in &["Evangelon", "Evangeliion", "Evangelian", "Evnagelion"] {
+            let typo_score = score("Evangelion", typo);
+            assert!(typo_score > exact / 2.0, "{} only scored {}", typo, typo_score);
+            assert!(typo_score < exact, "{} scored as well as an exact match", typo);
+        }
+    }
+
+    #[test]
+    fn typos_are_bounded_by_the_query_length() {
+        // Short queries don't get any typos
+        assert_eq!(score("Eva", "Ewa"), 0.0);
+        // 2 typos is too many for a query with less than 8 characters
+        assert!(score("Gundam", "Gandum") < score("Gundam", "Gundum"));
+        assert!(score("Evangelion", "Evangelon") > score("Evangelion", "Evngelon"));
+    }
 }
@@ -0,0 +1,683 @@
+use std::{
+    fmt,
+    iter::{Peekable},
+    str::{Chars, FromStr},
+};
+
+use chrono::{Datelike, NaiveDate};
+
+use super::{ItemType};
+use crate::{
+    error::{Error},
+    simple_enum::{SimpleEnum},
+    sources::source_types::{SourceType},
+    tracking::{CompletionStatus},
+};
+
+/// A search query that has been split into the bare words and the qualifiers.
+/// eg. `type:novel tag:"Fate" person:Nasu year:2004..2010 status:unfinished Zero`
+///
+/// The bare words are matched with the fuzzy scorer,
+/// while every qualifier must be true for an item to show up at all.
+pub struct SearchFilter {
+    pub words: Vec<String>,
+    pub qualifiers: Vec<Qualifier>,
+}
+impl SearchFilter {
+    /// If there's nothing to search for at all, like when the query is blank
+    pub fn is_empty(&self) -> bool { self.words.is_empty() && self.qualifiers.is_empty() }
+
+    /// The bare words put back together, ready for the fuzzy scorer
+    pub fn fuzzy_query(&self) -> String { self.words.join(" ") }
+
+    /// If any of the qualifiers can only be true for a Source
+    pub fn is_source_only(&self) -> bool {
+        self.qualifiers.iter().any(|qualifier| !matches!(qualifier, Qualifier::ItemType(_)))
+    }
+
+    /// If the item type is allowed by all of the ItemType qualifiers
+    pub fn allows_item_type(&self, item_type: ItemType) -> bool {
+        if self.is_source_only() && item_type != ItemType::Source {
+            return false;
+        }
+        self.qualifiers.iter().all(|qualifier| match qualifier {
+            Qualifier::ItemType(wanted_type) => *wanted_type == item_type,
+            _ => true,
+        })
+    }
+
+    /// The status qualifiers, which need a tracker's DB to be resolved
+    pub fn status_filters(&self) -> impl Iterator<Item = StatusFilter> + '_ {
+        self.qualifiers.iter().filter_map(|qualifier| match qualifier {
+            Qualifier::Status(status_filter) => Some(*status_filter),
+            _ => None,
+        })
+    }
+
+    /// Compiles the qualifiers into a predicate for a WHERE clause on the Sources table.
+    /// The status qualifiers will look in the TempSearchStatus table, which must be filled out
+    /// with the source IDs from `StatusFilter::tracker_sql()`, using the qualifier's position
+    /// among the status filters.
+    ///
+    /// Returns the predicate with all of its parameters in order.
+    pub fn source_predicate(&self) -> (String, Vec<String>) {
+        let mut predicates = vec!["1".to_string()];
+        let mut params = Vec::new();
+        let mut status_index = 0_usize;
+
+        for qualifier in self.qualifiers.iter() {
+            match qualifier {
+                Qualifier::ItemType(_) => (),
+                Qualifier::SourceType(source_type) => {
+                    predicates.push("Sources.source_type=?".to_string());
+                    params.push(source_type.as_str().to_string());
+                },
+                Qualifier::UniverseTag(name) => {
+                    // Any of the children of a matching Universe Tag also count
+                    predicates.push("
+                        Sources.id IN (SELECT source_id FROM SourceUniverseTags
+                            WHERE universe_tag_id IN (
+                                WITH RECURSIVE MatchedTags(id) AS (
+                                    SELECT UniverseTags.id FROM UniverseTags
+                                        INNER JOIN Strings ON Strings.id=UniverseTags.names
+                                        WHERE Strings.english LIKE ? ESCAPE '\\'
+                                            OR Strings.japanese LIKE ? ESCAPE '\\'
+                                    UNION
+                                    SELECT UniverseTagChildren.child FROM UniverseTagChildren
+                                        INNER JOIN MatchedTags
+                                        ON UniverseTagChildren.parent=MatchedTags.id
+                                )
+                                SELECT id FROM MatchedTags
+                            )
+                        )
+                    ".to_string());
+                    params.push(like_pattern(name));
+                    params.push(like_pattern(name));
+                },
+                Qualifier::Person(name) => {
+                    predicates.push("
+                        Sources.id IN (SELECT source_id FROM SourcePeople
+                            WHERE person_id IN (
+                                SELECT People.id FROM People
+                                    INNER JOIN Strings ON Strings.id=People.names
+                                    WHERE Strings.english LIKE ? ESCAPE '\\'
+                                        OR Strings.japanese LIKE ? ESCAPE '\\'
+                                UNION
+                                SELECT person_id FROM PersonAliases WHERE alias LIKE ? ESCAPE '\\'
+                            )
+                        )
+                    ".to_string());
+                    params.push(like_pattern(name));
+                    params.push(like_pattern(name));
+                    params.push(like_pattern(name));
+                },
+                Qualifier::Company(name) => {
+                    predicates.push("
+                        Sources.id IN (SELECT source_id FROM SourceCompanies
+                            WHERE company_id IN (
+                                SELECT Companies.id FROM Companies
+                                    INNER JOIN Strings ON Strings.id=Companies.names
+                                    WHERE Strings.english LIKE ? ESCAPE '\\'
+                                        OR Strings.japanese LIKE ? ESCAPE '\\'
+                            )
+                        )
+                    ".to_string());
+                    params.push(like_pattern(name));
+                    params.push(like_pattern(name));
+                },
+                Qualifier::Date(date_range) => {
+                    let mut date_predicates = vec!["1".to_string()];
+                    if let Some(start) = date_range.start {
+                        date_predicates.push("date>=?".to_string());
+                        params.push(start.format("%Y-%m-%d").to_string());
+                    }
+                    if let Some(end) = date_range.end {
+                        date_predicates.push("date<?".to_string());
+                        params.push(end.format("%Y-%m-%d").to_string());
+                    }
+                    predicates.push(format!(
+                        "Sources.id IN (SELECT source_id FROM SourceDates WHERE {})",
+                        date_predicates.join(" AND "),
+                    ));
+                },
+                Qualifier::Status(status_filter) => {
+                    let in_or_not = if status_filter.is_exclusion() { "NOT IN" } else { "IN" };
+                    predicates.push(format!(
+                        "Sources.id {} (SELECT source_id FROM TempSearchStatus WHERE qualifier={})",
+                        in_or_not, status_index,
+                    ));
+                    status_index += 1;
+                },
+            }
+        }
+
+        (predicates.join(" AND "), params)
+    }
+}
+
+/// A single `key:value` part of the query
+pub enum Qualifier {
+    ItemType(ItemType),
+    SourceType(SourceType),
+    /// The name (or part of it) of a Universe Tag
+    UniverseTag(String),
+    /// The name or alias (or part of it) of a Person who worked on the source
+    Person(String),
+    /// The name (or part of it) of a Company that worked on the source
+    Company(String),
+    /// The source has a dated event within the range
+    Date(DateRange),
+    /// The completion status of the source in a tracker
+    Status(StatusFilter),
+}
+
+/// A range of dates, where the start is inclusive and the end is exclusive.
+/// A missing side is unbounded.
+#[derive(Copy, Clone)]
+pub struct DateRange {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+#[derive(Copy, Clone)]
+pub enum StatusFilter {
+    /// It's being tracked, but hasn't been finished yet
+    Unfinished,
+    /// It has reached the status at some point
+    Reached(CompletionStatus),
+    /// It isn't being tracked at all
+    Untracked,
+}
+impl StatusFilter {
+    /// If the matched sources from `tracker_sql()` should be excluded instead of included
+    pub fn is_exclusion(self) -> bool { matches!(self, Self::Untracked) }
+
+    /// The query to run against a tracker's DB to find the matching source IDs
+    /// Returns the query with all of its parameters in order.
+    pub fn tracker_sql(self) -> (&'static str, Vec<CompletionStatus>) {
+        match self {
+            Self::Unfinished => ("
+                SELECT source_id FROM TrackerEntries
+                    WHERE id NOT IN (SELECT entry_id FROM TrackerEntryStatusUpdates
+                        WHERE status=? OR status=?)
+            ", vec![CompletionStatus::Complete, CompletionStatus::GameComplete]),
+            Self::Reached(status) => ("
+                SELECT source_id FROM TrackerEntries
+                    WHERE id IN (SELECT entry_id FROM TrackerEntryStatusUpdates WHERE status=?)
+            ", vec![status]),
+            Self::Untracked => ("SELECT source_id FROM TrackerEntries", vec![]),
+        }
+    }
+}
+
+/// An error from parsing a query.
+/// The position is the index of the character (not byte) where the problem is.
+pub struct FilterParseError {
+    pub position: usize,
+    pub message: String,
+}
+impl fmt::Display for FilterParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.position)
+    }
+}
+impl From<FilterParseError> for Error {
+    fn from(e: FilterParseError) -> Error { Error::Validation(e.to_string()) }
+}
+
+/// Parses the raw query into a SearchFilter
+pub fn parse_filter(query: &str) -> Result<SearchFilter, FilterParseError> {
+    let mut tokenizer = Tokenizer {
+        chars: query.chars().peekable(),
+        position: 0,
+    };
+    let mut filter = SearchFilter {
+        words: Vec::new(),
+        qualifiers: Vec::new(),
+    };
+
+    while let Some(token) = tokenizer.next_token()? {
+        if let Some((key, value, value_position)) = token.qualifier {
+            filter.qualifiers.push(
+                parse_qualifier(&key, &value, token.position, value_position)?
+            );
+        } else {
+            filter.words.push(token.text);
+        }
+    }
+
+    Ok(filter)
+}
+
+fn parse_qualifier(key: &str, value: &str, key_position: usize, value_position: usize)
+-> Result<Qualifier, FilterParseError> {
+    let value_error = |message: String| FilterParseError {
+        position: value_position,
+        message,
+    };
+    if value.is_empty() {
+        return Err(value_error(format!("The '{}' qualifier needs a value", key)));
+    }
+
+    match key.to_lowercase().as_str() {
+        "type" => {
+            if let Some(item_type) = find_ignore_case(ItemType::all(), value, |item_type| {
+                item_type.as_str()
+            }) {
+                Ok(Qualifier::ItemType(item_type))
+            } else if let Some(source_type) =
+                find_ignore_case(SourceType::all(), value, |source_type| source_type.as_str()) {
+                Ok(Qualifier::SourceType(source_type))
+            } else {
+                Err(value_error(format!("'{}' is not an Item Type or a Source Type", value)))
+            }
+        },
+        "tag" => Ok(Qualifier::UniverseTag(value.to_string())),
+        "person" => Ok(Qualifier::Person(value.to_string())),
+        "company" => Ok(Qualifier::Company(value.to_string())),
+        "year" | "date" => parse_date_range(value, value_position).map(Qualifier::Date),
+        "status" => {
+            let status_filter = match value.to_lowercase().as_str() {
+                "unfinished" => StatusFilter::Unfinished,
+                "untracked" => StatusFilter::Untracked,
+                _ => if let Some(status) =
+                    find_ignore_case(CompletionStatus::all(), value, |status| status.as_str()) {
+                    StatusFilter::Reached(status)
+                } else {
+                    return Err(value_error(format!("'{}' is not a completion status", value)));
+                },
+            };
+            Ok(Qualifier::Status(status_filter))
+        },
+        _ => Err(FilterParseError {
+            position: key_position,
+            message: format!("Unknown qualifier '{}'", key),
+        }),
+    }
+}
+
+/// Parses a single date (`2004`) or a range (`2004..2010`, `2004-04..`, `..2010-05-31`).
+/// Both ends of a range are inclusive, so `2004..2010` runs until the end of 2010.
+fn parse_date_range(value: &str, value_position: usize) -> Result<DateRange, FilterParseError> {
+    if let Some(dots_index) = value.find("..") {
+        let start_str = &value[..dots_index];
+        let end_str = &value[dots_index + 2..];
+        let end_position = value_position + value[..dots_index + 2].chars().count();
+
+        let start = if start_str.is_empty() {
+            None
+        } else {
+            Some(parse_date_period(start_str, value_position)?.start.unwrap())
+        };
+        let end = if end_str.is_empty() {
+            None
+        } else {
+            Some(parse_date_period(end_str, end_position)?.end.unwrap())
+        };
+        if start.is_none() && end.is_none() {
+            return Err(FilterParseError {
+                position: value_position,
+                message: "A date range needs at least a start or an end".to_string(),
+            });
+        }
+        if let (Some(start), Some(end)) = (start, end) {
+            if start >= end {
+                return Err(FilterParseError {
+                    position: end_position,
+                    message: format!("The date range '{}' ends before it starts", value),
+                });
+            }
+        }
+        Ok(DateRange { start, end })
+    } else {
+        parse_date_period(value, value_position)
+    }
+}
+
+/// Parses a year (`2004`), month (`2004-05`) or day (`2004-05-31`) into the range that covers it
+fn parse_date_period(value: &str, position: usize) -> Result<DateRange, FilterParseError> {
+    let date_error = || FilterParseError {
+        position,
+        message: format!("'{}' is not a date like 2004, 2004-05 or 2004-05-31", value),
+    };
+
+    let mut parts = value.split('-');
+    // The year is parsed as an i32 so that anything too big for a NaiveDate is an error
+    let year: i32 = parse_date_part(parts.next().unwrap_or_default(), &date_error)?;
+    let month: Option<u32> = parts.next()
+        .map(|part| parse_date_part(part, &date_error))
+        .transpose()?;
+    let day: Option<u32> = parts.next()
+        .map(|part| parse_date_part(part, &date_error))
+        .transpose()?;
+    if parts.next().is_some() {
+        return Err(date_error());
+    }
+
+    let (start, end) = match (month, day) {
+        (None, _) => {
+            let end = year.checked_add(1)
+                .and_then(|next_year| NaiveDate::from_ymd_opt(next_year, 1, 1));
+            (NaiveDate::from_ymd_opt(year, 1, 1), end)
+        },
+        (Some(month), None) => {
+            let start = NaiveDate::from_ymd_opt(year, month, 1);
+            let end = start.and_then(|start| if start.month() == 12 {
+                start.year().checked_add(1)
+                    .and_then(|next_year| NaiveDate::from_ymd_opt(next_year, 1, 1))
+            } else {
+                NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)
+            });
+            (start, end)
+        },
+        (Some(month), Some(day)) => {
+            let start = NaiveDate::from_ymd_opt(year, month, day);
+            (start, start.and_then(|start| start.succ_opt()))
+        },
+    };
+
+    match (start, end) {
+        (Some(start), Some(end)) => Ok(DateRange {
+            start: Some(start),
+            end: Some(end),
+        }),
+        _ => Err(date_error()),
+    }
+}
+
+/// Parses one of the numbers in a date, which can only be made of digits
+fn parse_date_part<T: FromStr>(part: &str, date_error: &impl Fn() -> FilterParseError)
+-> Result<T, FilterParseError> {
+    if !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()) {
+        part.parse().map_err(|_| date_error())
+    } else {
+        Err(date_error())
+    }
+}
+
+fn find_ignore_case<T: Copy>(all: &[T], value: &str, as_str: impl Fn(&T) -> &'static str)
+-> Option<T> {
+    all.iter()
+        .find(|item| as_str(item).eq_ignore_ascii_case(value))
+        .copied()
+}
+
+/// Makes a LIKE pattern that matches any part of a string
+fn like_pattern(value: &str) -> String {
+    format!("%{}%", super::escape_like(value))
+}
+
+struct Token {
+    /// The character position where the token starts
+    position: usize,
+    text: String,
+    /// The (key, value, value position) if this is a qualifier
+    qualifier: Option<(String, String, usize)>,
+}
+
+struct Tokenizer<'a> {
+    chars: Peekable<Chars<'a>>,
+    /// The character position of the next character
+    position: usize,
+}
+impl Tokenizer<'_> {
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c.is_some() {
+            self.position += 1;
+        }
+        c
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, FilterParseError> {
+        while let Some(c) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.next_char();
+            } else {
+                break;
+            }
+        }
+        let position = self.position;
+        if self.chars.peek().is_none() {
+            return Ok(None);
+        }
+
+        let text = self.read_text(true)?;
+        // A qualifier is only there if the key wasn't quoted
+        if self.chars.peek() == Some(&':') && !text.is_empty() && !text.quoted {
+            self.next_char();
+            let value_position = self.position;
+            let value = self.read_text(false)?;
+            Ok(Some(Token {
+                position,
+                text: text.value.clone(),
+                qualifier: Some((text.value, value.value, value_position)),
+            }))
+        } else {
+            Ok(Some(Token {
+                position,
+                text: text.value,
+                qualifier: None,
+            }))
+        }
+    }
+
+    /// Reads a single word or a quoted string (which can have spaces).
+    /// A key has to stop at a colon, but a value can keep going (eg. `tag:Re:Zero`).
+    fn read_text(&mut self, is_key: bool) -> Result<TokenText, FilterParseError> {
+        let mut value = String::new();
+        if self.chars.peek() == Some(&'"') {
+            let quote_position = self.position;
+            self.next_char();
+            loop {
+                match self.next_char() {
+                    Some('"') => break,
+                    Some(c) => value.push(c),
+                    None => return Err(FilterParseError {
+                        position: quote_position,
+                        message: "This quote is never closed".to_string(),
+                    }),
+                }
+            }
+            Ok(TokenText { value, quoted: true })
+        } else {
+            while let Some(&c) = self.chars.peek() {
+                if c.is_whitespace() || c == '"' || (is_key && c == ':') {
+                    break;
+                }
+                value.push(c);
+                self.next_char();
+            }
+            // A colon with nothing in front of it is just part of the word
+            if value.is_empty() && self.chars.peek() == Some(&':') {
+                value.push(':');
+                self.next_char();
+            }
+            Ok(TokenText { value, quoted: false })
+        }
+    }
+}
+
+struct TokenText {
+    value: String,
+    quoted: bool,
+}
+impl TokenText {
+    fn is_empty(&self) -> bool { self.value.is_empty() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qualifiers(query: &str) -> Vec<Qualifier> {
+        match parse_filter(query) {
+            Ok(filter) => filter.qualifiers,
+            Err(e) => panic!("'{}' didn't parse: {}", query, e),
+        }
+    }
+
+    /// The (position, message) of the error from parsing the query
+    fn parse_error(query: &str) -> (usize, String) {
+        match parse_filter(query) {
+            Ok(_) => panic!("'{}' should not have parsed", query),
+            Err(e) => (e.position, e.message),
+        }
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    fn date_range(query: &str) -> (Option<NaiveDate>, Option<NaiveDate>) {
+        match qualifiers(query).as_slice() {
+            [Qualifier::Date(date_range)] => (date_range.start, date_range.end),
+            _ => panic!("'{}' isn't a single date qualifier", query),
+        }
+    }
+
+    #[test]
+    fn words_and_qualifiers_are_split() {
+        let filter = parse_filter("Fate tag:Nasuverse Zero").ok().unwrap();
+        assert_eq!(filter.words, vec!["Fate", "Zero"]);
+        assert_eq!(filter.fuzzy_query(), "Fate Zero");
+        assert!(matches!(filter.qualifiers.as_slice(),
+            [Qualifier::UniverseTag(name)] if name == "Nasuverse"));
+    }
+
+    #[test]
+    fn type_qualifiers() {
+        assert!(matches!(qualifiers("type:character").as_slice(),
+            [Qualifier::ItemType(ItemType::Character)]));
+        assert!(matches!(qualifiers("TYPE:Novel").as_slice(),
+            [Qualifier::SourceType(SourceType::Novel)]));
+
+        let filter = parse_filter("type:Person").ok().unwrap();
+        assert!(!filter.is_source_only());
+        assert!(filter.allows_item_type(ItemType::Person));
+        assert!(!filter.allows_item_type(ItemType::Source));
+        // A Source Type can only be true for a Source
+        let filter = parse_filter("type:Novel").ok().unwrap();
+        assert!(filter.is_source_only());
+        assert!(filter.allows_item_type(ItemType::Source));
+        assert!(!filter.allows_item_type(ItemType::Person));
+    }
+
+    #[test]
+    fn name_qualifiers() {
+        assert!(matches!(qualifiers("tag:Fate").as_slice(),
+            [Qualifier::UniverseTag(name)] if name == "Fate"));
+        assert!(matches!(qualifiers("person:Nasu").as_slice(),
+            [Qualifier::Person(name)] if name == "Nasu"));
+        assert!(matches!(qualifiers("company:ufotable").as_slice(),
+            [Qualifier::Company(name)] if name == "ufotable"));
+        // Only the first colon splits the key from the value
+        assert!(matches!(qualifiers("tag:Re:Zero").as_slice(),
+            [Qualifier::UniverseTag(name)] if name == "Re:Zero"));
+    }
+
+    #[test]
+    fn quoted_values() {
+        assert!(matches!(qualifiers("tag:\"Fate/stay night\"").as_slice(),
+            [Qualifier::UniverseTag(name)] if name == "Fate/stay night"));
+        let filter = parse_filter("\"Kara no Kyoukai\" person:\"Takeuchi Takashi\"").ok().unwrap();
+        assert_eq!(filter.words, vec!["Kara no Kyoukai"]);
+        assert!(matches!(filter.qualifiers.as_slice(),
+            [Qualifier::Person(name)] if name == "Takeuchi Takashi"));
+        // A quoted key is just a word
+        let filter = parse_filter("\"tag\":Fate").ok().unwrap();
+        assert!(filter.qualifiers.is_empty());
+    }
+
+    #[test]
+    fn status_qualifiers() {
+        let filter = parse_filter("status:unfinished status:Complete status:untracked")
+            .ok().unwrap();
+        let status_filters: Vec<StatusFilter> = filter.status_filters().collect();
+        assert!(matches!(status_filters.as_slice(), [
+            StatusFilter::Unfinished,
+            StatusFilter::Reached(CompletionStatus::Complete),
+            StatusFilter::Untracked,
+        ]));
+        assert!(!status_filters[0].is_exclusion());
+        assert!(status_filters[2].is_exclusion());
+
+        // Each one looks at its own rows in the temp table
+        let (predicate, _) = filter.source_predicate();
+        let status_table = "(SELECT source_id FROM TempSearchStatus WHERE qualifier=";
+        assert!(predicate.contains(&format!("Sources.id IN {}0)", status_table)));
+        assert!(predicate.contains(&format!("Sources.id IN {}1)", status_table)));
+        assert!(predicate.contains(&format!("Sources.id NOT IN {}2)", status_table)));
+        assert_eq!(parse_filter("tag:Fate").ok().unwrap().status_filters().count(), 0);
+    }
+
+    #[test]
+    fn dates_cover_the_whole_period() {
+        assert_eq!(date_range("year:2004"), (date(2004, 1, 1), date(2005, 1, 1)));
+        assert_eq!(date_range("date:2004-12"), (date(2004, 12, 1), date(2005, 1, 1)));
+        assert_eq!(date_range("date:2004-02"), (date(2004, 2, 1), date(2004, 3, 1)));
+        assert_eq!(date_range("date:2004-05-31"), (date(2004, 5, 31), date(2004, 6, 1)));
+    }
+
+    #[test]
+    fn date_ranges() {
+        // Both ends are inclusive
+        assert_eq!(date_range("year:2004..2010"), (date(2004, 1, 1), date(2011, 1, 1)));
+        assert_eq!(date_range("date:2004-04..2004-05-31"), (date(2004, 4, 1), date(2004, 6, 1)));
+        assert_eq!(date_range("year:2004.."), (date(2004, 1, 1), None));
+        assert_eq!(date_range("year:..2010"), (None, date(2011, 1, 1)));
+        assert_eq!(date_range("year:2004..2004"), (date(2004, 1, 1), date(2005, 1, 1)));
+
+        let (_, params) = parse_filter("year:2004..2010").ok().unwrap().source_predicate();
+        assert_eq!(params, vec!["2004-01-01", "2011-01-01"]);
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        let (predicate, params) = parse_filter("tag:100%_done").ok().unwrap().source_predicate();
+        assert!(predicate.contains("ESCAPE '\\'"));
+        assert_eq!(params, vec!["%100\\%\\_done%", "%100\\%\\_done%"]);
+        let (_, params) = parse_filter("company:a\\b").ok().unwrap().source_predicate();
+        assert_eq!(params, vec!["%a\\\\b%", "%a\\\\b%"]);
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(parse_error("Fate tag:"),
+            (9, "The 'tag' qualifier needs a value".to_string()));
+        assert_eq!(parse_error("Fate type:book"),
+            (10, "'book' is not an Item Type or a Source Type".to_string()));
+        assert_eq!(parse_error("status:done"),
+            (7, "'done' is not a completion status".to_string()));
+        assert_eq!(parse_error("Fate colour:red"),
+            (5, "Unknown qualifier 'colour'".to_string()));
+        assert_eq!(parse_error("Fate tag:\"Fate/Zero"),
+            (9, "This quote is never closed".to_string()));
+        assert_eq!(parse_error("year:.."),
+            (5, "A date range needs at least a start or an end".to_string()));
+        assert_eq!(parse_error("year:2010..2004"),
+            (11, "The date range '2010..2004' ends before it starts".to_string()));
+        assert_eq!(parse_error("year:2004..2010-13"),
+            (11, "'2010-13' is not a date like 2004, 2004-05 or 2004-05-31".to_string()));
+        // The positions are in characters, not bytes
+        assert_eq!(parse_error("狼と香辛料 date:2006-1-1-1").0, 11);
+    }
+
+    #[test]
+    fn bad_dates_are_errors() {
+        for value in &["20O4", "2004-", "-05", "2004-02-30", "2004-00", "2004-05-31-1", "+2004"] {
+            let (position, message) = parse_error(&format!("year:{}", value));
+            assert_eq!(position, 5);
+            assert!(message.starts_with(&format!("'{}' is not a date", value)), "{}", message);
+        }
+    }
+
+    #[test]
+    fn huge_years_are_errors() {
+        // This used to overflow when working out the end of the year
+        assert_eq!(parse_error("year:2147483647").0, 5);
+        assert_eq!(parse_error("year:2147483647-12").0, 5);
+        assert_eq!(parse_error("year:2147483648").0, 5);
+        assert_eq!(parse_error("year:99999999999999999999").0, 5);
+    }
+}
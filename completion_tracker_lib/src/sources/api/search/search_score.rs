@@ -17,8 +17,17 @@ impl SearchScore {
     }
     pub fn score(&self) -> f64 { self.score }
 
-    pub fn update(&mut self, matched_char_count: usize, haystack_index: usize) {
-        let haystack_start_index = haystack_index - matched_char_count;
+    /// Adds a chain of exactly matched characters, starting at the haystack index
+    pub fn update(&mut self, matched_char_count: usize, haystack_start_index: usize) {
+        self.update_with_edits(matched_char_count, haystack_start_index, 0);
+    }
+
+    /// Adds a chain of characters that needed some edits (typo fixes) to match
+    pub fn update_with_edits(&mut self, matched_char_count: usize, haystack_start_index: usize,
+    edits: usize) {
+        if matched_char_count == 0 || self.query_len == 0 || self.haystack_len == 0 {
+            return;
+        }
 
         // The basic score that we reduce based on penalties
         let base_score = matched_char_count as f64;
@@ -31,8 +40,11 @@ impl SearchScore {
         // Penalizes matches that don't use up the entire query
         let query_fragment_penalty = (matched_char_count as f64) / (self.query_len as f64);
 
+        // Penalizes every edit that was needed, based on how much of the query it takes up
+        let typo_penalty = 1_f64 - (edits as f64) / (self.query_len as f64);
+
         let chain_score = base_score * late_match_penalty *
-            haystack_fragment_penalty * query_fragment_penalty;
+            haystack_fragment_penalty * query_fragment_penalty * typo_penalty.max(0.0);
         self.score += chain_score;
     }
 }
@@ -0,0 +1,275 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+};
+
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use super::{ItemType};
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    sources::{
+        api,
+        source_types::{Lang, LangMap},
+    },
+};
+
+const DEFAULT_SUGGEST_LIMIT: usize = 10;
+const MAX_SUGGEST_LIMIT: usize = 50;
+
+/// A light-weight search for as-you-type suggestions.
+/// Only the start of the names (or any word in them) and aliases are matched.
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct SuggestQuery {
+    pub prefix: String,
+    pub item_type: ItemType,
+    /// The most suggestions that will be given back
+    #[serde(default = "default_suggest_limit")]
+    pub limit: usize,
+    /// The items that have already been picked, so they won't be suggested again
+    #[serde(default)]
+    pub selected_ids: Vec<u64>,
+}
+fn default_suggest_limit() -> usize { DEFAULT_SUGGEST_LIMIT }
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct Suggestion {
+    pub id: u64,
+    pub item_type: ItemType,
+    pub names: LangMap,
+    /// The alias that was matched, if none of the names were
+    pub matched_alias: Option<String>,
+}
+
+/// How well a suggestion matched, where lower is better
+#[derive(Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
+enum SuggestRank {
+    NameStart,
+    AliasStart,
+    NameWord,
+    AliasWord,
+}
+
+pub fn suggest(db: &ConnectionHolder, query: SuggestQuery) -> Result<Vec<Suggestion>, Error> {
+    let prefix = query.prefix.trim().to_lowercase();
+    if prefix.is_empty() {
+        return Ok(Vec::new());
+    }
+    let limit = query.limit.min(MAX_SUGGEST_LIMIT);
+    let selected_ids: HashSet<u64> = query.selected_ids.into_iter().collect();
+    let item_type = query.item_type;
+
+    let db = db.lock();
+
+    // The rank of each match is worked out below
+    let mut sql = format!("
+        SELECT {0}.id, Strings.english, Strings.japanese, NULL FROM {0}
+            INNER JOIN Strings ON Strings.id={0}.names
+            WHERE has_word_prefix(Strings.english, ?1) OR has_word_prefix(Strings.japanese, ?1)
+    ", item_type.table_name());
+    if let Some((alias_table, id_column)) = item_type.alias_table() {
+        sql.push_str(&format!("
+            UNION ALL
+            SELECT {0}.id, Strings.english, Strings.japanese, {1}.alias FROM {1}
+                INNER JOIN {0} ON {0}.id={1}.{2}
+                INNER JOIN Strings ON Strings.id={0}.names
+                WHERE has_word_prefix({1}.alias, ?1)
+        ", item_type.table_name(), alias_table, id_column));
+    }
+
+    let mut statement = db.prepare(&sql)?;
+    let rows = api::collect_query_map(statement.query_map(&[&prefix], |row| {
+        let id: i64 = row.get(0)?;
+        let english: Option<String> = row.get(1)?;
+        let japanese: Option<String> = row.get(2)?;
+        let alias: Option<String> = row.get(3)?;
+
+        let mut names = LangMap::new();
+        if let Some(english) = english { names.insert(Lang::English, english); }
+        if let Some(japanese) = japanese { names.insert(Lang::Japanese, japanese); }
+
+        Ok( (id as u64, names, alias) )
+    }))?;
+
+    // Keep the best rank for each item
+    let mut ranked: BTreeMap<u64, (SuggestRank, Suggestion)> = BTreeMap::new();
+    for (id, names, alias) in rows {
+        if selected_ids.contains(&id) {
+            continue;
+        }
+        let rank = if let Some(ref alias) = alias {
+            match find_prefix(alias, &prefix) {
+                Some(PrefixMatch::Start) => SuggestRank::AliasStart,
+                Some(PrefixMatch::Word) => SuggestRank::AliasWord,
+                None => continue,
+            }
+        } else {
+            let best_match = names.values()
+                .filter_map(|name| find_prefix(name, &prefix))
+                .min();
+            match best_match {
+                Some(PrefixMatch::Start) => SuggestRank::NameStart,
+                Some(PrefixMatch::Word) => SuggestRank::NameWord,
+                None => continue,
+            }
+        };
+
+        let is_better = ranked.get(&id).is_none_or(|(best_rank, _)| rank < *best_rank);
+        if is_better {
+            ranked.insert(id, (rank, Suggestion {
+                id,
+                item_type,
+                names,
+                matched_alias: alias,
+            }));
+        }
+    }
+
+    let mut suggestions: Vec<(SuggestRank, Suggestion)> = ranked.into_values().collect();
+    // The shortest names are the closest to the prefix
+    suggestions.sort_by_key(|(rank, suggestion)| {
+        let shortest_name = suggestion.names.values()
+            .map(|name| name.chars().count())
+            .min()
+            .unwrap_or(0);
+        (*rank, shortest_name, suggestion.id)
+    });
+
+    Ok(suggestions.into_iter()
+        .take(limit)
+        .map(|(_, suggestion)| suggestion)
+        .collect())
+}
+
+#[derive(Copy, Clone, Eq, Ord, PartialEq, PartialOrd)]
+enum PrefixMatch {
+    /// The value starts with the prefix
+    Start,
+    /// One of the words in the value starts with the prefix (eg. "Zero" in "Fate/Zero")
+    Word,
+}
+
+/// If the value or any word in it starts with the prefix, ignoring the case
+pub(crate) fn has_word_prefix(value: &str, lowercase_prefix: &str) -> bool {
+    find_prefix(value, lowercase_prefix).is_some()
+}
+
+fn find_prefix(value: &str, lowercase_prefix: &str) -> Option<PrefixMatch> {
+    let value = value.to_lowercase();
+    if value.starts_with(lowercase_prefix) {
+        return Some(PrefixMatch::Start);
+    }
+
+    let mut previous_char = None;
+    for (i, c) in value.char_indices() {
+        let is_word_start = previous_char.is_some_and(|previous: char| !previous.is_alphanumeric());
+        if is_word_start && value[i..].starts_with(lowercase_prefix) {
+            return Some(PrefixMatch::Word);
+        }
+        previous_char = Some(c);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::{params};
+
+    /// Makes a Person for each of the (English name, aliases).
+    /// They're put straight into the tables, so the IDs count up from 1.
+    fn make_db(people: &[(&str, &[&str])]) -> ConnectionHolder {
+        let db = crate::init_source_db().unwrap();
+        {
+            let connection = db.lock();
+            for (i, (name, aliases)) in people.iter().enumerate() {
+                let id = i as i64 + 1;
+                connection.execute("INSERT INTO Strings (id, english) VALUES (?, ?)",
+                    params![id * 2, name]).unwrap();
+                connection.execute("INSERT INTO Strings (id, english) VALUES (?, '-')",
+                    params![id * 2 + 1]).unwrap();
+                connection.execute("INSERT INTO People (id, names, descriptions) VALUES (?, ?, ?)",
+                    params![id, id * 2, id * 2 + 1]).unwrap();
+                for alias in aliases.iter() {
+                    connection.execute("
+                        INSERT INTO PersonAliases (person_id, lang, alias) VALUES (?, 'English', ?)
+                    ", params![id, alias]).unwrap();
+                }
+            }
+        }
+        db
+    }
+
+    fn suggested_ids(db: &ConnectionHolder, prefix: &str, limit: usize, selected_ids: Vec<u64>)
+    -> Vec<u64> {
+        suggest(db, SuggestQuery {
+            prefix: prefix.to_string(),
+            item_type: ItemType::Person,
+            limit,
+            selected_ids,
+        }).unwrap()
+            .into_iter()
+            .map(|suggestion| suggestion.id)
+            .collect()
+    }
+
+    #[test]
+    fn names_then_aliases_then_words() {
+        let db = make_db(&[
+            ("Sora Kara", &[]),
+            ("Beta", &["Mr. Kato"]),
+            ("Kara Sora", &[]),
+            ("Alpha", &["Katsu"]),
+        ]);
+        assert_eq!(suggested_ids(&db, "ka", 10, Vec::new()), vec![3, 4, 1, 2]);
+
+        let suggestions = suggest(&db, SuggestQuery {
+            prefix: "kats".to_string(),
+            item_type: ItemType::Person,
+            limit: 10,
+            selected_ids: Vec::new(),
+        }).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].names.get(&Lang::English).unwrap(), "Alpha");
+        assert_eq!(suggestions[0].matched_alias.as_deref(), Some("Katsu"));
+    }
+
+    #[test]
+    fn only_prefixes_match() {
+        let db = make_db(&[("Okara", &["Tokara"]), ("Nasu Kinoko", &[])]);
+        assert!(suggested_ids(&db, "kara", 10, Vec::new()).is_empty());
+        assert!(suggested_ids(&db, "asu", 10, Vec::new()).is_empty());
+        assert_eq!(suggested_ids(&db, "kino", 10, Vec::new()), vec![2]);
+        assert!(suggested_ids(&db, "  ", 10, Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn case_is_ignored_beyond_ascii() {
+        let db = make_db(&[("Élodie Ängström", &["ΣΟΦΙΑ"]), ("狼と香辛料", &[])]);
+        assert_eq!(suggested_ids(&db, "éLO", 10, Vec::new()), vec![1]);
+        assert_eq!(suggested_ids(&db, "äng", 10, Vec::new()), vec![1]);
+        assert_eq!(suggested_ids(&db, "σοφ", 10, Vec::new()), vec![1]);
+        assert_eq!(suggested_ids(&db, "狼", 10, Vec::new()), vec![2]);
+    }
+
+    #[test]
+    fn selected_items_are_left_out() {
+        let db = make_db(&[("Kara", &[]), ("Karasu", &[]), ("Karin", &["Kara"])]);
+        assert_eq!(suggested_ids(&db, "kar", 10, Vec::new()), vec![1, 3, 2]);
+        assert_eq!(suggested_ids(&db, "kar", 10, vec![1, 3]), vec![2]);
+    }
+
+    #[test]
+    fn the_limit_is_capped() {
+        let names: Vec<String> = (0..(MAX_SUGGEST_LIMIT + 5))
+            .map(|i| format!("Person {}", i))
+            .collect();
+        let people: Vec<(&str, &[&str])> = names.iter()
+            .map(|name| (name.as_str(), &[][..]))
+            .collect();
+        let db = make_db(&people);
+        assert_eq!(suggested_ids(&db, "person", 3, Vec::new()), vec![1, 2, 3]);
+        assert_eq!(suggested_ids(&db, "person", 1000, Vec::new()).len(), MAX_SUGGEST_LIMIT);
+    }
+}
@@ -0,0 +1,27 @@
+mod create;
+mod find;
+pub use self::create::{create};
+pub use self::find::{exists, find_by_external_id, find_by_link};
+
+use chrono::{NaiveDate};
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::sources::source_types::{CompanyRole, LangMap, PersonRole, Relation, SourceType};
+
+/// Everything that goes into a new Source.
+/// It can also be made by an importer, so that it can be looked over before it's created.
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct SourceCreator {
+    pub names: LangMap,
+    pub descriptions: LangMap,
+    pub source_type: SourceType,
+    pub related_links: Vec<(String, LangMap)>,
+    pub dates: Vec<(NaiveDate, LangMap)>,
+    pub universe_tags: Vec<u64>,
+    pub related_sources: Vec<(u64, Relation)>,
+    /// A Person can be in here more than once, with a different role each time
+    pub people: Vec<(u64, PersonRole)>,
+    pub characters: Vec<u64>,
+    pub companies: Vec<(u64, Option<CompanyRole>)>,
+}
@@ -0,0 +1,84 @@
+use rusqlite::{Connection, params};
+
+use super::{SourceCreator};
+use crate::db_link::{ConnectionHolder};
+use crate::sources::api::{self, DBSourceRelation};
+use crate::sources::source_types::{LangMap, PersonRole};
+use crate::error::{Error};
+
+pub fn create(db: &ConnectionHolder, creator: SourceCreator) -> Result<u64, Error> {
+    let mut db = db.lock();
+
+    let related_links = api::transform_related_links(creator.related_links)?;
+
+    let transaction = db.transaction()?;
+
+    let names_id = insert_lang_map(&transaction, &creator.names)?;
+    let descriptions_id = insert_lang_map(&transaction, &creator.descriptions)?;
+    transaction.execute("INSERT INTO Sources (names, descriptions, source_type) VALUES (?, ?, ?)",
+        params![names_id, descriptions_id, creator.source_type])?;
+    let id = transaction.last_insert_rowid();
+
+    for related_link in related_links {
+        let descriptions_id = insert_lang_map(&transaction, related_link.descriptions())?;
+        transaction.execute("
+            INSERT INTO SourceRelatedLinks (
+                source_id, url, link_type, external_id, descriptions
+            ) VALUES (?, ?, ?, ?, ?)
+        ", params![id, related_link.url().as_str(), related_link.link_type().as_str(),
+            related_link.external_id(), descriptions_id])?;
+    }
+    for (date, descriptions) in creator.dates.iter() {
+        let descriptions_id = insert_lang_map(&transaction, descriptions)?;
+        transaction.execute("
+            INSERT INTO SourceDates (source_id, date, descriptions) VALUES (?, ?, ?)
+        ", params![id, date, descriptions_id])?;
+    }
+    for universe_tag_id in creator.universe_tags {
+        transaction.execute("
+            INSERT INTO SourceUniverseTags (source_id, universe_tag_id) VALUES (?, ?)
+        ", params![id, universe_tag_id as i64])?;
+    }
+    for (related_id, relation) in creator.related_sources {
+        // Make sure that the IDs are in the right spots for the relation
+        let (first_spot, db_relation) = DBSourceRelation::normalize(relation);
+        let (id1, id2) = if first_spot {
+            (id, related_id as i64)
+        } else {
+            (related_id as i64, id)
+        };
+        transaction.execute("
+            INSERT INTO SourceRelations (source_id1, source_id2, relation) VALUES (?, ?, ?)
+        ", params![id1, id2, db_relation])?;
+    }
+    for (person_id, role) in creator.people {
+        transaction.execute("
+            INSERT INTO SourcePeople (source_id, person_id, role) VALUES (?, ?, ?)
+        ", params![id, person_id as i64, role.db_name()])?;
+        if let PersonRole::VoiceActor(character_id, lang) = role {
+            let source_person_id = transaction.last_insert_rowid();
+            transaction.execute("
+                INSERT INTO SourcePersonVoiceActors (source_person_id, character_id, lang)
+                    VALUES (?, ?, ?)
+            ", params![source_person_id, character_id as i64, lang])?;
+        }
+    }
+    for character_id in creator.characters {
+        transaction.execute("
+            INSERT INTO SourceCharacters (source_id, character_id) VALUES (?, ?)
+        ", params![id, character_id as i64])?;
+    }
+    for (company_id, company_role) in creator.companies {
+        transaction.execute("
+            INSERT INTO SourceCompanies (source_id, company_id, company_role) VALUES (?, ?, ?)
+        ", params![id, company_id as i64, company_role])?;
+    }
+
+    transaction.commit()?;
+    Ok(id as u64)
+}
+
+fn insert_lang_map(db: &Connection, map: &LangMap) -> Result<i64, Error> {
+    api::insert_new_lang_map(map, db)?;
+    Ok(db.last_insert_rowid())
+}
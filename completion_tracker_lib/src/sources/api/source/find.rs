@@ -0,0 +1,49 @@
+use rusqlite::{Connection};
+
+use crate::db_link::{ConnectionHolder};
+use crate::sources::{
+    api,
+    source_types::{LangMap, RelatedLink},
+};
+use crate::error::{Error};
+
+/// If there's a Source with the ID
+pub fn exists(db: &ConnectionHolder, id: u64) -> Result<bool, Error> {
+    let db = db.lock();
+    let count: i64 = db.query_row("SELECT COUNT(*) FROM Sources WHERE id = ?", [id as i64],
+        |row| row.get(0))?;
+    Ok(count > 0)
+}
+
+/// The Sources with a link to the item on the site, oldest first
+pub fn find_by_external_id(db: &ConnectionHolder, link_type: &str, external_id: &str)
+-> Result<Vec<u64>, Error> {
+    let db = db.lock();
+    query_ids(&db, "
+        SELECT DISTINCT source_id FROM SourceRelatedLinks
+            WHERE link_type = ? AND external_id = ?
+            ORDER BY source_id
+    ", &[link_type, external_id])
+}
+
+/// The Sources with a link to the same item as the URL.
+/// The external ID is used when the site has one, so any URL for the item will find it.
+pub fn find_by_link(db: &ConnectionHolder, url: &str) -> Result<Vec<u64>, Error> {
+    let link = RelatedLink::new(url, LangMap::new())?;
+    if let Some(external_id) = link.external_id() {
+        return find_by_external_id(db, link.link_type().as_str(), external_id);
+    }
+
+    let db = db.lock();
+    query_ids(&db, "
+        SELECT DISTINCT source_id FROM SourceRelatedLinks WHERE url = ? ORDER BY source_id
+    ", &[link.url().as_str()])
+}
+
+fn query_ids(db: &Connection, sql: &str, params: &[&str]) -> Result<Vec<u64>, Error> {
+    let mut statement = db.prepare(sql)?;
+    api::collect_query_map(statement.query_map(params, |row| {
+        let id: i64 = row.get(0)?;
+        Ok(id as u64)
+    })).map_err(Error::from)
+}
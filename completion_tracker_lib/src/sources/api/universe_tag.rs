@@ -3,11 +3,12 @@ pub use self::create::{create};
 mod read;
 pub use self::read::{read_root_level_ids, read_list};
 
+use schemars::{JsonSchema};
 use serde::{Deserialize, Serialize};
 
 use crate::sources::source_types::{LangMap, RelatedLink, Relation};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct UniverseTagCreator {
     pub names: LangMap,
     pub descriptions: LangMap,
@@ -17,7 +18,7 @@ pub struct UniverseTagCreator {
     pub related_universe_tags: Vec<(u64, Relation)>,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct UniverseTagReadResult {
     pub id: u64,
     pub names: LangMap,
@@ -29,7 +30,7 @@ pub struct UniverseTagReadResult {
     pub sources: Vec<u64>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct UniverseTagReader {
     pub ids: Vec<u64>,
 }
@@ -4,33 +4,33 @@ use super::{UniverseTagReadResult, UniverseTagReader};
 use crate::db_link::{ConnectionHolder};
 use crate::sources::api::{self, DBSourceRelation};
 use crate::sources::source_types::{RelatedLink};
+use crate::error::{Error};
 
 /// A root level UniverseTag is one that isn't the child of any other UniverseTag
-pub fn read_root_level_ids(db: &ConnectionHolder) -> Result<Vec<u64>, String> {
+pub fn read_root_level_ids(db: &ConnectionHolder) -> Result<Vec<u64>, Error> {
     let db = db.lock();
 
     let mut get_page_statement = db.prepare(
         "SELECT id FROM UniverseTags
             WHERE id NOT IN (SELECT child FROM UniverseTagChildren)"
-    ).map_err(|e| e.to_string())?;
+    )?;
 
     let root_ids = api::collect_query_map(get_page_statement.query_map(rusqlite::NO_PARAMS, |row| {
         let id: i64 = row.get(0)?;
         Ok(id as u64)
-    })).map_err(|e| e.to_string())?;
+    }))?;
     Ok(root_ids)
 }
 
 // TODO Instead of just IDs, we could pass an options object. This would have the IDs and other options to specify what gets returned (like not getting sources or other things)
 pub fn read_list(db: &ConnectionHolder, reader: UniverseTagReader)
--> Result<Vec<UniverseTagReadResult>, String> {
+-> Result<Vec<UniverseTagReadResult>, Error> {
     let mut db = db.lock();
 
     let id_count = reader.ids.len();
 
     {
-        let transaction = db.transaction()
-            .map_err(|e| e.to_string())?;
+        let transaction = db.transaction()?;
 
         {
             // Always drop the index first since it will slow down inserts
@@ -40,50 +40,48 @@ pub fn read_list(db: &ConnectionHolder, reader: UniverseTagReader)
                     id INTEGER NOT NULL
                 );
                 DELETE FROM TempUniverseTagRead;
-            ").map_err(|e| e.to_string())?;
+            ")?;
             let mut insert_temp_id_statement = transaction.prepare("
                 INSERT INTO TempUniverseTagRead VALUES (?)
-            ").map_err(|e| e.to_string())?;
+            ")?;
             for id in reader.ids {
-                insert_temp_id_statement.execute(&[id as i64])
-                    .map_err(|e| e.to_string())?;
+                insert_temp_id_statement.execute(&[id as i64])?;
             }
             // We can't be in a transaction when querying stuff
             transaction.execute_batch("
                 CREATE INDEX TempUniverseTagIDIndex ON TempUniverseTagRead(id);
-            ").map_err(|e| e.to_string())?;
+            ")?;
         }
 
-        transaction.commit()
-            .map_err(|e| e.to_string())?;
+        transaction.commit()?;
     }
 
     let mut get_strings_statement = api::prepare_strings_get(&db)?;
     let mut get_children_statement = db.prepare(
         "SELECT child FROM UniverseTagChildren
             WHERE parent=?"
-    ).map_err(|e| e.to_string())?;
+    )?;
     let mut get_parents_statement = db.prepare("
         SELECT parent FROM UniverseTagChildren
             WHERE child=?
-    ").map_err(|e| e.to_string())?;
+    ")?;
     let mut get_relations_statement = db.prepare(
         "SELECT universe_tag_id1, universe_tag_id2, relation FROM UniverseTagRelations
             WHERE universe_tag_id1=?1 OR universe_tag_id2=?1"
-    ).map_err(|e| e.to_string())?;
+    )?;
     let mut get_links_statement = db.prepare(
         "SELECT url, descriptions FROM UniverseTagRelatedLinks
             WHERE universe_tag_id=?"
-    ).map_err(|e| e.to_string())?;
+    )?;
     let mut get_sources_statement = db.prepare("
         SELECT source_id FROM SourceUniverseTags
             WHERE universe_tag_id=?
-    ").map_err(|e| e.to_string())?;
+    ")?;
 
     let mut get_universe_tags_statement = db.prepare("
         SELECT id, names, descriptions FROM UniverseTags
             WHERE id IN (SELECT * FROM TempUniverseTagRead)
-    ").map_err(|e| e.to_string())?;
+    ")?;
     let mapped_tags = get_universe_tags_statement.query_map(rusqlite::NO_PARAMS, |row| {
         let id: i64 = row.get(0)?;
         let names_id: i64 = row.get(1)?;
@@ -133,7 +131,7 @@ pub fn read_list(db: &ConnectionHolder, reader: UniverseTagReader)
 
             Ok(
                 RelatedLink::new(&url, descriptions).map_err(|e| {
-                    SqlError::InvalidParameterName(e)
+                    SqlError::InvalidParameterName(e.to_string())
                 })?
             )
         }))?;
@@ -155,8 +153,7 @@ pub fn read_list(db: &ConnectionHolder, reader: UniverseTagReader)
         })
     });
 
-    let universe_tags = api::collect_query_map(mapped_tags)
-        .map_err(|e| e.to_string())?;
+    let universe_tags = api::collect_query_map(mapped_tags)?;
 
     if universe_tags.len() == id_count {
         Ok(universe_tags)
@@ -166,6 +163,6 @@ pub fn read_list(db: &ConnectionHolder, reader: UniverseTagReader)
             id_list.push_str(&format!("{},", universe_tag.id));
         }
         id_list.push_str("]");
-        Err(format!("Failed to find enough Universe Tags. Found {}", &id_list))
+        Err(Error::NotFound(format!("Failed to find enough Universe Tags. Found {}", &id_list)))
     }
 }
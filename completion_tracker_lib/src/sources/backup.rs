@@ -0,0 +1,655 @@
+use std::{
+    collections::{BTreeMap},
+    path::{Path},
+};
+
+use chrono::{NaiveDate};
+use rusqlite::{Connection, Statement, params};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error, ResultExt},
+    simple_enum::{SimpleEnum},
+    sources::{
+        api::{self, DBSourceRelation},
+        source_types::{
+            CompanyRole, Country, Lang, LangMap, LangMapList, PersonRole, Relation, SourceType,
+        },
+    },
+    utils,
+};
+
+/// This goes up every time the shape of the backup changes
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Everything in the sources DB.
+/// The IDs are the same as the ones in the DB, so they stay the same between exports.
+#[derive(Deserialize, Serialize)]
+pub struct SourcesBackup {
+    pub version: u32,
+    pub universe_tags: Vec<UniverseTagBackup>,
+    pub people: Vec<PersonBackup>,
+    pub companies: Vec<CompanyBackup>,
+    pub characters: Vec<CharacterBackup>,
+    pub sources: Vec<SourceBackup>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct UniverseTagBackup {
+    pub id: u64,
+    pub names: LangMap,
+    pub descriptions: LangMap,
+    pub related_links: Vec<(String, LangMap)>,
+    pub children: Vec<u64>,
+    /// Only the relations where this Universe Tag is in the 1st spot.
+    /// This way, every relation is only in the backup once.
+    pub related_universe_tags: Vec<(u64, Relation)>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PersonBackup {
+    pub id: u64,
+    pub names: LangMap,
+    pub descriptions: LangMap,
+    pub aliases: LangMapList,
+    pub birth_country: Option<Country>,
+    pub birth_date: Option<NaiveDate>,
+    pub death_date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CompanyBackup {
+    pub id: u64,
+    pub names: LangMap,
+    pub descriptions: LangMap,
+    pub country: Option<Country>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CharacterBackup {
+    pub id: u64,
+    pub names: LangMap,
+    pub descriptions: LangMap,
+    pub aliases: LangMapList,
+    /// Only the relations where this Character is in the 1st spot
+    pub related_characters: Vec<(u64, LangMap)>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SourceBackup {
+    pub id: u64,
+    pub names: LangMap,
+    pub descriptions: LangMap,
+    pub source_type: SourceType,
+    pub related_links: Vec<(String, LangMap)>,
+    pub dates: Vec<(NaiveDate, LangMap)>,
+    pub universe_tags: Vec<u64>,
+    /// Only the relations where this Source is in the 1st spot
+    pub related_sources: Vec<(u64, Relation)>,
+    /// Each Person ID with one of their roles. A Person can be in here more than once.
+    pub people: Vec<(u64, PersonRole)>,
+    pub characters: Vec<u64>,
+    pub companies: Vec<(u64, Option<CompanyRole>)>,
+}
+
+#[derive(Copy, Clone, Deserialize, Serialize)]
+pub enum ImportMode {
+    /// Keep all of the IDs from the backup. The DB must be empty.
+    Restore,
+    /// Give everything a new ID, so that the backup can be added to a DB that already has data
+    Merge,
+}
+
+/// The ID that each item from the backup ended up with in the DB
+#[derive(Default, Serialize)]
+pub struct BackupIDMaps {
+    pub universe_tags: BTreeMap<u64, u64>,
+    pub people: BTreeMap<u64, u64>,
+    pub companies: BTreeMap<u64, u64>,
+    pub characters: BTreeMap<u64, u64>,
+    pub sources: BTreeMap<u64, u64>,
+}
+
+pub fn export_to_file(db: &ConnectionHolder, file: impl AsRef<Path>) -> Result<(), Error> {
+    utils::write_json_file(&export(db)?, file)
+}
+
+pub fn import_from_file(db: &ConnectionHolder, file: impl AsRef<Path>, mode: ImportMode)
+-> Result<BackupIDMaps, Error> {
+    import(db, utils::read_json_file(file)?, mode)
+}
+
+pub fn export(db: &ConnectionHolder) -> Result<SourcesBackup, Error> {
+    let db = db.lock();
+
+    Ok(SourcesBackup {
+        version: BACKUP_VERSION,
+        universe_tags: export_universe_tags(&db)
+            .context("Failed to export the Universe Tags")?,
+        people: export_people(&db)
+            .context("Failed to export the People")?,
+        companies: export_companies(&db)
+            .context("Failed to export the Companies")?,
+        characters: export_characters(&db)
+            .context("Failed to export the Characters")?,
+        sources: export_sources(&db)
+            .context("Failed to export the Sources")?,
+    })
+}
+
+fn read_lang_map(get_strings_statement: &mut Statement, strings_id: i64)
+-> rusqlite::Result<LangMap> {
+    get_strings_statement.query_row([strings_id], |row| {
+        Ok(api::make_lang_strings_from_row(row))
+    })
+}
+
+fn read_id_list(statement: &mut Statement, id: i64) -> rusqlite::Result<Vec<u64>> {
+    api::collect_query_map(statement.query_map([id], |row| {
+        let other_id: i64 = row.get(0)?;
+        Ok(other_id as u64)
+    }))
+}
+
+/// Reads the (url, descriptions) with the statement
+fn read_links(statement: &mut Statement, get_strings_statement: &mut Statement, id: i64)
+-> rusqlite::Result<Vec<(String, LangMap)>> {
+    let raw_links = api::collect_query_map(statement.query_map([id], |row| {
+        let url: String = row.get(0)?;
+        let descriptions_id: i64 = row.get(1)?;
+        Ok( (url, descriptions_id) )
+    }))?;
+
+    let mut links = Vec::with_capacity(raw_links.len());
+    for (url, descriptions_id) in raw_links {
+        links.push( (url, read_lang_map(get_strings_statement, descriptions_id)?) );
+    }
+    Ok(links)
+}
+
+/// Reads the aliases with the statement, which must SELECT (lang, alias)
+fn read_aliases(statement: &mut Statement, id: i64) -> rusqlite::Result<LangMapList> {
+    let raw_aliases = api::collect_query_map(statement.query_map([id], |row| {
+        let lang: Lang = row.get(0)?;
+        let alias: String = row.get(1)?;
+        Ok( (lang, alias) )
+    }))?;
+
+    let mut aliases = LangMapList::new();
+    for (lang, alias) in raw_aliases {
+        aliases.entry(lang).or_default().push(alias);
+    }
+    Ok(aliases)
+}
+
+/// Reads the (id, names, descriptions) of every item in the table
+fn read_items(db: &Connection, get_strings_statement: &mut Statement, table_name: &str)
+-> rusqlite::Result<Vec<(i64, LangMap, LangMap)>> {
+    let mut statement = db.prepare(&format!(
+        "SELECT id, names, descriptions FROM {} ORDER BY id", table_name
+    ))?;
+    let raw_items = api::collect_query_map(statement.query_map(rusqlite::NO_PARAMS, |row| {
+        let id: i64 = row.get(0)?;
+        let names_id: i64 = row.get(1)?;
+        let descriptions_id: i64 = row.get(2)?;
+        Ok( (id, names_id, descriptions_id) )
+    }))?;
+
+    let mut items = Vec::with_capacity(raw_items.len());
+    for (id, names_id, descriptions_id) in raw_items {
+        items.push((
+            id,
+            read_lang_map(get_strings_statement, names_id)?,
+            read_lang_map(get_strings_statement, descriptions_id)?,
+        ));
+    }
+    Ok(items)
+}
+
+fn export_universe_tags(db: &Connection) -> rusqlite::Result<Vec<UniverseTagBackup>> {
+    let mut get_strings_statement = db.prepare("SELECT english, japanese FROM Strings WHERE id=?")?;
+    let mut get_links_statement = db.prepare("
+        SELECT url, descriptions FROM UniverseTagRelatedLinks WHERE universe_tag_id=? ORDER BY id
+    ")?;
+    let mut get_children_statement = db.prepare("
+        SELECT child FROM UniverseTagChildren WHERE parent=? ORDER BY child
+    ")?;
+    let mut get_relations_statement = db.prepare("
+        SELECT universe_tag_id2, relation FROM UniverseTagRelations
+            WHERE universe_tag_id1=? ORDER BY universe_tag_id2
+    ")?;
+
+    let mut universe_tags = Vec::new();
+    for (id, names, descriptions) in read_items(db, &mut get_strings_statement, "UniverseTags")? {
+        let related_universe_tags = api::collect_query_map(
+            get_relations_statement.query_map([id], |row| {
+                let related_id: i64 = row.get(0)?;
+                let relation: DBSourceRelation = row.get(1)?;
+                Ok( (related_id as u64, relation.denormalize(true)) )
+            })
+        )?;
+
+        universe_tags.push(UniverseTagBackup {
+            id: id as u64,
+            names,
+            descriptions,
+            related_links: read_links(&mut get_links_statement, &mut get_strings_statement, id)?,
+            children: read_id_list(&mut get_children_statement, id)?,
+            related_universe_tags,
+        });
+    }
+    Ok(universe_tags)
+}
+
+fn export_people(db: &Connection) -> rusqlite::Result<Vec<PersonBackup>> {
+    let mut get_strings_statement = db.prepare("SELECT english, japanese FROM Strings WHERE id=?")?;
+    let mut get_aliases_statement = db.prepare("
+        SELECT lang, alias FROM PersonAliases WHERE person_id=? ORDER BY id
+    ")?;
+    let mut get_details_statement = db.prepare("
+        SELECT birth_country, birth_date, death_date FROM People WHERE id=?
+    ")?;
+
+    let mut people = Vec::new();
+    for (id, names, descriptions) in read_items(db, &mut get_strings_statement, "People")? {
+        let (birth_country, birth_date, death_date) = get_details_statement.query_row([id], |row| {
+            Ok( (row.get(0)?, row.get(1)?, row.get(2)?) )
+        })?;
+
+        people.push(PersonBackup {
+            id: id as u64,
+            names,
+            descriptions,
+            aliases: read_aliases(&mut get_aliases_statement, id)?,
+            birth_country,
+            birth_date,
+            death_date,
+        });
+    }
+    Ok(people)
+}
+
+fn export_companies(db: &Connection) -> rusqlite::Result<Vec<CompanyBackup>> {
+    let mut get_strings_statement = db.prepare("SELECT english, japanese FROM Strings WHERE id=?")?;
+    let mut get_country_statement = db.prepare("SELECT country FROM Companies WHERE id=?")?;
+
+    let mut companies = Vec::new();
+    for (id, names, descriptions) in read_items(db, &mut get_strings_statement, "Companies")? {
+        companies.push(CompanyBackup {
+            id: id as u64,
+            names,
+            descriptions,
+            country: get_country_statement.query_row([id], |row| row.get(0))?,
+        });
+    }
+    Ok(companies)
+}
+
+fn export_characters(db: &Connection) -> rusqlite::Result<Vec<CharacterBackup>> {
+    let mut get_strings_statement = db.prepare("SELECT english, japanese FROM Strings WHERE id=?")?;
+    let mut get_aliases_statement = db.prepare("
+        SELECT lang, alias FROM CharacterAliases WHERE character_id=? ORDER BY id
+    ")?;
+    let mut get_relations_statement = db.prepare("
+        SELECT character_id2, descriptions FROM CharacterRelations
+            WHERE character_id1=? ORDER BY character_id2
+    ")?;
+
+    let mut characters = Vec::new();
+    for (id, names, descriptions) in read_items(db, &mut get_strings_statement, "Characters")? {
+        let raw_relations = api::collect_query_map(get_relations_statement.query_map([id], |row| {
+            let related_id: i64 = row.get(0)?;
+            let descriptions_id: i64 = row.get(1)?;
+            Ok( (related_id as u64, descriptions_id) )
+        }))?;
+        let mut related_characters = Vec::with_capacity(raw_relations.len());
+        for (related_id, descriptions_id) in raw_relations {
+            related_characters.push(
+                (related_id, read_lang_map(&mut get_strings_statement, descriptions_id)?)
+            );
+        }
+
+        characters.push(CharacterBackup {
+            id: id as u64,
+            names,
+            descriptions,
+            aliases: read_aliases(&mut get_aliases_statement, id)?,
+            related_characters,
+        });
+    }
+    Ok(characters)
+}
+
+fn export_sources(db: &Connection) -> rusqlite::Result<Vec<SourceBackup>> {
+    let mut get_strings_statement = db.prepare("SELECT english, japanese FROM Strings WHERE id=?")?;
+    let mut get_source_type_statement = db.prepare("SELECT source_type FROM Sources WHERE id=?")?;
+    let mut get_links_statement = db.prepare("
+        SELECT url, descriptions FROM SourceRelatedLinks WHERE source_id=? ORDER BY id
+    ")?;
+    let mut get_dates_statement = db.prepare("
+        SELECT date, descriptions FROM SourceDates WHERE source_id=? ORDER BY date
+    ")?;
+    let mut get_universe_tags_statement = db.prepare("
+        SELECT universe_tag_id FROM SourceUniverseTags WHERE source_id=? ORDER BY universe_tag_id
+    ")?;
+    let mut get_relations_statement = db.prepare("
+        SELECT source_id2, relation FROM SourceRelations WHERE source_id1=? ORDER BY source_id2
+    ")?;
+    let mut get_people_statement = db.prepare("
+        SELECT id, person_id, role FROM SourcePeople WHERE source_id=? ORDER BY id
+    ")?;
+    let mut get_voice_actors_statement = db.prepare("
+        SELECT character_id, lang FROM SourcePersonVoiceActors
+            WHERE source_person_id=? ORDER BY id
+    ")?;
+    let mut get_characters_statement = db.prepare("
+        SELECT character_id FROM SourceCharacters WHERE source_id=? ORDER BY character_id
+    ")?;
+    let mut get_companies_statement = db.prepare("
+        SELECT company_id, company_role FROM SourceCompanies WHERE source_id=? ORDER BY id
+    ")?;
+
+    let mut sources = Vec::new();
+    for (id, names, descriptions) in read_items(db, &mut get_strings_statement, "Sources")? {
+        let raw_dates = api::collect_query_map(get_dates_statement.query_map([id], |row| {
+            let date: NaiveDate = row.get(0)?;
+            let descriptions_id: i64 = row.get(1)?;
+            Ok( (date, descriptions_id) )
+        }))?;
+        let mut dates = Vec::with_capacity(raw_dates.len());
+        for (date, descriptions_id) in raw_dates {
+            dates.push( (date, read_lang_map(&mut get_strings_statement, descriptions_id)?) );
+        }
+
+        let related_sources = api::collect_query_map(get_relations_statement.query_map([id], |row| {
+            let related_id: i64 = row.get(0)?;
+            let relation: DBSourceRelation = row.get(1)?;
+            Ok( (related_id as u64, relation.denormalize(true)) )
+        }))?;
+
+        let raw_people = api::collect_query_map(get_people_statement.query_map([id], |row| {
+            let source_person_id: i64 = row.get(0)?;
+            let person_id: i64 = row.get(1)?;
+            let role: String = row.get(2)?;
+            Ok( (source_person_id, person_id as u64, role) )
+        }))?;
+        let mut people = Vec::with_capacity(raw_people.len());
+        for (source_person_id, person_id, role_name) in raw_people {
+            let voice_actors = api::collect_query_map(
+                get_voice_actors_statement.query_map([source_person_id], |row| {
+                    let character_id: i64 = row.get(0)?;
+                    let lang: Lang = row.get(1)?;
+                    Ok( (character_id as u64, lang) )
+                })
+            )?;
+
+            if voice_actors.is_empty() {
+                let role = PersonRole::from_db_name(&role_name, None)
+                    .ok_or_else(|| rusqlite::Error::InvalidParameterName(
+                        format!("Unknown role {} for Person {}", role_name, person_id)
+                    ))?;
+                people.push( (person_id, role) );
+            } else {
+                for voice_actor in voice_actors {
+                    let role = PersonRole::from_db_name(&role_name, Some(voice_actor))
+                        .ok_or_else(|| rusqlite::Error::InvalidParameterName(
+                            format!("Unknown role {} for Person {}", role_name, person_id)
+                        ))?;
+                    people.push( (person_id, role) );
+                }
+            }
+        }
+
+        let companies = api::collect_query_map(get_companies_statement.query_map([id], |row| {
+            let company_id: i64 = row.get(0)?;
+            let company_role: Option<CompanyRole> = row.get(1)?;
+            Ok( (company_id as u64, company_role) )
+        }))?;
+
+        sources.push(SourceBackup {
+            id: id as u64,
+            names,
+            descriptions,
+            source_type: get_source_type_statement.query_row([id], |row| row.get(0))?,
+            related_links: read_links(&mut get_links_statement, &mut get_strings_statement, id)?,
+            dates,
+            universe_tags: read_id_list(&mut get_universe_tags_statement, id)?,
+            related_sources,
+            people,
+            characters: read_id_list(&mut get_characters_statement, id)?,
+            companies,
+        });
+    }
+    Ok(sources)
+}
+
+pub fn import(db: &ConnectionHolder, backup: SourcesBackup, mode: ImportMode)
+-> Result<BackupIDMaps, Error> {
+    if backup.version != BACKUP_VERSION {
+        return Err(Error::Validation(format!(
+            "Can't import a backup with version {} (expected {})", backup.version, BACKUP_VERSION
+        )));
+    }
+
+    let mut db = db.lock();
+
+    let transaction = db.transaction()?;
+
+    if let ImportMode::Restore = mode {
+        let item_count: i64 = transaction.query_row("
+            SELECT (SELECT COUNT(*) FROM UniverseTags) + (SELECT COUNT(*) FROM People) +
+                (SELECT COUNT(*) FROM Companies) + (SELECT COUNT(*) FROM Characters) +
+                (SELECT COUNT(*) FROM Sources)
+        ", rusqlite::NO_PARAMS, |row| row.get(0))?;
+        if item_count != 0 {
+            return Err(Error::Conflict("A backup can only be restored into an empty DB".to_string()));
+        }
+    }
+
+    let mut id_maps = BackupIDMaps::default();
+    // The items all need to go in before anything that can refer to them
+    for universe_tag in backup.universe_tags.iter() {
+        let new_id = insert_item(&transaction, mode, "UniverseTags", universe_tag.id,
+            &universe_tag.names, &universe_tag.descriptions)?;
+        id_maps.universe_tags.insert(universe_tag.id, new_id);
+    }
+    for person in backup.people.iter() {
+        let new_id = insert_item(&transaction, mode, "People", person.id,
+            &person.names, &person.descriptions)?;
+        transaction.execute("
+            UPDATE People SET birth_country=?, birth_date=?, death_date=? WHERE id=?
+        ", params![person.birth_country, person.birth_date, person.death_date, new_id as i64])?;
+        insert_aliases(&transaction, "PersonAliases", "person_id", new_id, &person.aliases)?;
+        id_maps.people.insert(person.id, new_id);
+    }
+    for company in backup.companies.iter() {
+        let new_id = insert_item(&transaction, mode, "Companies", company.id,
+            &company.names, &company.descriptions)?;
+        transaction.execute("UPDATE Companies SET country=? WHERE id=?",
+            params![company.country, new_id as i64])?;
+        id_maps.companies.insert(company.id, new_id);
+    }
+    for character in backup.characters.iter() {
+        let new_id = insert_item(&transaction, mode, "Characters", character.id,
+            &character.names, &character.descriptions)?;
+        insert_aliases(&transaction, "CharacterAliases", "character_id", new_id,
+            &character.aliases)?;
+        id_maps.characters.insert(character.id, new_id);
+    }
+    for source in backup.sources.iter() {
+        let new_id = insert_item(&transaction, mode, "Sources", source.id,
+            &source.names, &source.descriptions)
+            .and_then(|new_id| {
+                transaction.execute("UPDATE Sources SET source_type=? WHERE id=?",
+                    params![source.source_type, new_id as i64])
+                    .map(|_| new_id)
+                    .map_err(Error::from)
+            })?;
+        id_maps.sources.insert(source.id, new_id);
+    }
+
+    // Now everything that goes between the items
+    for universe_tag in backup.universe_tags.iter() {
+        let id = map_id(&id_maps.universe_tags, universe_tag.id, "Universe Tag")?;
+        // The links go through the same checks as when they're created
+        for related_link in api::transform_related_links(universe_tag.related_links.clone())? {
+            let descriptions_id = insert_lang_map(&transaction, related_link.descriptions())?;
+            transaction.execute("
+                INSERT INTO UniverseTagRelatedLinks (
+                    universe_tag_id, url, link_type, external_id, descriptions
+                ) VALUES (?, ?, ?, ?, ?)
+            ", params![id, related_link.url().as_str(), related_link.link_type().as_str(),
+                related_link.external_id(), descriptions_id])?;
+        }
+        for child_id in universe_tag.children.iter() {
+            let child_id = map_id(&id_maps.universe_tags, *child_id, "Universe Tag")?;
+            transaction.execute("INSERT INTO UniverseTagChildren (parent, child) VALUES (?, ?)",
+                params![id, child_id])?;
+        }
+        for (related_id, relation) in universe_tag.related_universe_tags.iter() {
+            let related_id = map_id(&id_maps.universe_tags, *related_id, "Universe Tag")?;
+            let (id1, id2, db_relation) = normalize_relation(id, related_id, *relation);
+            transaction.execute("
+                INSERT INTO UniverseTagRelations (universe_tag_id1, universe_tag_id2, relation)
+                    VALUES (?, ?, ?)
+            ", params![id1, id2, db_relation])?;
+        }
+    }
+    for character in backup.characters.iter() {
+        let id = map_id(&id_maps.characters, character.id, "Character")?;
+        for (related_id, descriptions) in character.related_characters.iter() {
+            let related_id = map_id(&id_maps.characters, *related_id, "Character")?;
+            let descriptions_id = insert_lang_map(&transaction, descriptions)?;
+            transaction.execute("
+                INSERT INTO CharacterRelations (character_id1, character_id2, descriptions)
+                    VALUES (?, ?, ?)
+            ", params![id, related_id, descriptions_id])?;
+        }
+    }
+    for source in backup.sources.iter() {
+        let id = map_id(&id_maps.sources, source.id, "Source")?;
+        for related_link in api::transform_related_links(source.related_links.clone())? {
+            let descriptions_id = insert_lang_map(&transaction, related_link.descriptions())?;
+            transaction.execute("
+                INSERT INTO SourceRelatedLinks (
+                    source_id, url, link_type, external_id, descriptions
+                ) VALUES (?, ?, ?, ?, ?)
+            ", params![id, related_link.url().as_str(), related_link.link_type().as_str(),
+                related_link.external_id(), descriptions_id])?;
+        }
+        for (date, descriptions) in source.dates.iter() {
+            let descriptions_id = insert_lang_map(&transaction, descriptions)?;
+            transaction.execute("
+                INSERT INTO SourceDates (source_id, date, descriptions) VALUES (?, ?, ?)
+            ", params![id, date, descriptions_id])?;
+        }
+        for universe_tag_id in source.universe_tags.iter() {
+            let universe_tag_id = map_id(&id_maps.universe_tags, *universe_tag_id, "Universe Tag")?;
+            transaction.execute("
+                INSERT INTO SourceUniverseTags (source_id, universe_tag_id) VALUES (?, ?)
+            ", params![id, universe_tag_id])?;
+        }
+        for (related_id, relation) in source.related_sources.iter() {
+            let related_id = map_id(&id_maps.sources, *related_id, "Source")?;
+            let (id1, id2, db_relation) = normalize_relation(id, related_id, *relation);
+            transaction.execute("
+                INSERT INTO SourceRelations (source_id1, source_id2, relation) VALUES (?, ?, ?)
+            ", params![id1, id2, db_relation])?;
+        }
+        for (person_id, role) in source.people.iter() {
+            let person_id = map_id(&id_maps.people, *person_id, "Person")?;
+            transaction.execute("
+                INSERT INTO SourcePeople (source_id, person_id, role) VALUES (?, ?, ?)
+            ", params![id, person_id, role.db_name()])?;
+            if let PersonRole::VoiceActor(character_id, lang) = role {
+                let source_person_id = transaction.last_insert_rowid();
+                let character_id = map_id(&id_maps.characters, *character_id, "Character")?;
+                transaction.execute("
+                    INSERT INTO SourcePersonVoiceActors (source_person_id, character_id, lang)
+                        VALUES (?, ?, ?)
+                ", params![source_person_id, character_id, lang])?;
+            }
+        }
+        for character_id in source.characters.iter() {
+            let character_id = map_id(&id_maps.characters, *character_id, "Character")?;
+            transaction.execute("
+                INSERT INTO SourceCharacters (source_id, character_id) VALUES (?, ?)
+            ", params![id, character_id])?;
+        }
+        for (company_id, company_role) in source.companies.iter() {
+            let company_id = map_id(&id_maps.companies, *company_id, "Company")?;
+            transaction.execute("
+                INSERT INTO SourceCompanies (source_id, company_id, company_role) VALUES (?, ?, ?)
+            ", params![id, company_id, company_role])?;
+        }
+    }
+
+    transaction.commit()?;
+    Ok(id_maps)
+}
+
+fn insert_lang_map(db: &Connection, map: &LangMap) -> Result<i64, Error> {
+    api::insert_new_lang_map(map, db)?;
+    Ok(db.last_insert_rowid())
+}
+
+/// Inserts the names and descriptions of a new item, keeping the ID if we're restoring.
+/// Any other columns will need to be filled out afterwards.
+/// Returns the ID of the inserted item.
+fn insert_item(db: &Connection, mode: ImportMode, table_name: &str, id: u64,
+names: &LangMap, descriptions: &LangMap) -> Result<u64, Error> {
+    let names_id = insert_lang_map(db, names)?;
+    let descriptions_id = insert_lang_map(db, descriptions)?;
+    // A NULL ID lets the DB pick a new one
+    let id = match mode {
+        ImportMode::Restore => Some(id as i64),
+        ImportMode::Merge => None,
+    };
+    // A Source can't be inserted without a type, so use a placeholder until it gets updated
+    let (extra_columns, extra_values) = if table_name == "Sources" {
+        (", source_type", ", ''")
+    } else {
+        ("", "")
+    };
+
+    db.execute(&format!(
+        "INSERT INTO {} (id, names, descriptions{}) VALUES (?, ?, ?{})",
+        table_name, extra_columns, extra_values,
+    ), params![id, names_id, descriptions_id])
+        .with_context(|| format!("Failed to insert into {}", table_name))?;
+    Ok(db.last_insert_rowid() as u64)
+}
+
+fn insert_aliases(db: &Connection, table_name: &str, id_column: &str, id: u64,
+aliases: &LangMapList) -> Result<(), Error> {
+    let mut insert_alias_statement = db.prepare(&format!(
+        "INSERT INTO {} ({}, lang, alias) VALUES (?, ?, ?)", table_name, id_column
+    ))?;
+    for lang in Lang::all().iter() {
+        if let Some(lang_aliases) = aliases.get(lang) {
+            for alias in lang_aliases {
+                insert_alias_statement.execute(params![id as i64, lang, alias])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn map_id(id_map: &BTreeMap<u64, u64>, id: u64, item_name: &str) -> Result<i64, Error> {
+    id_map.get(&id)
+        .map(|new_id| *new_id as i64)
+        .ok_or_else(|| Error::Validation(
+            format!("The backup refers to a {} that it doesn't have: {}", item_name, id)))
+}
+
+/// Returns (id1, id2, relation) for the DB
+fn normalize_relation(id: i64, related_id: i64, relation: Relation)
+-> (i64, i64, DBSourceRelation) {
+    let (first_spot, db_relation) = DBSourceRelation::normalize(relation);
+    if first_spot {
+        (id, related_id, db_relation)
+    } else {
+        (related_id, id, db_relation)
+    }
+}
@@ -1,8 +1,14 @@
+pub mod link_types;
 mod roles;
 pub use roles::{CompanyRole, PersonRole};
 
 use std::collections::{BTreeMap};
 
+use schemars::{
+    JsonSchema,
+    gen::{SchemaGenerator},
+    schema::{Schema},
+};
 use serde::{
     Deserialize, Serialize,
     ser::{SerializeStruct},
@@ -10,11 +16,12 @@ use serde::{
 use url::{Url, Host};
 
 use crate::{
+    error::{Error},
     impl_sql_simple_enum,
     simple_enum::{SimpleEnum},
 };
 
-#[derive(Copy, Clone, Deserialize, Serialize, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Copy, Clone, Deserialize, Serialize, Eq, Ord, PartialEq, PartialOrd, JsonSchema)]
 pub enum Lang {
     English,
     Japanese,
@@ -39,7 +46,7 @@ impl SimpleEnum for Lang {
 impl_sql_simple_enum!(Lang);
 
 
-#[derive(Copy, Clone, Deserialize, Serialize)]
+#[derive(Copy, Clone, Deserialize, Serialize, JsonSchema)]
 pub enum Relation {
     Before,
     After,
@@ -66,83 +73,105 @@ impl_sql_simple_enum!(Relation);
 pub struct RelatedLink {
     url: Url,
     link_type: LinkType,
+    /// The ID that the site uses for the item, when the link type has a way to find it
+    external_id: Option<String>,
     descriptions: LangMap,
 }
 impl RelatedLink {
-    pub fn new(url: &str, descriptions: LangMap) -> Result<RelatedLink, String> {
-        let url = Url::parse(url).map_err(|e| e.to_string())?;
+    /// The URL gets put into its canonical form, so the same page always has the same URL
+    pub fn new(url: &str, descriptions: LangMap) -> Result<RelatedLink, Error> {
+        let mut url = Url::parse(url)?;
         let host = if let Some(host) = url.host() {
             match host {
-                Host::Domain(string) => string,
+                Host::Domain(string) => string.to_string(),
                 Host::Ipv4(_) | Host::Ipv6(_) =>
-                    return Err(format!("The URL cannot be an IP address: {}", url.as_str())),
+                    return Err(Error::Validation(
+                        format!("The URL cannot be an IP address: {}", url.as_str()))),
             }
         } else {
-            return Err(format!("Failed to find a host for: {}", url.as_str()));
+            return Err(Error::Validation(format!("Failed to find a host for: {}", url.as_str())));
         };
-        let link_type = if let Some(link_type) = LinkType::from_host(host) {
-            link_type
+        let link_type = if let Some(definition) = link_types::registry().find_by_host(&host) {
+            definition.canonicalize(&mut url)?;
+            LinkType(definition.name.clone())
         } else {
-            return Err(format!("Failed to find a link type for: {}", url.as_str()));
+            return Err(Error::Validation(
+                format!("Failed to find a link type for: {}", url.as_str())));
         };
+        let external_id = link_type.external_id(&url);
         Ok(RelatedLink {
             url,
             link_type,
+            external_id,
             descriptions,
         })
     }
     pub fn url(&self) -> &Url { &self.url }
-    pub fn link_type(&self) -> LinkType { self.link_type }
+    pub fn link_type(&self) -> &LinkType { &self.link_type }
+    pub fn external_id(&self) -> Option<&str> { self.external_id.as_deref() }
     pub fn descriptions(&self) -> &LangMap { &self.descriptions }
 }
 /// Implemented because URL is not serializable
 impl serde::Serialize for RelatedLink {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut s = serializer.serialize_struct("RelatedLink", 3)?;
+        let mut s = serializer.serialize_struct("RelatedLink", 5)?;
         s.serialize_field("url", self.url.as_str())?;
         s.serialize_field("link_type", &self.link_type)?;
+        s.serialize_field("link_type_name", self.link_type.display_name())?;
+        s.serialize_field("external_id", &self.external_id)?;
         s.serialize_field("descriptions", &self.descriptions)?;
         s.end()
     }
 }
-#[derive(Copy, Clone, Serialize)]
-pub enum LinkType {
-    /// This is for https://syosetu.com/ and the novels that it contains
-    ShousetsukaNarou,
-    /// Any part of wikipedia.org
-    Wikipedia,
-}
-impl SimpleEnum for LinkType {
-    fn all() -> &'static [LinkType] {
-        &[
-            Self::ShousetsukaNarou,
-            Self::Wikipedia,
-        ]
-    }
-    fn as_str(&self) -> &'static str {
-        match self {
-            Self::ShousetsukaNarou => "ShousetsukaNarou",
-            Self::Wikipedia => "Wikipedia",
-        }
+/// Reads what the Serialize above writes.
+/// The URL is kept the way it is, since it was made canonical before it was sent.
+impl<'de> Deserialize<'de> for RelatedLink {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let link = SerializedRelatedLink::deserialize(deserializer)?;
+        Ok(RelatedLink {
+            url: Url::parse(&link.url).map_err(serde::de::Error::custom)?,
+            link_type: link.link_type,
+            external_id: link.external_id,
+            descriptions: link.descriptions,
+        })
     }
 }
+impl JsonSchema for RelatedLink {
+    fn schema_name() -> String { SerializedRelatedLink::schema_name() }
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema { SerializedRelatedLink::json_schema(gen) }
+}
+// The same fields as the Serialize above
+#[derive(Deserialize, JsonSchema)]
+#[schemars(rename = "RelatedLink")]
+struct SerializedRelatedLink {
+    url: String,
+    link_type: LinkType,
+    /// The display name of the link type
+    #[allow(dead_code)]
+    link_type_name: String,
+    external_id: Option<String>,
+    descriptions: LangMap,
+}
+/// The name of a site in the link type registry
+#[derive(Clone, Deserialize, Serialize, JsonSchema, Eq, PartialEq)]
+#[serde(transparent)]
+pub struct LinkType(String);
 impl LinkType {
-    /// Pairs with a URL and the matching link type
-    fn host_pairs() -> &'static [(&'static str, LinkType)] {
-        &[
-            ("syosetu.com", Self::ShousetsukaNarou),
-            ("wikipedia.org", Self::Wikipedia),
-        ]
+    pub fn as_str(&self) -> &str { &self.0 }
+
+    /// Falls back to the name when the registry doesn't have the link type anymore
+    pub fn display_name(&self) -> &str {
+        link_types::registry().get(&self.0)
+            .map_or(&self.0, |definition| &definition.display_name)
     }
-    fn from_host(host: &str) -> Option<LinkType> {
-        for (host_part, link_type) in LinkType::host_pairs().iter() {
-            if host.contains(host_part) { return Some(*link_type); }
-        }
-        None
+
+    /// Finds the ID that the site uses for the item in the URL
+    pub fn external_id(&self, url: &Url) -> Option<String> {
+        link_types::registry().get(&self.0)?.external_id(url)
     }
 }
 
-#[derive(Copy, Clone, Deserialize, Serialize)]
+#[derive(Copy, Clone, Deserialize, Serialize, JsonSchema)]
 pub enum SourceType {
     Comic, // 漫画
     ComicRunning, // 連載の漫画話 (原点は関係のないこと)
@@ -0,0 +1,268 @@
+//! The sites that a RelatedLink can point to.
+//! Every site is defined with data, so more of them can be added with a config file
+//! without changing the code.
+
+use std::{
+    path::{Path},
+    sync::{OnceLock},
+};
+
+use percent_encoding::{percent_decode_str};
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+use url::{Url};
+
+use crate::utils;
+use crate::error::{Error, ResultExt};
+
+/// The names of the link types that the code needs to find by itself
+pub const SHOUSETSUKA_NAROU: &str = "ShousetsukaNarou";
+pub const WIKIPEDIA: &str = "Wikipedia";
+pub const MY_ANIME_LIST: &str = "MyAnimeList";
+pub const ANI_LIST: &str = "AniList";
+
+/// Query parameters that only say where a visit came from. Anything starting with "utm_" is too.
+const TRACKING_PARAMS: &[&str] = &[
+    "_ga", "fbclid", "gclid", "igshid", "mc_cid", "mc_eid", "ref", "ref_", "si", "yclid",
+];
+
+/// The registry that every RelatedLink uses. It's the built-in one until it gets set.
+static REGISTRY: OnceLock<LinkTypeRegistry> = OnceLock::new();
+
+/// Sets the registry to use for the rest of the run.
+/// This can only happen once, and before any link is made.
+pub fn init_registry(registry: LinkTypeRegistry) -> Result<(), Error> {
+    REGISTRY.set(registry)
+        .map_err(|_| Error::Conflict("The link type registry has already been set".to_string()))
+}
+
+pub fn registry() -> &'static LinkTypeRegistry {
+    REGISTRY.get_or_init(LinkTypeRegistry::built_in)
+}
+
+/// How a site is recognized and how its IDs are found
+#[derive(Clone, Deserialize, Serialize, JsonSchema)]
+pub struct LinkTypeDefinition {
+    /// The name that gets saved and sent with every link (like "MyAnimeList")
+    pub name: String,
+    /// The name to show for the site
+    pub display_name: String,
+    /// The domains of the site. Their subdomains are part of the site too,
+    /// but a domain that only ends with the same text (like "notsyosetu.com") isn't.
+    pub hosts: Vec<String>,
+    /// Where the site's ID is in the URL, tried in order. Like "/anime/{id}" or "?id={id}".
+    /// A pattern can start with a host ("ncode.syosetu.com/{id}") to only match that host.
+    /// A "*" segment matches any segment. When there's more than one part in {}
+    /// (like "{lang}.wikipedia.org/wiki/{title}"), the ID is all of them joined by ":".
+    #[serde(default)]
+    pub id_patterns: Vec<String>,
+}
+impl LinkTypeDefinition {
+    fn new(name: &str, display_name: &str, hosts: &[&str], id_patterns: &[&str])
+    -> LinkTypeDefinition {
+        LinkTypeDefinition {
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            hosts: hosts.iter().map(|host| host.to_string()).collect(),
+            id_patterns: id_patterns.iter().map(|pattern| pattern.to_string()).collect(),
+        }
+    }
+
+    /// The domain in the hosts that the host is or is a subdomain of
+    fn site_host(&self, host: &str) -> Option<&str> {
+        let host = host.trim_end_matches('.');
+        self.hosts.iter()
+            .map(String::as_str)
+            .find(|site_host| match host.strip_suffix(*site_host) {
+                Some(subdomains) => subdomains.is_empty() || subdomains.ends_with('.'),
+                None => false,
+            })
+    }
+
+    /// Forces https, takes out the mobile and www parts of the host (like en.m.wikipedia.org),
+    /// and removes the tracking query parameters, the fragment and any trailing slash
+    pub fn canonicalize(&self, url: &mut Url) -> Result<(), Error> {
+        match url.scheme() {
+            "https" => {},
+            "http" => url.set_scheme("https")
+                .map_err(|_| Error::Validation(format!("Failed to change {} to https", url)))?,
+            scheme => return Err(Error::Validation(
+                format!("{} links aren't supported: {}", scheme, url))),
+        }
+
+        let host = url.host_str().unwrap_or("").trim_end_matches('.').to_string();
+        if let Some(site_host) = self.site_host(&host) {
+            let subdomains = host[..host.len() - site_host.len()].trim_end_matches('.');
+            let kept: Vec<&str> = subdomains.split('.')
+                .filter(|label| !matches!(*label, "" | "m" | "www"))
+                .chain(std::iter::once(site_host))
+                .collect();
+            let canonical_host = kept.join(".");
+            if canonical_host != url.host_str().unwrap_or("") {
+                url.set_host(Some(&canonical_host))?;
+            }
+        }
+
+        let is_tracking = |key: &str| key.starts_with("utm_") || TRACKING_PARAMS.contains(&key);
+        if url.query_pairs().any(|(key, _)| is_tracking(&key)) {
+            let kept: Vec<(String, String)> = url.query_pairs()
+                .filter(|(key, _)| !is_tracking(key))
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect();
+            if kept.is_empty() {
+                url.set_query(None);
+            } else {
+                url.query_pairs_mut().clear().extend_pairs(kept);
+            }
+        }
+
+        url.set_fragment(None);
+        // The root path always keeps its slash
+        if url.path().len() > 1 && url.path().ends_with('/') {
+            let path = url.path().trim_end_matches('/').to_string();
+            url.set_path(&path);
+        }
+        Ok(())
+    }
+
+    /// The site's ID for the item in the URL, from the first pattern that matches
+    pub fn external_id(&self, url: &Url) -> Option<String> {
+        self.id_patterns.iter().find_map(|pattern| match_id_pattern(pattern, url))
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.name.is_empty() {
+            return Err(Error::Validation("A link type needs a name".to_string()));
+        }
+        if self.hosts.is_empty() {
+            return Err(Error::Validation(
+                format!("The link type {} needs at least 1 host", self.name)));
+        }
+        for pattern in self.id_patterns.iter() {
+            if !pattern.split(['/', '.', '=']).any(is_capture) {
+                return Err(Error::Validation(format!(
+                    "The ID pattern {} of {} needs a part like {{id}}", pattern, self.name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct LinkTypeRegistry {
+    definitions: Vec<LinkTypeDefinition>,
+}
+impl LinkTypeRegistry {
+    pub fn built_in() -> LinkTypeRegistry {
+        let definition = LinkTypeDefinition::new;
+        LinkTypeRegistry {
+            definitions: vec![
+                definition(SHOUSETSUKA_NAROU, "小説家になろう", &["syosetu.com"],
+                    &["ncode.syosetu.com/{id}", "novel18.syosetu.com/{id}"]),
+                definition(WIKIPEDIA, "Wikipedia", &["wikipedia.org"],
+                    &["{lang}.wikipedia.org/wiki/{title}"]),
+                definition(MY_ANIME_LIST, "MyAnimeList", &["myanimelist.net"],
+                    &["/anime/{id}", "/manga/{id}"]),
+                definition(ANI_LIST, "AniList", &["anilist.co"], &["/anime/{id}", "/manga/{id}"]),
+                definition("VNDB", "VNDB", &["vndb.org"], &["/{id}"]),
+                definition("Kakuyomu", "カクヨム", &["kakuyomu.jp"], &["/works/{id}"]),
+                definition("Amazon", "Amazon",
+                    &["amazon.com", "amazon.co.jp", "amazon.co.uk", "amazon.ca"],
+                    &["/dp/{id}", "/gp/product/{id}", "/*/dp/{id}"]),
+                definition("BookWalker", "BOOK☆WALKER", &["bookwalker.jp"],
+                    &["/series/{id}", "/{id}"]),
+                definition("Steam", "Steam", &["steampowered.com", "steamcommunity.com"],
+                    &["/app/{id}"]),
+                definition("Pixiv", "pixiv", &["pixiv.net"],
+                    &["/novel/series/{id}", "/artworks/{id}", "/novel/show.php?id={id}"]),
+                definition("Kadokawa", "KADOKAWA", &["kadokawa.co.jp"], &[]),
+                definition("Shueisha", "集英社", &["shueisha.co.jp"], &[]),
+                definition("Kodansha", "講談社", &["kodansha.co.jp", "kodansha.us"], &[]),
+                definition("Shogakukan", "小学館", &["shogakukan.co.jp"], &[]),
+                definition("YenPress", "Yen Press", &["yenpress.com"], &[]),
+                definition("SevenSeas", "Seven Seas Entertainment",
+                    &["sevenseasentertainment.com"], &[]),
+                definition("JNovelClub", "J-Novel Club", &["j-novel.club"], &[]),
+            ],
+        }
+    }
+
+    /// The built-in link types with the ones in the file (a JSON list of LinkTypeDefinitions).
+    /// A link type in the file replaces the built-in one with the same name.
+    pub fn with_config_file(file: impl AsRef<Path>) -> Result<LinkTypeRegistry, Error> {
+        let file = file.as_ref();
+        let definitions: Vec<LinkTypeDefinition> = utils::read_json_file(file)
+            .with_context(|| format!("Failed to read the link types in {}", file.display()))?;
+
+        let mut registry = Self::built_in();
+        for definition in definitions {
+            definition.validate()?;
+            match registry.definitions.iter_mut().find(|old| old.name == definition.name) {
+                Some(old) => *old = definition,
+                None => registry.definitions.push(definition),
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn definitions(&self) -> &[LinkTypeDefinition] { &self.definitions }
+
+    pub fn get(&self, name: &str) -> Option<&LinkTypeDefinition> {
+        self.definitions.iter().find(|definition| definition.name == name)
+    }
+
+    pub fn find_by_host(&self, host: &str) -> Option<&LinkTypeDefinition> {
+        self.definitions.iter().find(|definition| definition.site_host(host).is_some())
+    }
+}
+
+/// Gives the parts of the URL where the {} are in the pattern, if the rest of it matches.
+/// A part can be a whole label of the host, a whole segment of the path, or a query value.
+fn match_id_pattern(pattern: &str, url: &Url) -> Option<String> {
+    let (pattern, query_pattern) = match pattern.find('?') {
+        Some(index) => (&pattern[..index], Some(&pattern[index + 1..])),
+        None => (pattern, None),
+    };
+    let mut parts = Vec::new();
+    let mut match_part = |part_pattern: &str, part: &str| {
+        if is_capture(part_pattern) {
+            parts.push(part.to_string());
+            !part.is_empty()
+        } else {
+            part_pattern == "*" || part_pattern == part
+        }
+    };
+
+    let path_pattern = if pattern.starts_with('/') || pattern.is_empty() {
+        pattern
+    } else {
+        let index = pattern.find('/').unwrap_or(pattern.len());
+        let host_labels: Vec<&str> = url.host_str()?.trim_end_matches('.').split('.').collect();
+        let pattern_labels: Vec<&str> = pattern[..index].split('.').collect();
+        if host_labels.len() != pattern_labels.len() {
+            return None;
+        }
+        for (label_pattern, label) in pattern_labels.iter().zip(host_labels) {
+            if !match_part(label_pattern, label) { return None; }
+        }
+        &pattern[index..]
+    };
+
+    // The URL keeps its path percent-encoded, but the IDs shouldn't be
+    let mut segments = url.path_segments()?.filter(|segment| !segment.is_empty());
+    for segment_pattern in path_pattern.split('/').filter(|segment| !segment.is_empty()) {
+        let segment = percent_decode_str(segments.next()?).decode_utf8_lossy();
+        if !match_part(segment_pattern, &segment) { return None; }
+    }
+
+    if let Some(query_pattern) = query_pattern {
+        let (key, value_pattern) = query_pattern.split_at(query_pattern.find('=')?);
+        let value = url.query_pairs().find(|(query_key, _)| query_key == key)?.1;
+        if !match_part(&value_pattern[1..], &value) { return None; }
+    }
+    Some(parts.join(":"))
+}
+
+/// A part of a pattern like {id} or {lang}
+fn is_capture(part_pattern: &str) -> bool {
+    part_pattern.len() > 2 && part_pattern.starts_with('{') && part_pattern.ends_with('}')
+}
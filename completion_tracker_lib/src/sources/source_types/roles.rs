@@ -1,3 +1,4 @@
+use schemars::{JsonSchema};
 use serde::{Deserialize, Serialize};
 
 use super::{Lang};
@@ -7,7 +8,7 @@ use crate::{
 };
 
 /// The role that a company can have in the creation of a source
-#[derive(Copy, Clone, Deserialize, Serialize)]
+#[derive(Copy, Clone, Deserialize, Serialize, JsonSchema)]
 pub enum CompanyRole {
     Developer,
     Publisher,
@@ -32,7 +33,7 @@ impl SimpleEnum for CompanyRole {
 impl_sql_simple_enum!(CompanyRole);
 
 /// The role a person can have in the creation of a source
-#[derive(Copy, Clone, Deserialize, Serialize)]
+#[derive(Copy, Clone, Deserialize, Serialize, JsonSchema)]
 pub enum PersonRole {
     Writer, // 著者
     Illustrator, // イラスト
@@ -40,3 +41,26 @@ pub enum PersonRole {
     /// A character's ID with their spoken language
     VoiceActor(u64, Lang), // 声優
 }
+impl PersonRole {
+    /// The name of the role that goes into the DB.
+    /// The character and language of a VoiceActor go into SourcePersonVoiceActors instead.
+    pub fn db_name(&self) -> &'static str {
+        match self {
+            Self::Writer => "Writer",
+            Self::Illustrator => "Illustrator",
+            Self::ComicArtist => "ComicArtist",
+            Self::VoiceActor(_, _) => "VoiceActor",
+        }
+    }
+    /// Makes the role back from the name in the DB.
+    /// A VoiceActor needs the (character ID, language) from SourcePersonVoiceActors.
+    pub fn from_db_name(name: &str, voice_actor: Option<(u64, Lang)>) -> Option<PersonRole> {
+        match (name, voice_actor) {
+            ("Writer", _) => Some(Self::Writer),
+            ("Illustrator", _) => Some(Self::Illustrator),
+            ("ComicArtist", _) => Some(Self::ComicArtist),
+            ("VoiceActor", Some((character_id, lang))) => Some(Self::VoiceActor(character_id, lang)),
+            _ => None,
+        }
+    }
+}
@@ -1,9 +1,21 @@
+pub mod entry;
+pub mod smart_list;
+
 use std::{
     fs::{DirBuilder},
     path::{Path, PathBuf},
 };
 
-use serde::{Deserialize};
+use rusqlite::{Connection};
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, ResultExt},
+    impl_sql_simple_enum,
+    simple_enum::{SimpleEnum},
+    sources::api,
+};
 
 const DEFAULT_TRACKER_NAME: &'static str = "Default Tracker";
 
@@ -40,11 +52,10 @@ pub struct Tracker {
 }
 impl Tracker {
     pub fn new_from_info(tracking_folder: impl AsRef<Path>, tracking_infos: &[TrackingInfo])
-    -> Result<Vec<Tracker>, String> {
+    -> Result<Vec<Tracker>, Error> {
         let tracking_folder = tracking_folder.as_ref();
         if !tracking_folder.is_dir() {
-            DirBuilder::new().create(&tracking_folder)
-                .map_err(|e| e.to_string())?;
+            DirBuilder::new().create(&tracking_folder)?;
         }
 
         let mut trackers = Vec::with_capacity(tracking_infos.len());
@@ -65,9 +76,9 @@ impl Tracker {
                 // Don't let them use the any tracking name more than once
                 for other_tracker in trackers.iter() {
                     if other_tracker.name() == name {
-                        return Err(
+                        return Err(Error::Conflict(
                             format!("Cannot have duplicate tracker names: {}", name)
-                        );
+                        ));
                     }
                 }
 
@@ -83,17 +94,17 @@ impl Tracker {
     }
 
     fn new(tracking_folder: impl AsRef<Path>, name: String, game_id: GameID)
-    -> Result<Tracker, String> {
+    -> Result<Tracker, Error> {
         if name.is_empty() {
-            return Err("The tracker name must not be empty".to_string());
+            return Err(Error::Validation("The tracker name must not be empty".to_string()));
         }
         if name.chars().any(|c|
             c.is_ascii_punctuation() ||
             c.is_ascii_control()
         ) {
-            return Err(
+            return Err(Error::Validation(
                 "There cannot be any punctuation or control characters in the tracker name".to_string()
-            );
+            ));
         }
 
         let mut file = tracking_folder.as_ref().join(&name);
@@ -107,6 +118,69 @@ impl Tracker {
     }
 
     pub fn name(&self) -> &str { self.name.as_str() }
+    pub fn file(&self) -> &Path { self.file.as_path() }
+}
+
+/// Creates the tables for a single tracker's database.
+/// The source IDs point into the sources DB, so they can't be foreign keys here.
+pub fn create_tables(db: &Connection) -> Result<(), Error> {
+    // The entries follow the shape of TrackerEntry and TrackerTimeStatus (below)
+    db.execute_batch("
+        CREATE TABLE IF NOT EXISTS TrackerEntries (
+            id INTEGER PRIMARY KEY,
+            source_id INTEGER NOT NULL UNIQUE,
+            description TEXT NOT NULL DEFAULT '',
+            play_time INTEGER,
+            times_repeated INTEGER NOT NULL DEFAULT 0,
+            last_updated TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS TrackerEntryStatusUpdates (
+            id INTEGER PRIMARY KEY,
+            entry_id INTEGER NOT NULL REFERENCES TrackerEntries(id),
+            status TEXT NOT NULL,
+            date TEXT,
+            play_time INTEGER,
+            recorded_at TEXT,
+            UNIQUE(entry_id, status)
+        );
+    ").context("Error creating the Tracker tables")?;
+    // Tracker DBs are kept between runs, so the ones made before these columns need them added
+    add_missing_column(db, "TrackerEntries", "times_repeated", "INTEGER NOT NULL DEFAULT 0")?;
+    add_missing_column(db, "TrackerEntryStatusUpdates", "recorded_at", "TEXT")?;
+    db.execute_batch("
+        CREATE TABLE IF NOT EXISTS SmartLists (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL UNIQUE,
+            query TEXT NOT NULL,
+            item_type TEXT
+        );
+        CREATE TABLE IF NOT EXISTS SmartListTypeBoosts (
+            id INTEGER PRIMARY KEY,
+            smart_list_id INTEGER NOT NULL REFERENCES SmartLists(id),
+            item_type TEXT NOT NULL,
+            boost REAL NOT NULL,
+            UNIQUE(smart_list_id, item_type)
+        );
+    ").context("Error creating the Smart List tables")?;
+    Ok(())
+}
+
+fn add_missing_column(db: &Connection, table_name: &str, column_name: &str, definition: &str)
+-> Result<(), Error> {
+    let mut statement = db.prepare(&format!("PRAGMA table_info({})", table_name))?;
+    let column_names = api::collect_query_map(
+        statement.query_map(rusqlite::NO_PARAMS, |row| {
+            let name: String = row.get(1)?;
+            Ok(name)
+        })
+    )?;
+
+    if !column_names.iter().any(|name| name == column_name) {
+        db.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {};", table_name, column_name, definition
+        )).with_context(|| format!("Error adding {} to {}", column_name, table_name))?;
+    }
+    Ok(())
 }
 
 // #[derive(Clone)]
@@ -138,18 +212,44 @@ impl Tracker {
 //     time: Option<u64>,
 // }
 
-// /// This is the status of the source we're tracking.
-// /// We don't need a "not started" status since that's just all the other sources
-// #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
-// pub enum CompletionStatus {
-//     /// The source has been started reading, playing, watching, etc.
-//     InProgress,
-//     /// The source has been done to the end.
-//     /// Read to the end of the book, watched until the credits, etc.
-//     Complete,
-//     /// Games only. Not only is the game finished, it has been completed as much as possible.
-//     /// If the game has achievements then this is when all of them have been unlocked.
-//     /// If there are no achievements then it would be up to whatever the player deems
-//     /// "most complete".
-//     GameComplete,
-// }
+/// This is the status of the source we're tracking.
+/// We don't need a "not started" status since that's just all the other sources
+#[derive(Copy, Clone, Deserialize, Serialize, Ord, PartialOrd, Eq, PartialEq, JsonSchema)]
+pub enum CompletionStatus {
+    /// The source has been started reading, playing, watching, etc.
+    InProgress,
+    /// The source has been done to the end.
+    /// Read to the end of the book, watched until the credits, etc.
+    Complete,
+    /// Games only. Not only is the game finished, it has been completed as much as possible.
+    /// If the game has achievements then this is when all of them have been unlocked.
+    /// If there are no achievements then it would be up to whatever the player deems
+    /// "most complete".
+    GameComplete,
+}
+impl SimpleEnum for CompletionStatus {
+    fn all() -> &'static [CompletionStatus] {
+        &[
+            Self::InProgress,
+            Self::Complete,
+            Self::GameComplete,
+        ]
+    }
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::InProgress => "InProgress",
+            Self::Complete => "Complete",
+            Self::GameComplete => "GameComplete",
+        }
+    }
+}
+impl_sql_simple_enum!(CompletionStatus);
+impl CompletionStatus {
+    /// If this status means that the source has been done to the end
+    pub fn is_finished(self) -> bool {
+        match self {
+            Self::InProgress => false,
+            Self::Complete | Self::GameComplete => true,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{BTreeMap};
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    sources::api,
+    tracking::{CompletionStatus},
+};
+
+/// Everything that a tracker knows about a Source
+#[derive(Serialize)]
+pub struct TrackerEntry {
+    pub source_id: u64,
+    pub description: String,
+    /// The current play time (in seconds)
+    pub play_time: Option<u64>,
+    /// How many more times the Source was finished after the first time
+    pub times_repeated: u32,
+    pub last_updated: NaiveDate,
+    /// Each status is only reached once
+    pub status_updates: BTreeMap<CompletionStatus, StatusTime>,
+}
+impl TrackerEntry {
+    /// The furthest status that has been reached
+    pub fn current_status(&self) -> Option<CompletionStatus> {
+        self.status_updates.keys().next_back().copied()
+    }
+}
+
+/// When a status was reached
+#[derive(Copy, Clone, Serialize)]
+pub struct StatusTime {
+    pub date: Option<NaiveDate>,
+    /// The play time (in seconds)
+    pub play_time: Option<u64>,
+    /// When the status was last changed in the tracker.
+    /// Older statuses might not have this.
+    pub recorded_at: Option<DateTime<Utc>>,
+}
+
+/// A Source reaching a status in the tracker
+#[derive(Deserialize)]
+pub struct StatusUpdate {
+    pub source_id: u64,
+    pub status: CompletionStatus,
+    /// The day that the status was reached, if it's known
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    /// The play time (in seconds) when the status was reached
+    #[serde(default)]
+    pub play_time: Option<u64>,
+}
+
+pub fn update_status(tracker_db: &ConnectionHolder, update: StatusUpdate) -> Result<(), Error> {
+    let mut db = tracker_db.lock();
+    let transaction = db.transaction()?;
+    update_status_with(&transaction, &update)?;
+    transaction.commit()
+        .map_err(Error::from)
+}
+
+/// Reads every entry in the tracker, ordered by the Source ID
+pub fn read_all(tracker_db: &ConnectionHolder) -> Result<Vec<TrackerEntry>, Error> {
+    let db = tracker_db.lock();
+    let mut get_entries_statement = db.prepare("
+        SELECT id, source_id, description, play_time, times_repeated, last_updated
+            FROM TrackerEntries ORDER BY source_id
+    ")?;
+    let mut get_status_updates_statement = db.prepare("
+        SELECT status, date, play_time, recorded_at FROM TrackerEntryStatusUpdates
+            WHERE entry_id=?
+    ")?;
+
+    let raw_entries = api::collect_query_map(
+        get_entries_statement.query_map(rusqlite::NO_PARAMS, |row| {
+            let entry_id: i64 = row.get(0)?;
+            let source_id: i64 = row.get(1)?;
+            let play_time: Option<i64> = row.get(3)?;
+            Ok( (entry_id, TrackerEntry {
+                source_id: source_id as u64,
+                description: row.get(2)?,
+                play_time: play_time.map(|time| time as u64),
+                times_repeated: row.get(4)?,
+                last_updated: row.get(5)?,
+                status_updates: BTreeMap::new(),
+            }) )
+        })
+    )?;
+
+    let mut entries = Vec::with_capacity(raw_entries.len());
+    for (entry_id, mut entry) in raw_entries {
+        let status_updates = api::collect_query_map(
+            get_status_updates_statement.query_map([entry_id], |row| {
+                let status: CompletionStatus = row.get(0)?;
+                let play_time: Option<i64> = row.get(2)?;
+                Ok( (status, StatusTime {
+                    date: row.get(1)?,
+                    play_time: play_time.map(|time| time as u64),
+                    recorded_at: row.get(3)?,
+                }) )
+            })
+        )?;
+        entry.status_updates.extend(status_updates);
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Updates the status without starting a transaction, so it can be part of a bigger one.
+/// A date or play time that's already known won't be replaced with an unknown one.
+pub(crate) fn update_status_with(db: &Connection, update: &StatusUpdate) -> Result<(), Error> {
+    let entry_id = find_or_create_entry(db, update.source_id)?;
+    // Only a real change gets a new recorded_at, so that the feeds don't show it again
+    db.execute("
+        INSERT INTO TrackerEntryStatusUpdates (entry_id, status, date, play_time, recorded_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(entry_id, status) DO UPDATE SET
+                date=COALESCE(excluded.date, date),
+                play_time=COALESCE(excluded.play_time, play_time),
+                recorded_at=CASE
+                    WHEN COALESCE(excluded.date, date) IS NOT date
+                        OR COALESCE(excluded.play_time, play_time) IS NOT play_time
+                        OR recorded_at IS NULL
+                    THEN excluded.recorded_at
+                    ELSE recorded_at
+                END
+    ", params![
+        entry_id,
+        update.status,
+        update.date,
+        update.play_time.map(|time| time as i64),
+        Utc::now(),
+    ])?;
+    Ok(())
+}
+
+/// Sets how many more times the Source was finished after the first time
+pub(crate) fn set_times_repeated(db: &Connection, source_id: u64, times_repeated: u32)
+-> Result<(), Error> {
+    let entry_id = find_or_create_entry(db, source_id)?;
+    db.execute("UPDATE TrackerEntries SET times_repeated=? WHERE id=?",
+        params![times_repeated, entry_id])?;
+    Ok(())
+}
+
+pub(crate) fn set_description(db: &Connection, source_id: u64, description: &str)
+-> Result<(), Error> {
+    let entry_id = find_or_create_entry(db, source_id)?;
+    db.execute("UPDATE TrackerEntries SET description=? WHERE id=?", params![description, entry_id])?;
+    Ok(())
+}
+
+/// Returns the ID of the entry for the Source, after marking it as updated today
+fn find_or_create_entry(db: &Connection, source_id: u64) -> Result<i64, Error> {
+    let today = Local::today().naive_local();
+    db.execute("
+        INSERT INTO TrackerEntries (source_id, last_updated) VALUES (?, ?)
+            ON CONFLICT(source_id) DO UPDATE SET last_updated=excluded.last_updated
+    ", params![source_id as i64, today])?;
+    db.query_row("SELECT id FROM TrackerEntries WHERE source_id=?", [source_id as i64],
+        |row| row.get(0))
+        .map_err(Error::from)
+}
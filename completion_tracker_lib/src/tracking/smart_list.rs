@@ -0,0 +1,281 @@
+use std::collections::{BTreeMap};
+
+use rusqlite::{Statement, params};
+use schemars::{JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    TrackerDBs,
+    db_link::{ConnectionHolder},
+    error::{Error, ResultExt},
+    sources::api::{
+        self,
+        search::{self, ItemType, SearchQuery, SearchResults},
+    },
+};
+
+/// A saved search that is run again against the current data every time it's opened.
+/// The query can have any of the search qualifiers (eg. `type:webnovel tag:X status:unfinished`).
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct SmartListCreator {
+    pub name: String,
+    pub query: String,
+    pub item_type: Option<ItemType>,
+    #[serde(default)]
+    pub type_boosts: BTreeMap<ItemType, f64>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct SmartList {
+    pub id: u64,
+    pub name: String,
+    pub query: String,
+    pub item_type: Option<ItemType>,
+    pub type_boosts: BTreeMap<ItemType, f64>,
+}
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct SmartListSelector {
+    pub id: u64,
+}
+
+pub fn create(tracker_db: &ConnectionHolder, creator: SmartListCreator) -> Result<u64, Error> {
+    if creator.name.trim().is_empty() {
+        return Err(Error::Validation("The smart list name must not be empty".to_string()));
+    }
+    // Don't let a broken query get saved, since it would never be able to run
+    search::parse_filter(&creator.query)
+        .context("Failed to parse the query")?;
+    search::validate_type_boosts(&creator.type_boosts)?;
+
+    let mut tracker_db = tracker_db.lock();
+
+    let smart_list_id = {
+        let transaction = tracker_db.transaction()?;
+
+        transaction.execute("
+            INSERT INTO SmartLists (name, query, item_type) VALUES (?, ?, ?)
+        ", params![creator.name, creator.query, creator.item_type])?;
+        let smart_list_id = transaction.last_insert_rowid();
+
+        {
+            let mut insert_boost_statement = transaction.prepare("
+                INSERT INTO SmartListTypeBoosts (smart_list_id, item_type, boost) VALUES (?, ?, ?)
+            ")?;
+            for (item_type, boost) in creator.type_boosts {
+                insert_boost_statement.execute(params![smart_list_id, item_type, boost])?;
+            }
+        }
+
+        transaction.commit()?;
+        smart_list_id
+    };
+
+    Ok(smart_list_id as u64)
+}
+
+pub fn read_all(tracker_db: &ConnectionHolder) -> Result<Vec<SmartList>, Error> {
+    let tracker_db = tracker_db.lock();
+
+    let mut get_boosts_statement = tracker_db.prepare("
+        SELECT item_type, boost FROM SmartListTypeBoosts WHERE smart_list_id=?
+    ")?;
+    let mut get_smart_lists_statement = tracker_db.prepare("
+        SELECT id, name, query, item_type FROM SmartLists ORDER BY name
+    ")?;
+
+    let mapped_smart_lists = get_smart_lists_statement.query_map(rusqlite::NO_PARAMS, |row| {
+        let id: i64 = row.get(0)?;
+        Ok(SmartList {
+            id: id as u64,
+            name: row.get(1)?,
+            query: row.get(2)?,
+            item_type: row.get(3)?,
+            type_boosts: read_type_boosts(&mut get_boosts_statement, id)?,
+        })
+    });
+
+    api::collect_query_map(mapped_smart_lists)
+        .map_err(Error::from)
+}
+
+/// Reads the single smart list with the ID
+fn read(tracker_db: &ConnectionHolder, id: u64) -> Result<SmartList, Error> {
+    let tracker_db = tracker_db.lock();
+
+    let (name, query, item_type) = tracker_db.query_row("
+        SELECT name, query, item_type FROM SmartLists WHERE id=?
+    ", [id as i64], |row| Ok( (row.get(0)?, row.get(1)?, row.get(2)?) ))
+        .with_context(|| format!("Failed to find the smart list {}", id))?;
+    let mut get_boosts_statement = tracker_db.prepare("
+        SELECT item_type, boost FROM SmartListTypeBoosts WHERE smart_list_id=?
+    ")?;
+
+    Ok(SmartList {
+        id,
+        name,
+        query,
+        item_type,
+        type_boosts: read_type_boosts(&mut get_boosts_statement, id as i64)?,
+    })
+}
+
+fn read_type_boosts(get_boosts_statement: &mut Statement, smart_list_id: i64)
+-> rusqlite::Result<BTreeMap<ItemType, f64>> {
+    let type_boosts = api::collect_query_map(get_boosts_statement.query_map([smart_list_id], |row| {
+        let item_type: ItemType = row.get(0)?;
+        let boost: f64 = row.get(1)?;
+        Ok( (item_type, boost) )
+    }))?;
+    Ok(type_boosts.into_iter().collect())
+}
+
+/// Runs the saved search for the smart list in the tracker
+pub fn run(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs, tracker_name: &str,
+selector: SmartListSelector) -> Result<SearchResults, Error> {
+    let tracker_db = tracker_dbs.get(tracker_name)
+        .ok_or_else(|| Error::NotFound(format!("There isn't a tracker called {}", tracker_name)))?;
+
+    let smart_list = read(tracker_db, selector.id)?;
+
+    // The search will need the tracker's DB for itself, so we can't be holding onto it here
+    search::search(sources_db, tracker_dbs, SearchQuery {
+        query: smart_list.query,
+        item_type: smart_list.item_type,
+        tracker: Some(tracker_name.to_string()),
+        type_boosts: smart_list.type_boosts,
+    })
+}
+
+pub fn delete(tracker_db: &ConnectionHolder, selector: SmartListSelector) -> Result<(), Error> {
+    let mut tracker_db = tracker_db.lock();
+
+    let transaction = tracker_db.transaction()?;
+    transaction.execute("
+        DELETE FROM SmartListTypeBoosts WHERE smart_list_id=?
+    ", [selector.id as i64])?;
+    let deleted_count = transaction.execute("
+        DELETE FROM SmartLists WHERE id=?
+    ", [selector.id as i64])?;
+    if deleted_count == 0 {
+        return Err(Error::NotFound(format!("Failed to find the smart list {}", selector.id)));
+    }
+    transaction.commit()
+        .map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::{Connection};
+
+    use crate::{
+        simple_enum::{SimpleEnum},
+        sources::{
+            api::source::{self, SourceCreator},
+            source_types::{Lang, LangMap, SourceType},
+        },
+    };
+
+    const TRACKER: &str = "Default Tracker";
+
+    /// A sources DB with a Novel and a Comic, and a single tracker
+    fn make_dbs() -> (ConnectionHolder, TrackerDBs) {
+        let sources_db = crate::init_source_db().unwrap();
+        for (name, source_type) in &[("Spice and Wolf", SourceType::Novel),
+            ("Spice and Wolf", SourceType::Comic)] {
+            let mut names = LangMap::new();
+            names.insert(Lang::English, name.to_string());
+            source::create(&sources_db, SourceCreator {
+                names,
+                descriptions: LangMap::new(),
+                source_type: *source_type,
+                related_links: Vec::new(),
+                dates: Vec::new(),
+                universe_tags: Vec::new(),
+                related_sources: Vec::new(),
+                people: Vec::new(),
+                characters: Vec::new(),
+                companies: Vec::new(),
+            }).unwrap();
+        }
+
+        let connection = Connection::open_in_memory().unwrap();
+        crate::tracking::create_tables(&connection).unwrap();
+        let mut tracker_dbs = TrackerDBs::new();
+        tracker_dbs.insert(TRACKER.to_string(), ConnectionHolder::new(connection));
+        (sources_db, tracker_dbs)
+    }
+
+    fn creator(name: &str, query: &str) -> SmartListCreator {
+        SmartListCreator {
+            name: name.to_string(),
+            query: query.to_string(),
+            item_type: Some(ItemType::Source),
+            type_boosts: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn created_smart_lists_are_read_back() {
+        let (_, tracker_dbs) = make_dbs();
+        let tracker_db = &tracker_dbs[TRACKER];
+        let mut boosted = creator("Boosted", "wolf");
+        boosted.item_type = None;
+        boosted.type_boosts.insert(ItemType::Character, 0.5);
+        let boosted_id = create(tracker_db, boosted).unwrap();
+        let novels_id = create(tracker_db, creator("Novels", "type:Novel")).unwrap();
+
+        let smart_lists = read_all(tracker_db).unwrap();
+        let ids: Vec<u64> = smart_lists.iter().map(|smart_list| smart_list.id).collect();
+        assert_eq!(ids, vec![boosted_id, novels_id]);
+        assert_eq!(smart_lists[0].query, "wolf");
+        assert!(smart_lists[0].item_type.is_none());
+        assert_eq!(smart_lists[0].type_boosts.get(&ItemType::Character), Some(&0.5));
+        assert_eq!(smart_lists[1].item_type.map(|item_type| item_type.as_str()), Some("Source"));
+        assert!(smart_lists[1].type_boosts.is_empty());
+    }
+
+    #[test]
+    fn bad_smart_lists_are_not_saved() {
+        let (_, tracker_dbs) = make_dbs();
+        let tracker_db = &tracker_dbs[TRACKER];
+        assert!(matches!(create(tracker_db, creator(" ", "wolf")), Err(Error::Validation(_))));
+        assert!(matches!(create(tracker_db, creator("Broken", "year:never")),
+            Err(Error::Validation(_))));
+        // A list with a bad boost would fail every time it's run
+        for boost in [-1.0, f64::NAN, f64::INFINITY] {
+            let mut bad_boost = creator("Boosted", "wolf");
+            bad_boost.type_boosts.insert(ItemType::Character, boost);
+            assert!(matches!(create(tracker_db, bad_boost), Err(Error::Validation(_))));
+        }
+        assert!(read_all(tracker_db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn running_a_smart_list_searches() {
+        let (sources_db, tracker_dbs) = make_dbs();
+        let id = create(&tracker_dbs[TRACKER], creator("Novels", "type:Novel")).unwrap();
+
+        let results = run(&sources_db, &tracker_dbs, TRACKER, SmartListSelector { id }).unwrap();
+        let ids: Vec<u64> = results.iter().map(|result| result.id).collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[test]
+    fn missing_smart_lists_are_not_found() {
+        let (sources_db, tracker_dbs) = make_dbs();
+        let id = create(&tracker_dbs[TRACKER], creator("Novels", "type:Novel")).unwrap();
+
+        assert!(matches!(run(&sources_db, &tracker_dbs, TRACKER, SmartListSelector { id: id + 1 }),
+            Err(Error::NotFound(_))));
+        assert!(matches!(run(&sources_db, &tracker_dbs, "Other", SmartListSelector { id }),
+            Err(Error::NotFound(_))));
+
+        delete(&tracker_dbs[TRACKER], SmartListSelector { id }).unwrap();
+        assert!(matches!(run(&sources_db, &tracker_dbs, TRACKER, SmartListSelector { id }),
+            Err(Error::NotFound(_))));
+        assert!(matches!(delete(&tracker_dbs[TRACKER], SmartListSelector { id }),
+            Err(Error::NotFound(_))));
+    }
+}
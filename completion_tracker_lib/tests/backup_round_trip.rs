@@ -0,0 +1,203 @@
+//! A backup that goes into a DB and back out again should come out the same.
+
+use chrono::{NaiveDate};
+
+use completion_tracker_lib::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    sources::{
+        backup::{
+            self, BACKUP_VERSION, CharacterBackup, CompanyBackup, ImportMode, PersonBackup,
+            SourceBackup, SourcesBackup, UniverseTagBackup,
+        },
+        source_types::{
+            CompanyRole, Country, Lang, LangMap, LangMapList, PersonRole, Relation, SourceType,
+        },
+    },
+};
+
+fn lang_map(english: &str, japanese: Option<&str>) -> LangMap {
+    let mut map = LangMap::new();
+    map.insert(Lang::English, english.to_string());
+    if let Some(japanese) = japanese {
+        map.insert(Lang::Japanese, japanese.to_string());
+    }
+    map
+}
+
+fn make_backup() -> SourcesBackup {
+    let mut aliases = LangMapList::new();
+    aliases.insert(Lang::Japanese, vec!["きのこ".to_string(), "奈須".to_string()]);
+
+    SourcesBackup {
+        version: BACKUP_VERSION,
+        universe_tags: vec![
+            UniverseTagBackup {
+                id: 1,
+                names: lang_map("Fate", None),
+                descriptions: lang_map("The whole series", None),
+                related_links: vec![
+                    ("https://en.wikipedia.org/wiki/Fate/stay_night".to_string(),
+                        lang_map("Wikipedia", Some("ウィキペディア"))),
+                ],
+                children: vec![2],
+                related_universe_tags: Vec::new(),
+            },
+            UniverseTagBackup {
+                id: 2,
+                names: lang_map("Fate/Zero", None),
+                descriptions: lang_map("-", None),
+                related_links: Vec::new(),
+                children: Vec::new(),
+                related_universe_tags: vec![(3, Relation::Before)],
+            },
+            UniverseTagBackup {
+                id: 3,
+                names: lang_map("Fate/stay night", None),
+                descriptions: lang_map("-", None),
+                related_links: Vec::new(),
+                children: Vec::new(),
+                related_universe_tags: Vec::new(),
+            },
+        ],
+        people: vec![
+            PersonBackup {
+                id: 1,
+                names: lang_map("Kinoko Nasu", Some("奈須きのこ")),
+                descriptions: lang_map("-", None),
+                aliases,
+                birth_country: Some(Country::Japan),
+                birth_date: Some(NaiveDate::from_ymd_opt(1973, 11, 28).unwrap()),
+                death_date: None,
+            },
+            PersonBackup {
+                id: 2,
+                names: lang_map("Rie Kugimiya", None),
+                descriptions: lang_map("-", None),
+                aliases: LangMapList::new(),
+                birth_country: None,
+                birth_date: None,
+                death_date: None,
+            },
+        ],
+        companies: vec![
+            CompanyBackup {
+                id: 1,
+                names: lang_map("Type-Moon", None),
+                descriptions: lang_map("-", None),
+                country: Some(Country::Japan),
+            },
+        ],
+        characters: vec![
+            CharacterBackup {
+                id: 1,
+                names: lang_map("Saber", None),
+                descriptions: lang_map("-", None),
+                aliases: LangMapList::new(),
+                related_characters: vec![(2, lang_map("Master", None))],
+            },
+            CharacterBackup {
+                id: 2,
+                names: lang_map("Shirou Emiya", None),
+                descriptions: lang_map("-", None),
+                aliases: LangMapList::new(),
+                related_characters: Vec::new(),
+            },
+        ],
+        sources: vec![
+            SourceBackup {
+                id: 1,
+                names: lang_map("Fate/stay night", None),
+                descriptions: lang_map("The visual novel", None),
+                source_type: SourceType::Novel,
+                related_links: vec![
+                    ("https://ja.wikipedia.org/wiki/Fate/stay_night".to_string(),
+                        lang_map("Wikipedia", None)),
+                ],
+                dates: vec![(NaiveDate::from_ymd_opt(2004, 1, 30).unwrap(), lang_map("Release", None))],
+                universe_tags: vec![3],
+                related_sources: vec![(2, Relation::Alternate)],
+                people: vec![(1, PersonRole::Writer)],
+                characters: vec![1, 2],
+                companies: vec![(1, Some(CompanyRole::Developer)), (1, None)],
+            },
+            SourceBackup {
+                id: 2,
+                names: lang_map("Fate/stay night (2006)", None),
+                descriptions: lang_map("The first anime", None),
+                source_type: SourceType::TVShow,
+                related_links: Vec::new(),
+                dates: Vec::new(),
+                universe_tags: vec![3],
+                related_sources: Vec::new(),
+                people: vec![
+                    (2, PersonRole::VoiceActor(1, Lang::Japanese)),
+                    (2, PersonRole::VoiceActor(1, Lang::English)),
+                ],
+                characters: vec![1],
+                companies: Vec::new(),
+            },
+        ],
+    }
+}
+
+fn to_json(backup: &SourcesBackup) -> String {
+    serde_json::to_string_pretty(backup).unwrap()
+}
+
+fn restored_db(backup: SourcesBackup) -> ConnectionHolder {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    backup::import(&db, backup, ImportMode::Restore).unwrap();
+    db
+}
+
+#[test]
+fn restore_then_export_is_identical() {
+    let db = restored_db(make_backup());
+    assert_eq!(to_json(&backup::export(&db).unwrap()), to_json(&make_backup()));
+}
+
+#[test]
+fn export_then_restore_is_identical() {
+    let first_export = backup::export(&restored_db(make_backup())).unwrap();
+    let first_json = to_json(&first_export);
+    let second_export = backup::export(&restored_db(first_export)).unwrap();
+    assert_eq!(to_json(&second_export), first_json);
+}
+
+#[test]
+fn restore_needs_an_empty_db() {
+    let db = restored_db(make_backup());
+    assert!(matches!(backup::import(&db, make_backup(), ImportMode::Restore),
+        Err(Error::Conflict(_))));
+}
+
+#[test]
+fn merge_gives_new_ids() {
+    let db = restored_db(make_backup());
+    let id_maps = backup::import(&db, make_backup(), ImportMode::Merge).unwrap();
+    assert_eq!(id_maps.universe_tags.get(&1), Some(&4));
+    assert_eq!(id_maps.characters.get(&2), Some(&4));
+    assert_eq!(id_maps.sources.get(&2), Some(&4));
+
+    let export = backup::export(&db).unwrap();
+    assert_eq!(export.sources.len(), 4);
+    let merged_anime = &export.sources[3];
+    assert_eq!(merged_anime.universe_tags, vec![6]);
+    assert_eq!(merged_anime.characters, vec![3]);
+    match merged_anime.people[0] {
+        (4, PersonRole::VoiceActor(3, Lang::Japanese)) => {},
+        _ => panic!("The voice actor wasn't remapped"),
+    }
+}
+
+#[test]
+fn unknown_ids_are_rejected() {
+    let mut backup = make_backup();
+    backup.sources[0].universe_tags.push(42);
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    assert!(matches!(backup::import(&db, backup, ImportMode::Restore),
+        Err(Error::Validation(_))));
+    // Nothing from the failed import is left behind
+    assert!(backup::export(&db).unwrap().sources.is_empty());
+}
@@ -0,0 +1,231 @@
+//! Books from a CSV should match the Novels that are already there by their title and writer.
+
+use std::{
+    fs,
+    time::{Duration},
+};
+
+use chrono::{NaiveDate};
+use rusqlite::{Connection};
+
+use completion_tracker_lib::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    imports::{
+        ImportReport, ReportEntry,
+        csv::{self, CsvColumnMapping, CsvPreset},
+    },
+    simple_enum::{SimpleEnum},
+    sources::{
+        backup::{self, BACKUP_VERSION, ImportMode, PersonBackup, SourceBackup, SourcesBackup},
+        source_types::{Lang, LangMap, LangMapList, PersonRole, SourceType},
+    },
+    tracking::{
+        self,
+        entry::{self, TrackerEntry},
+    },
+};
+
+const GOODREADS_CSV: &str = include_str!("fixtures/goodreads_library_export.csv");
+
+fn lang_map(english: &str) -> LangMap {
+    let mut lang_map = LangMap::new();
+    lang_map.insert(Lang::English, english.to_string());
+    lang_map
+}
+
+fn person(id: u64, name: &str) -> PersonBackup {
+    PersonBackup {
+        id,
+        names: lang_map(name),
+        descriptions: LangMap::new(),
+        aliases: LangMapList::new(),
+        birth_country: None,
+        birth_date: None,
+        death_date: None,
+    }
+}
+
+fn source(id: u64, source_type: SourceType, people: Vec<(u64, PersonRole)>) -> SourceBackup {
+    SourceBackup {
+        id,
+        names: lang_map("Spice and Wolf, Vol. 1"),
+        descriptions: LangMap::new(),
+        source_type,
+        related_links: Vec::new(),
+        dates: Vec::new(),
+        universe_tags: Vec::new(),
+        related_sources: Vec::new(),
+        people,
+        characters: Vec::new(),
+        companies: Vec::new(),
+    }
+}
+
+/// The first volume of Spice and Wolf as a Comic (Source 1) and as a Novel (Source 2),
+/// both written by Isuna Hasekura (Person 1) with Jyuu Ayakura (Person 2) as the illustrator.
+/// The tracker DB is empty.
+fn make_dbs() -> (ConnectionHolder, ConnectionHolder) {
+    let sources_db = completion_tracker_lib::init_source_db().unwrap();
+    backup::import(&sources_db, SourcesBackup {
+        version: BACKUP_VERSION,
+        universe_tags: Vec::new(),
+        people: vec![person(1, "Isuna Hasekura"), person(2, "Jyuu Ayakura")],
+        companies: Vec::new(),
+        characters: Vec::new(),
+        sources: vec![
+            source(1, SourceType::Comic, vec![(1, PersonRole::Writer)]),
+            source(2, SourceType::Novel,
+                vec![(1, PersonRole::Writer), (2, PersonRole::Illustrator)]),
+        ],
+    }, ImportMode::Restore).unwrap();
+
+    let connection = Connection::open_in_memory().unwrap();
+    tracking::create_tables(&connection).unwrap();
+    (sources_db, ConnectionHolder::new(connection))
+}
+
+/// The (title, source ID) of each entry
+fn titles_and_ids(entries: &[ReportEntry]) -> Vec<(&str, u64)> {
+    entries.iter().map(|entry| (entry.title.as_str(), entry.source_id)).collect()
+}
+
+/// The (title, reason) of each skipped entry
+fn skipped(report: &ImportReport) -> Vec<(&str, &str)> {
+    report.skipped.iter().map(|entry| (entry.title.as_str(), entry.reason.as_str())).collect()
+}
+
+/// The (status, date) of every status that the entry has reached
+fn statuses(entry: &TrackerEntry) -> Vec<(&'static str, Option<NaiveDate>)> {
+    entry.status_updates.iter()
+        .map(|(status, status_time)| (status.as_str(), status_time.date))
+        .collect()
+}
+
+fn date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+#[test]
+fn goodreads_export() {
+    let (sources_db, tracker_db) = make_dbs();
+    let report = csv::import(&sources_db, &tracker_db, GOODREADS_CSV,
+        &CsvPreset::Goodreads.mapping()).unwrap();
+
+    // The series is taken off the end of the titles, and only a Novel can match
+    assert_eq!(titles_and_ids(&report.matched), vec![
+        ("Spice and Wolf, Vol. 1", 2),
+        // From one of the additional authors, since the main author doesn't match
+        ("Spice and Wolf, Vol. 2", 3),
+    ]);
+    assert_eq!(titles_and_ids(&report.created), vec![
+        ("Spice and Wolf, Vol. 2", 3),
+        // The illustrator of the Novel isn't one of its writers
+        ("Spice and Wolf, Vol. 1", 4),
+    ]);
+    assert_eq!(skipped(&report), vec![
+        ("Kara no Kyoukai: The Garden of Sinners", "The status to-read isn't mapped"),
+        ("", "There isn't a title"),
+    ]);
+
+    let entries = entry::read_all(&tracker_db).unwrap();
+    assert_eq!(entries.iter().map(|entry| entry.source_id).collect::<Vec<u64>>(),
+        vec![2, 3, 4]);
+    assert_eq!(statuses(&entries[0]), vec![("Complete", date(2012, 5, 3))]);
+    assert_eq!(entries[0].times_repeated, 1);
+    assert_eq!(entries[0].description, "Read it on the train,\nthen again \"later\"");
+    assert_eq!(statuses(&entries[1]), vec![
+        ("InProgress", None),
+        ("Complete", date(2013, 6, 30)),
+    ]);
+    assert_eq!(entries[2].times_repeated, 0);
+
+    // The new books are Novels with every author as a writer, reusing the People that are there
+    let backup = backup::export(&sources_db).unwrap();
+    assert_eq!(backup.people.len(), 2);
+    let writers = |source: &SourceBackup| -> Vec<(u64, &'static str)> {
+        source.people.iter().map(|(id, role)| (*id, role.db_name())).collect()
+    };
+    assert_eq!(backup.sources[2].source_type.as_str(), "Novel");
+    assert_eq!(writers(&backup.sources[2]), vec![(1, "Writer")]);
+    assert_eq!(writers(&backup.sources[3]), vec![(2, "Writer")]);
+}
+
+#[test]
+fn importing_again_matches_everything() {
+    let (sources_db, tracker_db) = make_dbs();
+    let mapping = CsvPreset::Goodreads.mapping();
+    csv::import(&sources_db, &tracker_db, GOODREADS_CSV, &mapping).unwrap();
+    let report = csv::import(&sources_db, &tracker_db, GOODREADS_CSV, &mapping).unwrap();
+    assert_eq!(report.matched.len(), 4);
+    assert!(report.created.is_empty());
+}
+
+#[test]
+fn failed_tracker_commit_leaves_only_the_new_books() {
+    let (sources_db, _) = make_dbs();
+    let file = std::env::temp_dir()
+        .join(format!("completion_tracker_csv_{}.db", std::process::id()));
+    let _ = fs::remove_file(&file);
+    let connection = Connection::open(&file).unwrap();
+    tracking::create_tables(&connection).unwrap();
+    connection.busy_timeout(Duration::from_millis(0)).unwrap();
+    let tracker_db = ConnectionHolder::new(connection);
+
+    // Another reader stops the tracker from being committed
+    let reader = Connection::open(&file).unwrap();
+    reader.execute_batch("BEGIN; SELECT COUNT(*) FROM sqlite_master;").unwrap();
+    let mapping = CsvPreset::Goodreads.mapping();
+    assert!(csv::import(&sources_db, &tracker_db, GOODREADS_CSV, &mapping).is_err());
+    assert_eq!(backup::export(&sources_db).unwrap().sources.len(), 4);
+    assert!(entry::read_all(&tracker_db).unwrap().is_empty());
+    drop(reader);
+
+    let report = csv::import(&sources_db, &tracker_db, GOODREADS_CSV, &mapping).unwrap();
+    assert!(report.created.is_empty());
+    assert_eq!(entry::read_all(&tracker_db).unwrap().len(), 3);
+    drop(tracker_db);
+    fs::remove_file(&file).unwrap();
+}
+
+fn custom_mapping() -> CsvColumnMapping {
+    serde_json::from_value(serde_json::json!({
+        "title": "Name",
+        "author": "Writer",
+        "start_date": "Started",
+        "finish_date": "Finished",
+    })).unwrap()
+}
+
+#[test]
+fn custom_mapping_without_a_status() {
+    let (sources_db, tracker_db) = make_dbs();
+    let csv = "\u{feff}Name,Writer,Started,Finished\r\n\
+        \"Spice and Wolf, Vol. 1 (Spice and Wolf, #1)\",isuna hasekura,2010-01-02,2010/01/05\r\n\
+        狼と香辛料,支倉凍砂,,\r\n";
+    let report = csv::import(&sources_db, &tracker_db, csv, &custom_mapping()).unwrap();
+
+    // The suffix is only taken off when the mapping asks for it, and the case is ignored
+    assert_eq!(titles_and_ids(&report.created), vec![
+        ("Spice and Wolf, Vol. 1 (Spice and Wolf, #1)", 3),
+        ("狼と香辛料", 4),
+    ]);
+    let entries = entry::read_all(&tracker_db).unwrap();
+    assert_eq!(statuses(&entries[0]), vec![
+        ("InProgress", date(2010, 1, 2)),
+        ("Complete", date(2010, 1, 5)),
+    ]);
+    assert_eq!(statuses(&entries[1]), vec![("Complete", None)]);
+}
+
+#[test]
+fn bad_files_are_rejected() {
+    let (sources_db, tracker_db) = make_dbs();
+    let mapping = custom_mapping();
+    assert!(matches!(csv::import(&sources_db, &tracker_db, "", &mapping),
+        Err(Error::Validation(_))));
+    assert!(matches!(csv::import(&sources_db, &tracker_db, "Name,Writer\n", &mapping),
+        Err(Error::Validation(message)) if message.contains("Started")));
+    assert!(matches!(csv::import(&sources_db, &tracker_db, GOODREADS_CSV, &mapping),
+        Err(Error::Validation(_))));
+}
@@ -0,0 +1,315 @@
+//! Each export has to use the vocabulary of the site or program that will read it.
+
+use std::io::{Read};
+
+use chrono::{NaiveDate};
+use rusqlite::{Connection};
+
+use completion_tracker_lib::{
+    db_link::{ConnectionHolder},
+    exports::{
+        MediaType, anilist, atom, my_anime_list,
+        csv::{self, CsvExportOptions},
+    },
+    imports,
+    sources::{
+        api::source::{self, SourceCreator},
+        source_types::{Lang, LangMap, SourceType},
+    },
+    tracking::{
+        self, CompletionStatus,
+        entry::{self, StatusUpdate},
+    },
+};
+
+/// (English name, source type, links, statuses) for each Source, in ID order
+const SOURCES: &[(&str, SourceType, &[&str], &[CompletionStatus])] = &[
+    ("Spice and Wolf", SourceType::TVShow, &[
+        "https://myanimelist.net/anime/2966/Ookami_to_Koushinryou",
+        "https://anilist.co/anime/2966/",
+    ], &[CompletionStatus::InProgress, CompletionStatus::Complete]),
+    ("Kara no Kyoukai", SourceType::Movie, &[], &[CompletionStatus::InProgress]),
+    ("Berserk", SourceType::Comic, &["https://myanimelist.net/manga/2/Berserk"],
+        &[CompletionStatus::InProgress]),
+    ("Mushoku Tensei", SourceType::WebNovel, &[], &[CompletionStatus::Complete]),
+    ("Fate/Zero", SourceType::Novel, &[], &[]),
+];
+
+fn date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// The sources and a tracker with the statuses from SOURCES.
+/// Every status is reached in 2008, with a month for each Source.
+fn make_dbs() -> (ConnectionHolder, ConnectionHolder) {
+    let sources_db = completion_tracker_lib::init_source_db().unwrap();
+    let connection = Connection::open_in_memory().unwrap();
+    tracking::create_tables(&connection).unwrap();
+    let tracker_db = ConnectionHolder::new(connection);
+
+    for (i, (name, source_type, links, statuses)) in SOURCES.iter().enumerate() {
+        let mut names = LangMap::new();
+        names.insert(Lang::English, name.to_string());
+        let source_id = source::create(&sources_db, SourceCreator {
+            names,
+            descriptions: LangMap::new(),
+            source_type: *source_type,
+            related_links: links.iter().map(|url| (url.to_string(), LangMap::new())).collect(),
+            dates: Vec::new(),
+            universe_tags: Vec::new(),
+            related_sources: Vec::new(),
+            people: Vec::new(),
+            characters: Vec::new(),
+            companies: Vec::new(),
+        }).unwrap();
+        for (day, status) in statuses.iter().enumerate() {
+            entry::update_status(&tracker_db, StatusUpdate {
+                source_id,
+                status: *status,
+                date: date(2008, i as u32 + 1, day as u32 + 1),
+                play_time: None,
+            }).unwrap();
+        }
+    }
+    (sources_db, tracker_db)
+}
+
+/// The (status, [title]) of each list
+fn anilist_lists(collection: &anilist::MediaListCollection) -> Vec<(&str, Vec<&str>)> {
+    collection.lists.iter()
+        .map(|list| (list.status, list.entries.iter()
+            .map(|media_list| media_list.media.title.user_preferred.as_str())
+            .collect()))
+        .collect()
+}
+
+#[test]
+fn anilist_statuses_and_ids() {
+    let (sources_db, tracker_db) = make_dbs();
+
+    let anime = anilist::export(&sources_db, &tracker_db, MediaType::Anime).unwrap();
+    assert_eq!(anilist_lists(&anime), vec![
+        ("CURRENT", vec!["Kara no Kyoukai"]),
+        ("COMPLETED", vec!["Spice and Wolf"]),
+    ]);
+    assert_eq!(anime.lists[0].name, "Watching");
+    let spice_and_wolf = &anime.lists[1].entries[0];
+    assert_eq!(spice_and_wolf.status, "COMPLETED");
+    assert_eq!(spice_and_wolf.media_id, Some(2966));
+    assert_eq!(spice_and_wolf.media.id_mal, Some(2966));
+    assert_eq!(spice_and_wolf.media.media_type, "ANIME");
+    assert_eq!(spice_and_wolf.media.format, "TV");
+    assert_eq!(spice_and_wolf.started_at.month, Some(1));
+    assert_eq!(spice_and_wolf.completed_at.day, Some(2));
+    let kara_no_kyoukai = &anime.lists[0].entries[0];
+    assert_eq!(kara_no_kyoukai.status, "CURRENT");
+    assert_eq!(kara_no_kyoukai.media.format, "MOVIE");
+    assert_eq!(kara_no_kyoukai.media_id, None);
+    assert_eq!(kara_no_kyoukai.media.id_mal, None);
+    assert_eq!(kara_no_kyoukai.completed_at.year, None);
+
+    let manga = anilist::export(&sources_db, &tracker_db, MediaType::Manga).unwrap();
+    assert_eq!(anilist_lists(&manga), vec![
+        ("CURRENT", vec!["Berserk"]),
+        ("COMPLETED", vec!["Mushoku Tensei"]),
+    ]);
+    assert_eq!(manga.lists[0].name, "Reading");
+    let berserk = &manga.lists[0].entries[0];
+    assert_eq!(berserk.media.media_type, "MANGA");
+    assert_eq!(berserk.media.format, "MANGA");
+    assert_eq!(berserk.media_id, None);
+    assert_eq!(berserk.media.id_mal, Some(2));
+    assert_eq!(manga.lists[1].entries[0].media.format, "NOVEL");
+}
+
+/// Every <anime> or <manga> element in the XML
+fn mal_entries<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    xml.split(&format!("<{}>", tag)).skip(1).collect()
+}
+
+#[test]
+fn my_anime_list_statuses_and_ids() {
+    let (sources_db, tracker_db) = make_dbs();
+
+    let anime = my_anime_list::export(&sources_db, &tracker_db, MediaType::Anime).unwrap();
+    assert!(anime.contains("<user_export_type>1</user_export_type>"));
+    let entries = mal_entries(&anime, "anime");
+    assert_eq!(entries.len(), 2);
+    assert!(entries[0].contains("<series_animedb_id>2966</series_animedb_id>"));
+    assert!(entries[0].contains("<series_title><![CDATA[Spice and Wolf]]></series_title>"));
+    assert!(entries[0].contains("<series_type>TV</series_type>"));
+    assert!(entries[0].contains("<my_status>Completed</my_status>"));
+    assert!(entries[0].contains("<my_start_date>2008-01-01</my_start_date>"));
+    assert!(entries[0].contains("<my_finish_date>2008-01-02</my_finish_date>"));
+    // MAL uses 0 for a missing ID and zeros for a missing day
+    assert!(entries[1].contains("<series_animedb_id>0</series_animedb_id>"));
+    assert!(entries[1].contains("<series_type>Movie</series_type>"));
+    assert!(entries[1].contains("<my_status>Watching</my_status>"));
+    assert!(entries[1].contains("<my_finish_date>0000-00-00</my_finish_date>"));
+
+    let manga = my_anime_list::export(&sources_db, &tracker_db, MediaType::Manga).unwrap();
+    assert!(manga.contains("<user_export_type>2</user_export_type>"));
+    let entries = mal_entries(&manga, "manga");
+    assert_eq!(entries.len(), 2);
+    assert!(entries[0].contains("<manga_mangadb_id>2</manga_mangadb_id>"));
+    assert!(entries[0].contains("<my_status>Reading</my_status>"));
+    assert!(entries[1].contains("<manga_mangadb_id>0</manga_mangadb_id>"));
+    assert!(entries[1].contains("<manga_title><![CDATA[Mushoku Tensei]]></manga_title>"));
+    assert!(entries[1].contains("<my_status>Completed</my_status>"));
+}
+
+#[test]
+fn my_anime_list_exports_import_again() {
+    let (sources_db, tracker_db) = make_dbs();
+    let anime = my_anime_list::export(&sources_db, &tracker_db, MediaType::Anime).unwrap();
+
+    let connection = Connection::open_in_memory().unwrap();
+    tracking::create_tables(&connection).unwrap();
+    let new_tracker_db = ConnectionHolder::new(connection);
+    let report = imports::my_anime_list::import(&sources_db, &new_tracker_db, &anime).unwrap();
+    let matched: Vec<u64> = report.matched.iter().map(|entry| entry.source_id).collect();
+    assert_eq!(matched, vec![1, 2]);
+    assert!(report.created.is_empty() && report.skipped.is_empty());
+
+    let entries = entry::read_all(&new_tracker_db).unwrap();
+    assert_eq!(entries[0].current_status().map(|status| status.is_finished()), Some(true));
+    assert_eq!(entries[0].status_updates[&CompletionStatus::Complete].date, date(2008, 1, 2));
+    assert_eq!(entries[1].current_status().map(|status| status.is_finished()), Some(false));
+}
+
+#[test]
+fn links_that_are_not_valid_anymore_are_left_out() {
+    let (sources_db, tracker_db) = make_dbs();
+    // Like a link to a site that was taken out of the link types config file
+    sources_db.lock().execute_batch("
+        INSERT INTO Strings (english) VALUES ('Gone');
+        INSERT INTO SourceRelatedLinks (source_id, url, link_type, external_id, descriptions)
+            VALUES (1, 'https://example.com/anime/1', 'Example', '1', last_insert_rowid());
+    ").unwrap();
+
+    let anime = anilist::export(&sources_db, &tracker_db, MediaType::Anime).unwrap();
+    assert_eq!(anime.lists[1].entries[0].media_id, Some(2966));
+    let anime = my_anime_list::export(&sources_db, &tracker_db, MediaType::Anime).unwrap();
+    assert!(anime.contains("<series_animedb_id>2966</series_animedb_id>"));
+    read_csv(&sources_db, &tracker_db, Lang::English, false);
+    atom::export(&sources_db, &tracker_db, "Tracker", Lang::English).unwrap();
+}
+
+/// The whole CSV, read a few bytes at a time
+fn read_csv(sources_db: &ConnectionHolder, tracker_db: &ConnectionHolder, lang: Lang, bom: bool)
+-> String {
+    let mut csv_export = csv::export(sources_db, tracker_db, &CsvExportOptions { lang, bom })
+        .unwrap();
+    let mut csv = Vec::new();
+    let mut buffer = [0; 7];
+    loop {
+        match csv_export.read(&mut buffer).unwrap() {
+            0 => break,
+            length => csv.extend_from_slice(&buffer[..length]),
+        }
+    }
+    String::from_utf8(csv).unwrap()
+}
+
+#[test]
+fn csv_rows() {
+    let (sources_db, tracker_db) = make_dbs();
+    let mut names = LangMap::new();
+    names.insert(Lang::English, "Kara no Kyoukai, Part 2".to_string());
+    names.insert(Lang::Japanese, "空の境界 第二章".to_string());
+    let source_id = source::create(&sources_db, SourceCreator {
+        names,
+        descriptions: LangMap::new(),
+        source_type: SourceType::Movie,
+        related_links: Vec::new(),
+        dates: Vec::new(),
+        universe_tags: Vec::new(),
+        related_sources: Vec::new(),
+        people: Vec::new(),
+        characters: Vec::new(),
+        companies: Vec::new(),
+    }).unwrap();
+    entry::update_status(&tracker_db, StatusUpdate {
+        source_id,
+        status: CompletionStatus::InProgress,
+        date: None,
+        play_time: Some(3661),
+    }).unwrap();
+
+    // One row for each status, by the date, with the ones without a date at the end
+    let csv = read_csv(&sources_db, &tracker_db, Lang::English, false);
+    assert_eq!(csv.split("\r\n").collect::<Vec<&str>>(), vec![
+        "Source,Source Type,Universe Tags,Status,Date,Play Time,Notes",
+        "Spice and Wolf,TVShow,,InProgress,2008-01-01,,",
+        "Spice and Wolf,TVShow,,Complete,2008-01-02,,",
+        "Kara no Kyoukai,Movie,,InProgress,2008-02-01,,",
+        "Berserk,Comic,,InProgress,2008-03-01,,",
+        "Mushoku Tensei,WebNovel,,Complete,2008-04-01,,",
+        "\"Kara no Kyoukai, Part 2\",Movie,,InProgress,,1:01:01,",
+        "",
+    ]);
+
+    // Excel needs the BOM to know that it's UTF-8
+    let csv = read_csv(&sources_db, &tracker_db, Lang::Japanese, true);
+    assert!(csv.starts_with("\u{feff}Source,"));
+    assert!(csv.contains("\r\nSpice and Wolf,TVShow,"));
+    assert!(csv.ends_with("\r\n空の境界 第二章,Movie,,InProgress,,1:01:01,\r\n"));
+
+    let mut whole_csv = String::new();
+    csv::export(&sources_db, &tracker_db, &CsvExportOptions { lang: Lang::Japanese, bom: true })
+        .unwrap()
+        .read_to_string(&mut whole_csv)
+        .unwrap();
+    assert_eq!(whole_csv, csv);
+}
+
+/// The <id> of the feed and then of every entry, in order
+fn feed_ids(feed: &str) -> Vec<&str> {
+    feed.lines()
+        .filter_map(|line| line.trim().strip_prefix("<id>")?.strip_suffix("</id>"))
+        .collect()
+}
+
+#[test]
+fn feed_entries_keep_their_ids() {
+    let (sources_db, tracker_db) = make_dbs();
+    let feed = atom::export(&sources_db, &tracker_db, "Books & Anime", Lang::English).unwrap();
+    assert!(feed.contains("<title>Books &amp; Anime</title>"));
+
+    // The newest first, and the Source ID and status are enough to tell them apart
+    let prefix = "tag:completion-tracker,2019:tracker/Books+%26+Anime";
+    let ids = vec![
+        prefix.to_string(),
+        format!("{}/source/4/Complete", prefix),
+        format!("{}/source/3/InProgress", prefix),
+        format!("{}/source/2/InProgress", prefix),
+        format!("{}/source/1/Complete", prefix),
+        format!("{}/source/1/InProgress", prefix),
+    ];
+    assert_eq!(feed_ids(&feed), ids);
+
+    // Saving the same status again isn't a new event
+    entry::update_status(&tracker_db, StatusUpdate {
+        source_id: 1,
+        status: CompletionStatus::Complete,
+        date: date(2008, 1, 2),
+        play_time: None,
+    }).unwrap();
+    let same_feed = atom::export(&sources_db, &tracker_db, "Books & Anime", Lang::English)
+        .unwrap();
+    assert_eq!(same_feed, feed);
+
+    // A new status goes in front, without changing the others
+    entry::update_status(&tracker_db, StatusUpdate {
+        source_id: 5,
+        status: CompletionStatus::InProgress,
+        date: None,
+        play_time: None,
+    }).unwrap();
+    let new_feed = atom::export(&sources_db, &tracker_db, "Books & Anime", Lang::English)
+        .unwrap();
+    let new_ids = feed_ids(&new_feed);
+    assert_eq!(new_ids[1], format!("{}/source/5/InProgress", prefix));
+    assert_eq!(new_ids[0], ids[0]);
+    assert_eq!(new_ids[2..], ids[1..]);
+}
@@ -0,0 +1,131 @@
+//! Every subgraph has to show up in both formats, with its edges going the right way.
+
+use completion_tracker_lib::{
+    db_link::{ConnectionHolder},
+    exports::graph::{self, GraphFormat, GraphOptions, Subgraph},
+    sources::{
+        backup::{self, BACKUP_VERSION, ImportMode, SourceBackup, SourcesBackup, UniverseTagBackup},
+        source_types::{Lang, LangMap, Relation, SourceType},
+    },
+};
+
+fn names(english: &str) -> LangMap {
+    let mut names = LangMap::new();
+    names.insert(Lang::English, english.to_string());
+    names
+}
+
+fn make_universe_tag(id: u64, name: &str, children: Vec<u64>,
+related_universe_tags: Vec<(u64, Relation)>) -> UniverseTagBackup {
+    UniverseTagBackup {
+        id,
+        names: names(name),
+        descriptions: LangMap::new(),
+        related_links: Vec::new(),
+        children,
+        related_universe_tags,
+    }
+}
+
+fn make_source(id: u64, name: &str, source_type: SourceType,
+related_sources: Vec<(u64, Relation)>) -> SourceBackup {
+    SourceBackup {
+        id,
+        names: names(name),
+        descriptions: LangMap::new(),
+        source_type,
+        related_links: Vec::new(),
+        dates: Vec::new(),
+        universe_tags: Vec::new(),
+        related_sources,
+        people: Vec::new(),
+        characters: Vec::new(),
+        companies: Vec::new(),
+    }
+}
+
+/// Fate is the parent of Fate/Zero, Tsukihime is an alternate to Fate,
+/// and the Fate/Zero anime is after the novel.
+/// The DB keeps that last one as the novel being before the anime.
+fn make_db() -> ConnectionHolder {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    backup::import(&db, SourcesBackup {
+        version: BACKUP_VERSION,
+        universe_tags: vec![
+            make_universe_tag(1, "Fate", vec![2], Vec::new()),
+            make_universe_tag(2, "Fate/Zero", Vec::new(), Vec::new()),
+            make_universe_tag(3, "Tsukihime \"Type-Moon\" & Co", Vec::new(),
+                vec![(1, Relation::Alternate)]),
+        ],
+        people: Vec::new(),
+        companies: Vec::new(),
+        characters: Vec::new(),
+        sources: vec![
+            make_source(1, "Fate/Zero", SourceType::Novel, Vec::new()),
+            make_source(2, "Fate/Zero", SourceType::TVShow, vec![(1, Relation::After)]),
+        ],
+    }, ImportMode::Restore).unwrap();
+    db
+}
+
+fn export(db: &ConnectionHolder, format: GraphFormat, subgraphs: Vec<Subgraph>) -> String {
+    graph::export(db, &GraphOptions {
+        subgraphs,
+        lang: Lang::English,
+        format,
+    }).unwrap()
+}
+
+#[test]
+fn dot_has_every_subgraph() {
+    let db = make_db();
+    let dot = export(&db, GraphFormat::Dot, Vec::new());
+    assert!(dot.starts_with("digraph CompletionTracker {\n"));
+    assert!(dot.contains(
+        "    universeTag3 [label=\"Tsukihime \\\"Type-Moon\\\" & Co\", kind=\"UniverseTag\"];\n"));
+    assert!(dot.contains("    source2 [label=\"Fate/Zero\", kind=\"Source\"];\n"));
+
+    assert!(dot.contains("    universeTag1 -> universeTag2 [label=\"Child\", \
+        subgraph=\"UniverseTagHierarchy\"];\n"));
+    // Alternates don't have an arrow
+    assert!(dot.contains("    universeTag3 -> universeTag1 [label=\"Alternate\", \
+        subgraph=\"UniverseTagRelations\", dir=none];\n"));
+    assert!(dot.contains(
+        "    source1 -> source2 [label=\"Before\", subgraph=\"SourceRelations\"];\n"));
+}
+
+#[test]
+fn graphml_has_every_subgraph() {
+    let db = make_db();
+    let graphml = export(&db, GraphFormat::GraphML, Vec::new());
+    assert!(graphml.contains("      <data key=\"label\">\
+        Tsukihime &quot;Type-Moon&quot; &amp; Co</data>\n"));
+
+    let edges: Vec<&str> = graphml.split("    <edge ").skip(1).collect();
+    assert_eq!(edges.len(), 3);
+    assert!(edges[0].starts_with(
+        "id=\"e0\" source=\"universeTag1\" target=\"universeTag2\" directed=\"true\">\n"));
+    assert!(edges[0].contains("<data key=\"subgraph\">UniverseTagHierarchy</data>"));
+    assert!(edges[1].starts_with(
+        "id=\"e1\" source=\"universeTag3\" target=\"universeTag1\" directed=\"false\">\n"));
+    assert!(edges[1].contains("<data key=\"label\">Alternate</data>"));
+    assert!(edges[1].contains("<data key=\"subgraph\">UniverseTagRelations</data>"));
+    assert!(edges[2].starts_with(
+        "id=\"e2\" source=\"source1\" target=\"source2\" directed=\"true\">\n"));
+    assert!(edges[2].contains("<data key=\"label\">Before</data>"));
+    assert!(edges[2].contains("<data key=\"subgraph\">SourceRelations</data>"));
+}
+
+#[test]
+fn subgraphs_can_be_left_out() {
+    let db = make_db();
+    let dot = export(&db, GraphFormat::Dot, vec![Subgraph::SourceRelations]);
+    assert!(!dot.contains("universeTag"));
+    assert!(dot.contains("source1 -> source2"));
+
+    // The Universe Tags are still there without their hierarchy
+    let dot = export(&db, GraphFormat::Dot, vec![Subgraph::UniverseTagRelations]);
+    assert!(dot.contains("universeTag3 -> universeTag1"));
+    assert!(!dot.contains("universeTag1 -> universeTag2"));
+    assert!(!dot.contains("source"));
+}
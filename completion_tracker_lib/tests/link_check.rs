@@ -0,0 +1,211 @@
+//! The link checker should record what happened to every link, using a local server
+//! in place of the real sites.
+
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration},
+};
+
+use rouille::{Response};
+
+use completion_tracker_lib::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    link_check::{self, HttpClient, HttpResponse, LinkCheckOptions, UreqClient},
+    simple_enum::{SimpleEnum},
+    sources::{
+        backup::{self, BACKUP_VERSION, ImportMode, SourceBackup, SourcesBackup, UniverseTagBackup},
+        source_types::{Lang, LangMap, SourceType},
+    },
+};
+
+/// Starts a server on any free port that answers for the MAL anime pages:
+/// 1 is fine, 2 is gone, 3 redirects to 1, 4 doesn't allow HEAD, and 5 takes too long
+fn start_mock_server() -> String {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let server = rouille::Server::new("127.0.0.1:0", |req| {
+            match req.url().as_str() {
+                "/anime/1" => Response::text("ok"),
+                "/anime/3" => Response::redirect_301("/anime/1"),
+                "/anime/4" if req.method() == "HEAD" => Response::empty_400().with_status_code(405),
+                "/anime/4" => Response::text("ok"),
+                "/anime/5" => {
+                    thread::sleep(Duration::from_secs(2));
+                    Response::text("too late")
+                },
+                _ => Response::empty_404(),
+            }
+        }).unwrap();
+        sender.send(server.server_addr().to_string()).unwrap();
+        loop {
+            server.poll();
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+    receiver.recv().unwrap()
+}
+
+/// Sends every request to the mock server, keeping only the path
+struct LocalClient {
+    address: String,
+    client: UreqClient,
+}
+impl HttpClient for LocalClient {
+    fn request(&self, method: &str, url: &str, timeout: Duration) -> Result<HttpResponse, String> {
+        let url = url::Url::parse(url).map_err(|e| e.to_string())?;
+        let local_url = format!("http://{}{}", self.address, url.path());
+        self.client.request(method, &local_url, timeout)
+    }
+}
+
+fn lang_map(english: &str) -> LangMap {
+    let mut map = LangMap::new();
+    map.insert(Lang::English, english.to_string());
+    map
+}
+
+fn link(id: u32) -> (String, LangMap) {
+    (format!("https://myanimelist.net/anime/{}", id), LangMap::new())
+}
+
+fn make_db() -> ConnectionHolder {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    backup::import(&db, SourcesBackup {
+        version: BACKUP_VERSION,
+        universe_tags: vec![
+            UniverseTagBackup {
+                id: 1,
+                names: lang_map("Tag"),
+                descriptions: LangMap::new(),
+                related_links: vec![link(1), link(2)],
+                children: Vec::new(),
+                related_universe_tags: Vec::new(),
+            },
+        ],
+        people: Vec::new(),
+        companies: Vec::new(),
+        characters: Vec::new(),
+        sources: vec![
+            SourceBackup {
+                id: 1,
+                names: lang_map("Fine Source"),
+                descriptions: LangMap::new(),
+                source_type: SourceType::TVShow,
+                related_links: vec![link(1), link(3), link(4)],
+                dates: Vec::new(),
+                universe_tags: Vec::new(),
+                related_sources: Vec::new(),
+                people: Vec::new(),
+                characters: Vec::new(),
+                companies: Vec::new(),
+            },
+            SourceBackup {
+                id: 2,
+                names: lang_map("Slow Source"),
+                descriptions: LangMap::new(),
+                source_type: SourceType::TVShow,
+                related_links: vec![link(5), link(2)],
+                dates: Vec::new(),
+                universe_tags: Vec::new(),
+                related_sources: Vec::new(),
+                people: Vec::new(),
+                characters: Vec::new(),
+                companies: Vec::new(),
+            },
+        ],
+    }, ImportMode::Restore).unwrap();
+    db
+}
+
+#[test]
+fn broken_links_by_item() {
+    let db = make_db();
+    let client = LocalClient {
+        address: start_mock_server(),
+        client: UreqClient::new(),
+    };
+    let options = LinkCheckOptions {
+        concurrency: 2,
+        timeout_millis: 500,
+        ..LinkCheckOptions::default()
+    };
+
+    let summary = link_check::check_links(&db, &client, &options).unwrap();
+    // The same URL is only checked once
+    assert_eq!(summary.checked, 5);
+    assert_eq!(summary.broken, 2);
+    assert_eq!(summary.redirected, 1);
+
+    let items = link_check::broken_links(&db).unwrap();
+    let found: Vec<(&str, u64, Vec<&str>)> = items.iter()
+        .map(|item| (item.item_type.as_str(), item.id,
+            item.links.iter().map(|link| link.url.as_str()).collect()))
+        .collect();
+    assert_eq!(found, vec![
+        ("UniverseTag", 1, vec!["https://myanimelist.net/anime/2"]),
+        ("Source", 2, vec!["https://myanimelist.net/anime/5", "https://myanimelist.net/anime/2"]),
+    ]);
+    assert_eq!(items[0].names.get(&Lang::English).map(String::as_str), Some("Tag"));
+    let not_found = &items[0].links[0];
+    assert_eq!(not_found.status, Some(404));
+    assert!(not_found.error.is_none());
+    let timed_out = &items[1].links[0];
+    assert_eq!(timed_out.status, None);
+    assert!(timed_out.error.is_some());
+}
+
+#[test]
+fn checking_again_replaces_the_results() {
+    let db = make_db();
+    let client = LocalClient {
+        address: start_mock_server(),
+        client: UreqClient::new(),
+    };
+    let options = LinkCheckOptions {
+        timeout_millis: 500,
+        ..LinkCheckOptions::default()
+    };
+    link_check::check_links(&db, &client, &options).unwrap();
+
+    // Everything breaks when the server goes away
+    let gone = LocalClient {
+        address: "127.0.0.1:1".to_string(),
+        client: UreqClient::new(),
+    };
+    let summary = link_check::check_links(&db, &gone, &options).unwrap();
+    assert_eq!(summary.broken, 5);
+    let items = link_check::broken_links(&db).unwrap();
+    assert_eq!(items.iter().map(|item| item.links.len()).sum::<usize>(), 7);
+}
+
+/// For the checks that should fail before anything is requested
+struct NoClient;
+impl HttpClient for NoClient {
+    fn request(&self, _: &str, url: &str, _: Duration) -> Result<HttpResponse, String> {
+        panic!("Nothing should have been requested, but {} was", url);
+    }
+}
+
+#[test]
+fn out_of_range_options_are_rejected() {
+    let db = make_db();
+    for (options, option_name) in [
+        (LinkCheckOptions { concurrency: 0, ..LinkCheckOptions::default() }, "concurrency"),
+        (LinkCheckOptions { concurrency: 100_000, ..LinkCheckOptions::default() }, "concurrency"),
+        (LinkCheckOptions { timeout_millis: 0, ..LinkCheckOptions::default() }, "timeout"),
+        (LinkCheckOptions { timeout_millis: 60_001, ..LinkCheckOptions::default() }, "timeout"),
+        (LinkCheckOptions { max_redirects: 11, ..LinkCheckOptions::default() }, "redirects"),
+    ] {
+        match link_check::check_links(&db, &NoClient, &options) {
+            Err(Error::Validation(message)) => {
+                assert!(message.contains(option_name), "{}", message)
+            },
+            Err(e) => panic!("Expected a validation error, but got {}", e),
+            Ok(_) => panic!("The {} should have been rejected", option_name),
+        }
+    }
+    // Nothing was checked, so nothing is broken yet
+    assert!(link_check::broken_links(&db).unwrap().is_empty());
+}
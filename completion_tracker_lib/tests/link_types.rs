@@ -0,0 +1,217 @@
+//! Links should be recognized by the built-in link types and the ones in a config file,
+//! and should always come out in the same canonical form.
+
+use std::fs;
+
+use url::{Url};
+
+use completion_tracker_lib::{
+    error::{Error},
+    sources::{
+        api::source::{self, SourceCreator},
+        source_types::{
+            Lang, LangMap, RelatedLink, SourceType,
+            link_types::{self, LinkTypeRegistry},
+        },
+    },
+};
+
+fn link_type_and_id(url: &str) -> (String, Option<String>) {
+    let link = RelatedLink::new(url, LangMap::new()).unwrap();
+    (link.link_type().as_str().to_string(), link.link_type().external_id(link.url()))
+}
+
+fn pair(link_type: &str, id: Option<&str>) -> (String, Option<String>) {
+    (link_type.to_string(), id.map(str::to_string))
+}
+
+#[test]
+fn built_in_sites() {
+    assert_eq!(link_type_and_id("https://myanimelist.net/anime/5341/Ookami_to_Koushinryou"),
+        pair(link_types::MY_ANIME_LIST, Some("5341")));
+    assert_eq!(link_type_and_id("https://anilist.co/manga/30115/"),
+        pair(link_types::ANI_LIST, Some("30115")));
+    assert_eq!(link_type_and_id("https://ncode.syosetu.com/n9669bk/"),
+        pair(link_types::SHOUSETSUKA_NAROU, Some("n9669bk")));
+    assert_eq!(link_type_and_id("https://mypage.syosetu.com/288399/"),
+        pair(link_types::SHOUSETSUKA_NAROU, None));
+    assert_eq!(link_type_and_id("https://en.wikipedia.org/wiki/Spice_and_Wolf"),
+        pair(link_types::WIKIPEDIA, Some("en:Spice_and_Wolf")));
+    // The IDs aren't percent-encoded, like the URLs are
+    let encoded = "https://ja.wikipedia.org/wiki/%E7%8B%BC%E3%81%A8%E9%A6%99%E8%BE%9B%E6%96%99";
+    assert_eq!(link_type_and_id(encoded), pair(link_types::WIKIPEDIA, Some("ja:狼と香辛料")));
+    assert_eq!(link_type_and_id("https://vndb.org/v17"), pair("VNDB", Some("v17")));
+    assert_eq!(link_type_and_id("https://kakuyomu.jp/works/1177354054880238351"),
+        pair("Kakuyomu", Some("1177354054880238351")));
+    assert_eq!(link_type_and_id("https://www.amazon.co.jp/狼と香辛料/dp/4840233020/"),
+        pair("Amazon", Some("4840233020")));
+    assert_eq!(link_type_and_id("https://store.steampowered.com/app/1113000/Persona_4_Golden/"),
+        pair("Steam", Some("1113000")));
+    assert_eq!(link_type_and_id("https://www.pixiv.net/novel/show.php?id=12345"),
+        pair("Pixiv", Some("12345")));
+    assert_eq!(link_type_and_id("https://www.kadokawa.co.jp/product/321"), pair("Kadokawa", None));
+    assert!(RelatedLink::new("https://example.com/", LangMap::new()).is_err());
+}
+
+fn canonical(url: &str) -> String {
+    RelatedLink::new(url, LangMap::new()).unwrap().url().as_str().to_string()
+}
+
+#[test]
+fn hosts_match_on_domains() {
+    assert!(RelatedLink::new("https://wikipedia.org.evil.com/wiki/A", LangMap::new()).is_err());
+    assert!(RelatedLink::new("https://notsyosetu.com.example/n9669bk/", LangMap::new()).is_err());
+    assert!(RelatedLink::new("https://notsyosetu.com/n9669bk/", LangMap::new()).is_err());
+    assert!(RelatedLink::new("https://syosetu.com/", LangMap::new()).is_ok());
+    assert!(RelatedLink::new("https://ja.wikipedia.org./wiki/A", LangMap::new()).is_ok());
+}
+
+#[test]
+fn canonical_urls() {
+    assert_eq!(canonical("http://en.m.wikipedia.org/wiki/Spice_and_Wolf"),
+        "https://en.wikipedia.org/wiki/Spice_and_Wolf");
+    assert_eq!(canonical("https://m.myanimelist.net/anime/5341"),
+        "https://myanimelist.net/anime/5341");
+    assert_eq!(canonical("https://myanimelist.net/anime/5341?utm_source=twitter&fbclid=abc"),
+        "https://myanimelist.net/anime/5341");
+    assert_eq!(canonical("https://www.pixiv.net/novel/show.php?id=12345&utm_medium=social"),
+        "https://pixiv.net/novel/show.php?id=12345");
+    assert_eq!(canonical("https://www.myanimelist.net/anime/5341"),
+        canonical("https://myanimelist.net/anime/5341"));
+    assert_eq!(canonical("https://myanimelist.net/anime/5341#reviews"),
+        "https://myanimelist.net/anime/5341");
+    assert_eq!(canonical("https://ncode.syosetu.com/n9669bk/"),
+        "https://ncode.syosetu.com/n9669bk");
+    assert_eq!(canonical("https://ncode.syosetu.com/n9669bk/"),
+        canonical("https://ncode.syosetu.com/n9669bk"));
+    assert_eq!(canonical("https://www.kadokawa.co.jp/"), "https://kadokawa.co.jp/");
+    assert!(RelatedLink::new("ftp://myanimelist.net/anime/5341", LangMap::new()).is_err());
+}
+
+#[test]
+fn duplicate_links_are_caught() {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    let mut names = LangMap::new();
+    names.insert(Lang::English, "Spice and Wolf".to_string());
+    let creator = SourceCreator {
+        names,
+        descriptions: LangMap::new(),
+        source_type: SourceType::Novel,
+        related_links: vec![
+            ("https://en.wikipedia.org/wiki/Spice_and_Wolf".to_string(), LangMap::new()),
+            ("http://en.m.wikipedia.org/wiki/Spice_and_Wolf?utm_source=x".to_string(),
+                LangMap::new()),
+        ],
+        dates: Vec::new(),
+        universe_tags: Vec::new(),
+        related_sources: Vec::new(),
+        people: Vec::new(),
+        characters: Vec::new(),
+        companies: Vec::new(),
+    };
+    assert!(matches!(source::create(&db, creator), Err(Error::Conflict(_))));
+}
+
+#[test]
+fn sources_found_by_external_id() {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    let mut names = LangMap::new();
+    names.insert(Lang::English, "Spice and Wolf".to_string());
+    let id = source::create(&db, SourceCreator {
+        names,
+        descriptions: LangMap::new(),
+        source_type: SourceType::Novel,
+        related_links: vec![
+            ("https://myanimelist.net/anime/5341/Ookami_to_Koushinryou".to_string(),
+                LangMap::new()),
+            ("https://ja.wikipedia.org/wiki/狼と香辛料".to_string(), LangMap::new()),
+        ],
+        dates: Vec::new(),
+        universe_tags: Vec::new(),
+        related_sources: Vec::new(),
+        people: Vec::new(),
+        characters: Vec::new(),
+        companies: Vec::new(),
+    }).unwrap();
+
+    assert_eq!(source::find_by_external_id(&db, link_types::MY_ANIME_LIST, "5341").unwrap(),
+        vec![id]);
+    assert!(source::find_by_external_id(&db, link_types::ANI_LIST, "5341").unwrap().is_empty());
+    assert_eq!(source::find_by_external_id(&db, link_types::WIKIPEDIA, "ja:狼と香辛料").unwrap(),
+        vec![id]);
+    // Any URL for the same item finds it
+    assert_eq!(source::find_by_link(&db, "https://m.myanimelist.net/anime/5341").unwrap(),
+        vec![id]);
+    assert_eq!(source::find_by_link(&db, "https://ja.m.wikipedia.org/wiki/狼と香辛料").unwrap(),
+        vec![id]);
+    assert!(source::find_by_link(&db, "https://myanimelist.net/anime/1").unwrap().is_empty());
+}
+
+#[test]
+fn sources_found_by_link_without_an_id() {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    let mut names = LangMap::new();
+    names.insert(Lang::English, "Spice and Wolf".to_string());
+    let id = source::create(&db, SourceCreator {
+        names,
+        descriptions: LangMap::new(),
+        source_type: SourceType::Novel,
+        related_links: vec![
+            ("https://www.kadokawa.co.jp/product/321/".to_string(), LangMap::new()),
+        ],
+        dates: Vec::new(),
+        universe_tags: Vec::new(),
+        related_sources: Vec::new(),
+        people: Vec::new(),
+        characters: Vec::new(),
+        companies: Vec::new(),
+    }).unwrap();
+
+    // Kadokawa doesn't have IDs, so it's the canonical URL that has to match
+    let urls = ["https://kadokawa.co.jp/product/321", "https://www.kadokawa.co.jp/product/321#top"];
+    for url in urls {
+        assert_eq!(source::find_by_link(&db, url).unwrap(), vec![id]);
+    }
+}
+
+#[test]
+fn config_file() {
+    let file = std::env::temp_dir().join("completion_tracker_link_types.json");
+    fs::write(&file, r#"[
+        {
+            "name": "Example",
+            "display_name": "Example Site",
+            "hosts": ["example.com"],
+            "id_patterns": ["/items/{id}"]
+        },
+        {
+            "name": "VNDB",
+            "display_name": "The Visual Novel Database",
+            "hosts": ["vndb.org"]
+        }
+    ]"#).unwrap();
+    let registry = LinkTypeRegistry::with_config_file(&file).unwrap();
+    fs::remove_file(&file).unwrap();
+
+    let example = registry.find_by_host("www.example.com").unwrap();
+    assert_eq!(example.display_name, "Example Site");
+    let url = Url::parse("https://www.example.com/items/42").unwrap();
+    assert_eq!(example.external_id(&url).as_deref(), Some("42"));
+
+    // The built-in VNDB got replaced, and the rest are still there
+    let vndb = registry.get("VNDB").unwrap();
+    assert_eq!(vndb.display_name, "The Visual Novel Database");
+    assert!(vndb.id_patterns.is_empty());
+    assert_eq!(registry.definitions().len(), LinkTypeRegistry::built_in().definitions().len() + 1);
+}
+
+#[test]
+fn bad_config_file() {
+    let file = std::env::temp_dir().join("completion_tracker_bad_link_types.json");
+    fs::write(&file, r#"[{ "name": "Example", "display_name": "Example", "hosts": [],
+        "id_patterns": [] }]"#).unwrap();
+    let error = LinkTypeRegistry::with_config_file(&file).err().unwrap();
+    fs::remove_file(&file).unwrap();
+    assert!(matches!(&error, Error::Validation(_)));
+    assert!(error.to_string().contains("Example"));
+}
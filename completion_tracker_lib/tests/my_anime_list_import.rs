@@ -0,0 +1,179 @@
+//! The MyAnimeList exports should be matched to the Sources that are already there,
+//! with a stub Source for everything else.
+
+use std::{
+    fs,
+    time::{Duration},
+};
+
+use chrono::{NaiveDate};
+use rusqlite::{Connection};
+
+use completion_tracker_lib::{
+    db_link::{ConnectionHolder},
+    imports::{ImportReport, ReportEntry, my_anime_list},
+    simple_enum::{SimpleEnum},
+    sources::{
+        api::source::{self, SourceCreator},
+        backup,
+        source_types::{Lang, LangMap, SourceType},
+    },
+    tracking::{
+        self, CompletionStatus,
+        entry::{self, TrackerEntry},
+    },
+};
+
+const ANIME_XML: &str = include_str!("fixtures/animelist.xml");
+const MANGA_XML: &str = include_str!("fixtures/mangalist.xml");
+
+/// A sources DB with the Spice and Wolf TV show (Source 1) and the Berserk comic (Source 2),
+/// and an empty tracker DB
+fn make_dbs() -> (ConnectionHolder, ConnectionHolder) {
+    let sources_db = completion_tracker_lib::init_source_db().unwrap();
+    for (name, source_type) in &[("Spice and Wolf", SourceType::TVShow),
+        ("Berserk", SourceType::Comic)] {
+        let mut names = LangMap::new();
+        names.insert(Lang::English, name.to_string());
+        source::create(&sources_db, SourceCreator {
+            names,
+            descriptions: LangMap::new(),
+            source_type: *source_type,
+            related_links: Vec::new(),
+            dates: Vec::new(),
+            universe_tags: Vec::new(),
+            related_sources: Vec::new(),
+            people: Vec::new(),
+            characters: Vec::new(),
+            companies: Vec::new(),
+        }).unwrap();
+    }
+
+    let connection = Connection::open_in_memory().unwrap();
+    tracking::create_tables(&connection).unwrap();
+    (sources_db, ConnectionHolder::new(connection))
+}
+
+/// The (title, source ID) of each entry
+fn titles_and_ids(entries: &[ReportEntry]) -> Vec<(&str, u64)> {
+    entries.iter().map(|entry| (entry.title.as_str(), entry.source_id)).collect()
+}
+
+/// The (title, reason) of each skipped entry
+fn skipped(report: &ImportReport) -> Vec<(&str, &str)> {
+    report.skipped.iter().map(|entry| (entry.title.as_str(), entry.reason.as_str())).collect()
+}
+
+/// The (status, date) of every status that the entry has reached
+fn statuses(entry: &TrackerEntry) -> Vec<(&'static str, Option<NaiveDate>)> {
+    entry.status_updates.iter()
+        .map(|(status, status_time)| (status.as_str(), status_time.date))
+        .collect()
+}
+
+fn date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// The source type of each Source in the sources DB, in ID order
+fn source_types(sources_db: &ConnectionHolder) -> Vec<&'static str> {
+    backup::export(sources_db).unwrap().sources.iter()
+        .map(|source| source.source_type.as_str())
+        .collect()
+}
+
+#[test]
+fn anime_list() {
+    let (sources_db, tracker_db) = make_dbs();
+    let report = my_anime_list::import(&sources_db, &tracker_db, ANIME_XML).unwrap();
+
+    assert_eq!(titles_and_ids(&report.matched), vec![("Spice and Wolf", 1)]);
+    assert_eq!(titles_and_ids(&report.created),
+        vec![("Fate/Zero", 3), ("Kara no Kyoukai 1: Fukan Fuukei", 4)]);
+    assert_eq!(skipped(&report), vec![
+        ("Spice and Wolf II", "It hasn't been started"),
+        ("Cowboy Bebop", "Unknown status Rewatching"),
+        ("", "There isn't a title"),
+    ]);
+    assert_eq!(source_types(&sources_db), vec!["TVShow", "Comic", "TVShow", "Movie"]);
+
+    let entries = entry::read_all(&tracker_db).unwrap();
+    assert_eq!(entries.iter().map(|entry| entry.source_id).collect::<Vec<u64>>(),
+        vec![1, 3, 4]);
+    assert_eq!(statuses(&entries[0]), vec![
+        ("InProgress", date(2008, 1, 8)),
+        ("Complete", date(2008, 3, 25)),
+    ]);
+    assert_eq!(entries[0].times_repeated, 1);
+    // Dropped and On-Hold were still started
+    assert_eq!(statuses(&entries[1]), vec![("InProgress", date(2011, 10, 2))]);
+    assert_eq!(statuses(&entries[2]), vec![("InProgress", None)]);
+    assert_eq!(entries[2].times_repeated, 0);
+}
+
+#[test]
+fn manga_list() {
+    let (sources_db, tracker_db) = make_dbs();
+    let report = my_anime_list::import(&sources_db, &tracker_db, MANGA_XML).unwrap();
+
+    assert_eq!(titles_and_ids(&report.matched), vec![("Berserk", 2)]);
+    // The TV show with the same name isn't a match for the comic
+    assert_eq!(titles_and_ids(&report.created), vec![("Spice and Wolf", 3)]);
+    assert_eq!(skipped(&report), vec![("Kaguya-sama: Love & War", "It hasn't been started")]);
+    assert_eq!(source_types(&sources_db), vec!["TVShow", "Comic", "Comic"]);
+
+    let entries = entry::read_all(&tracker_db).unwrap();
+    assert_eq!(entries.iter().map(|entry| entry.source_id).collect::<Vec<u64>>(), vec![2, 3]);
+    // The old exports use numbers for the statuses, and there isn't a start date to use
+    assert_eq!(statuses(&entries[0]), vec![("Complete", date(2019, 5, 1))]);
+    assert_eq!(entries[0].current_status().map(|status| status.as_str()),
+        Some(CompletionStatus::Complete.as_str()));
+    assert_eq!(entries[0].times_repeated, 2);
+    assert_eq!(statuses(&entries[1]), vec![("InProgress", date(2010, 4, 1))]);
+}
+
+#[test]
+fn importing_again_matches_the_stubs() {
+    let (sources_db, tracker_db) = make_dbs();
+    my_anime_list::import(&sources_db, &tracker_db, ANIME_XML).unwrap();
+    let report = my_anime_list::import(&sources_db, &tracker_db, ANIME_XML).unwrap();
+    assert_eq!(titles_and_ids(&report.matched),
+        vec![("Spice and Wolf", 1), ("Fate/Zero", 3), ("Kara no Kyoukai 1: Fukan Fuukei", 4)]);
+    assert!(report.created.is_empty());
+    assert_eq!(entry::read_all(&tracker_db).unwrap().len(), 3);
+}
+
+#[test]
+fn failed_tracker_commit_leaves_only_the_stubs() {
+    let (sources_db, _) = make_dbs();
+    let file = std::env::temp_dir()
+        .join(format!("completion_tracker_mal_{}.db", std::process::id()));
+    let _ = fs::remove_file(&file);
+    let connection = Connection::open(&file).unwrap();
+    tracking::create_tables(&connection).unwrap();
+    connection.busy_timeout(Duration::from_millis(0)).unwrap();
+    let tracker_db = ConnectionHolder::new(connection);
+
+    // Another reader stops the tracker from being committed
+    let reader = Connection::open(&file).unwrap();
+    reader.execute_batch("BEGIN; SELECT COUNT(*) FROM sqlite_master;").unwrap();
+    assert!(my_anime_list::import(&sources_db, &tracker_db, ANIME_XML).is_err());
+    assert_eq!(source_types(&sources_db), vec!["TVShow", "Comic", "TVShow", "Movie"]);
+    assert!(entry::read_all(&tracker_db).unwrap().is_empty());
+    drop(reader);
+
+    let report = my_anime_list::import(&sources_db, &tracker_db, ANIME_XML).unwrap();
+    assert!(report.created.is_empty());
+    assert_eq!(entry::read_all(&tracker_db).unwrap().len(), 3);
+    drop(tracker_db);
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn other_files_are_rejected() {
+    let (sources_db, tracker_db) = make_dbs();
+    assert!(my_anime_list::import(&sources_db, &tracker_db, "<html></html>").is_err());
+    let report = my_anime_list::import(&sources_db, &tracker_db, "<myanimelist></myanimelist>")
+        .unwrap();
+    assert!(report.matched.is_empty() && report.created.is_empty() && report.skipped.is_empty());
+}
@@ -0,0 +1,108 @@
+//! Novels from the Narou API and from saved tables of contents should fill out a SourceCreator.
+
+use chrono::{NaiveDate};
+
+use completion_tracker_lib::{
+    db_link::{ConnectionHolder},
+    error::{Error},
+    imports::narou,
+    simple_enum::{SimpleEnum},
+    sources::{
+        backup::{self, BACKUP_VERSION, ImportMode, PersonBackup, SourcesBackup},
+        source_types::{Lang, LangMap, LangMapList},
+    },
+};
+
+const API_JSON: &str = include_str!("fixtures/narou_api.json");
+const TOC_HTML: &str = include_str!("fixtures/narou_toc.html");
+
+const URL: &str = "https://ncode.syosetu.com/n9669bk/";
+
+/// A DB with 理不尽な孫の手 (Person 1) in it
+fn make_db() -> ConnectionHolder {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    let mut names = LangMap::new();
+    names.insert(Lang::Japanese, "理不尽な孫の手".to_string());
+    backup::import(&db, SourcesBackup {
+        version: BACKUP_VERSION,
+        universe_tags: Vec::new(),
+        people: vec![
+            PersonBackup {
+                id: 1,
+                names,
+                descriptions: LangMap::new(),
+                aliases: LangMapList::new(),
+                birth_country: None,
+                birth_date: None,
+                death_date: None,
+            },
+        ],
+        companies: Vec::new(),
+        characters: Vec::new(),
+        sources: Vec::new(),
+    }, ImportMode::Restore).unwrap();
+    db
+}
+
+#[test]
+fn ncode_from_urls() {
+    assert_eq!(narou::parse_ncode(URL).unwrap(), "n9669bk");
+    assert_eq!(narou::parse_ncode("https://ncode.syosetu.com/N9669BK/12/").unwrap(), "n9669bk");
+    assert_eq!(narou::parse_ncode("https://ncode.syosetu.com/novelview/infotop/ncode/n9669bk/")
+        .unwrap(), "n9669bk");
+    assert_eq!(narou::parse_ncode("https://api.syosetu.com/novelapi/api/?out=json&ncode=n9669bk")
+        .unwrap(), "n9669bk");
+    assert!(narou::parse_ncode("https://kakuyomu.jp/works/n9669bk").is_err());
+    assert!(narou::parse_ncode("https://ncode.syosetu.com/").is_err());
+}
+
+#[test]
+fn api_json() {
+    let db = make_db();
+    let import = narou::import(&db, URL, API_JSON).unwrap();
+    let creator = &import.source_creator;
+
+    assert_eq!(import.ncode, "n9669bk");
+    assert_eq!(creator.names.get(&Lang::Japanese).map(String::as_str),
+        Some("無職転生　- 異世界行ったら本気だす -"));
+    assert_eq!(creator.source_type.as_str(), "WebNovel");
+    assert_eq!(creator.people.len(), 1);
+    assert_eq!(creator.people[0].0, 1);
+    assert!(import.unmatched_people.is_empty());
+    assert_eq!(import.episode_count, Some(286));
+    assert_eq!(import.last_updated, Some(NaiveDate::from_ymd_opt(2015, 4, 3).unwrap()));
+    assert_eq!(creator.dates.len(), 2);
+    assert_eq!(creator.dates[0].0, NaiveDate::from_ymd_opt(2012, 11, 22).unwrap());
+    assert_eq!(creator.related_links[0].0, URL);
+}
+
+#[test]
+fn table_of_contents() {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    let import = narou::import(&db, URL, TOC_HTML).unwrap();
+    let creator = &import.source_creator;
+
+    assert_eq!(creator.names.get(&Lang::Japanese).map(String::as_str),
+        Some("無職転生　- 異世界行ったら本気だす -"));
+    assert!(creator.descriptions.get(&Lang::Japanese).unwrap().starts_with("３４歳"));
+    assert!(creator.people.is_empty());
+    assert_eq!(import.unmatched_people[0].0, "理不尽な孫の手");
+    assert_eq!(import.episode_count, Some(3));
+    assert_eq!(import.last_updated, Some(NaiveDate::from_ymd_opt(2012, 11, 24).unwrap()));
+    assert_eq!(creator.dates[0].0, NaiveDate::from_ymd_opt(2012, 11, 22).unwrap());
+}
+
+#[test]
+fn different_novel() {
+    let db = make_db();
+    let url = "https://ncode.syosetu.com/n1234ab/";
+    assert!(matches!(narou::import(&db, url, API_JSON), Err(Error::Validation(_))));
+    assert!(matches!(narou::import(&db, url, TOC_HTML), Err(Error::Validation(_))));
+
+    // A short story doesn't have any episodes, but it still has its canonical link
+    let canonical_link = "<link rel=\"canonical\" href=\"https://ncode.syosetu.com/n9669bk/\">";
+    let short_story = TOC_HTML.replace("href=\"/n9669bk/", "href=\"/episodes/")
+        .replace("<head>", &format!("<head>\n{}", canonical_link));
+    assert!(narou::import(&db, URL, &short_story).is_ok());
+    assert!(matches!(narou::import(&db, url, &short_story), Err(Error::Validation(_))));
+}
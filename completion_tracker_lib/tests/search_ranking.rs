@@ -0,0 +1,183 @@
+//! Guards the order that search results come back in.
+//! If a change to the scoring moves one of these around, it should be on purpose.
+
+use std::collections::{BTreeMap};
+
+use completion_tracker_lib::{
+    TrackerDBs,
+    db_link::{ConnectionHolder},
+    error::{Error},
+    simple_enum::{SimpleEnum},
+    sources::{
+        api::{
+            search::{self, ItemType, SearchQuery},
+            source::{self, SourceCreator},
+            universe_tag::{self, UniverseTagCreator},
+        },
+        source_types::{Lang, LangMap, SourceType},
+    },
+};
+
+const NAMES: &[&str] = &[
+    "Fate",
+    "Fate/Zero",
+    "Fate/stay night",
+    "Neon Genesis Evangelion",
+    "Evangelion: 1.0 You Are (Not) Alone",
+    "Re:Zero",
+    "Sword Art Online",
+    "Zero no Tsukaima",
+];
+
+fn make_db() -> ConnectionHolder {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    for name in NAMES {
+        let mut names = LangMap::new();
+        names.insert(Lang::English, name.to_string());
+        let mut descriptions = LangMap::new();
+        descriptions.insert(Lang::English, "-".to_string());
+
+        universe_tag::create(&db, UniverseTagCreator {
+            names,
+            descriptions,
+            related_links: Vec::new(),
+            parents: Vec::new(),
+            children: Vec::new(),
+            related_universe_tags: Vec::new(),
+        }).unwrap();
+    }
+    db
+}
+
+/// Searches for the names in ranked order
+fn ranked_names(db: &ConnectionHolder, query: &str) -> Vec<&'static str> {
+    let results = search::search(db, &TrackerDBs::new(), SearchQuery {
+        query: query.to_string(),
+        item_type: Some(ItemType::UniverseTag),
+        tracker: None,
+        type_boosts: Default::default(),
+    }).unwrap();
+    // The Universe Tags were created in the order of the names
+    results.into_iter()
+        .map(|result| NAMES[result.id as usize - 1])
+        .collect()
+}
+
+fn assert_ranked(db: &ConnectionHolder, query: &str, expected: &[&str]) {
+    let ranked = ranked_names(db, query);
+    assert!(ranked.len() >= expected.len(), "'{}' only found {:?}", query, ranked);
+    assert_eq!(&ranked[..expected.len()], expected, "'{}' was ranked as {:?}", query, ranked);
+}
+
+#[test]
+fn exact_names_rank_first() {
+    let db = make_db();
+    assert_ranked(&db, "Fate", &["Fate"]);
+    assert_ranked(&db, "fate", &["Fate"]);
+    assert_ranked(&db, "Fate/", &["Fate", "Fate/Zero", "Fate/stay night"]);
+    assert_ranked(&db, "Sword Art Online", &["Sword Art Online"]);
+}
+
+#[test]
+fn fragments_rank_their_source_first() {
+    let db = make_db();
+    assert_ranked(&db, "Fate Zero", &["Fate/Zero"]);
+    assert_ranked(&db, "stay night", &["Fate/stay night"]);
+    assert_ranked(&db, "sword art", &["Sword Art Online"]);
+}
+
+#[test]
+fn typos_rank_their_source_first() {
+    let db = make_db();
+    assert_ranked(&db, "Swrod Art Online", &["Sword Art Online"]);
+    assert_ranked(&db, "Fate/Zreo", &["Fate/Zero"]);
+
+    let evangelion = ranked_names(&db, "Evangelon");
+    assert!(evangelion.len() >= 2, "Only found {:?}", evangelion);
+    assert!(evangelion[..2].contains(&"Neon Genesis Evangelion"));
+    assert!(evangelion[..2].contains(&"Evangelion: 1.0 You Are (Not) Alone"));
+}
+
+#[test]
+fn unrelated_names_are_cut_off() {
+    let db = make_db();
+    let ranked = ranked_names(&db, "Evangelion");
+    assert!(!ranked.contains(&"Fate"), "Found {:?}", ranked);
+    assert!(!ranked.contains(&"Sword Art Online"), "Found {:?}", ranked);
+}
+
+/// A Source called "Spice and Wolf" and a Character called "Holo the Wise Wolf"
+fn make_mixed_db() -> ConnectionHolder {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    let mut names = LangMap::new();
+    names.insert(Lang::English, "Spice and Wolf".to_string());
+    source::create(&db, SourceCreator {
+        names,
+        descriptions: LangMap::new(),
+        source_type: SourceType::Novel,
+        related_links: Vec::new(),
+        dates: Vec::new(),
+        universe_tags: Vec::new(),
+        related_sources: Vec::new(),
+        people: Vec::new(),
+        characters: Vec::new(),
+        companies: Vec::new(),
+    }).unwrap();
+    // Characters can't be created through the API yet
+    db.lock().execute_batch("
+        INSERT INTO Strings (id, english) VALUES (100, 'Holo the Wise Wolf'), (101, '-');
+        INSERT INTO Characters (names, descriptions) VALUES (100, 101);
+    ").unwrap();
+    db
+}
+
+/// Searches every item type, giving back the ranked item types
+fn ranked_types(db: &ConnectionHolder, query: &str, type_boosts: BTreeMap<ItemType, f64>)
+-> Result<Vec<&'static str>, Error> {
+    let results = search::search(db, &TrackerDBs::new(), SearchQuery {
+        query: query.to_string(),
+        item_type: None,
+        tracker: None,
+        type_boosts,
+    })?;
+    Ok(results.into_iter().map(|result| result.item_type.as_str()).collect())
+}
+
+#[test]
+fn blank_queries_find_nothing() {
+    let db = make_mixed_db();
+    for query in ["", "   ", "\t\n"] {
+        assert!(ranked_types(&db, query, BTreeMap::new()).unwrap().is_empty());
+    }
+    // Qualifiers by themselves still find everything that they allow
+    assert_eq!(ranked_types(&db, "type:source", BTreeMap::new()).unwrap(), vec!["Source"]);
+}
+
+#[test]
+fn strong_matches_outrank_other_item_types() {
+    let db = make_mixed_db();
+    // "Wolf" is a bigger part of the Source's name
+    assert_eq!(ranked_types(&db, "Wolf", BTreeMap::new()).unwrap(), vec!["Source", "Character"]);
+    assert_eq!(ranked_types(&db, "Wise Wolf", BTreeMap::new()).unwrap(), vec!["Character"]);
+}
+
+#[test]
+fn type_boosts_change_the_order() {
+    let db = make_mixed_db();
+    let mut type_boosts = BTreeMap::new();
+    type_boosts.insert(ItemType::Character, 3.0);
+    assert_eq!(ranked_types(&db, "Wolf", type_boosts).unwrap(), vec!["Character", "Source"]);
+
+    let mut type_boosts = BTreeMap::new();
+    type_boosts.insert(ItemType::Source, 0.0);
+    assert_eq!(ranked_types(&db, "Wolf", type_boosts).unwrap(), vec!["Character"]);
+}
+
+#[test]
+fn negative_boosts_are_rejected() {
+    let db = make_mixed_db();
+    let mut type_boosts = BTreeMap::new();
+    type_boosts.insert(ItemType::Character, -1.0);
+    assert!(matches!(ranked_types(&db, "Wolf", type_boosts),
+        Err(Error::Validation(_))));
+}
@@ -0,0 +1,158 @@
+//! The infoboxes of saved Wikipedia pages should fill out a SourceCreator.
+
+use chrono::{NaiveDate};
+
+use completion_tracker_lib::{
+    db_link::{ConnectionHolder},
+    imports::wikipedia,
+    simple_enum::{SimpleEnum},
+    sources::{
+        api::source,
+        backup::{self, BACKUP_VERSION, CompanyBackup, ImportMode, PersonBackup, SourcesBackup},
+        source_types::{Lang, LangMap, LangMapList, PersonRole},
+    },
+};
+
+const EN_WIKITEXT: &str = include_str!("fixtures/wikipedia_en.wikitext");
+const JA_WIKITEXT: &str = include_str!("fixtures/wikipedia_ja.wikitext");
+const EN_HTML: &str = include_str!("fixtures/wikipedia_en.html");
+const JA_HTML: &str = include_str!("fixtures/wikipedia_ja.html");
+
+fn lang_map(lang: Lang, text: &str) -> LangMap {
+    let mut map = LangMap::new();
+    map.insert(lang, text.to_string());
+    map
+}
+
+/// A DB with Isuna Hasekura (Person 1) and KADOKAWA (Company 1) in it
+fn make_db() -> ConnectionHolder {
+    let db = completion_tracker_lib::init_source_db().unwrap();
+    let mut aliases = LangMapList::new();
+    aliases.insert(Lang::Japanese, vec!["支倉凍砂".to_string()]);
+    backup::import(&db, SourcesBackup {
+        version: BACKUP_VERSION,
+        universe_tags: Vec::new(),
+        people: vec![
+            PersonBackup {
+                id: 1,
+                names: lang_map(Lang::English, "Isuna Hasekura"),
+                descriptions: lang_map(Lang::English, "Author"),
+                aliases,
+                birth_country: None,
+                birth_date: None,
+                death_date: None,
+            },
+        ],
+        companies: vec![
+            CompanyBackup {
+                id: 1,
+                names: lang_map(Lang::English, "KADOKAWA"),
+                descriptions: lang_map(Lang::English, "Publisher"),
+                country: None,
+            },
+        ],
+        characters: Vec::new(),
+        sources: Vec::new(),
+    }, ImportMode::Restore).unwrap();
+    db
+}
+
+fn role_names(people: &[(u64, PersonRole)]) -> Vec<(u64, &'static str)> {
+    people.iter().map(|(id, role)| (*id, role.db_name())).collect()
+}
+
+#[test]
+fn english_wikitext() {
+    let db = make_db();
+    let url = "https://en.wikipedia.org/wiki/Spice_and_Wolf";
+    let import = wikipedia::import(&db, EN_WIKITEXT, Lang::English, Some(url)).unwrap();
+    let creator = &import.source_creator;
+
+    assert_eq!(creator.names.get(&Lang::English).map(String::as_str), Some("Spice and Wolf"));
+    assert_eq!(creator.source_type.as_str(), "Novel");
+    assert_eq!(role_names(&creator.people), vec![(1, "Writer")]);
+    assert_eq!(import.unmatched_people.len(), 1);
+    assert_eq!(import.unmatched_people[0].0, "Jū Ayakura");
+    assert_eq!(import.unmatched_people[0].1.db_name(), "Illustrator");
+    assert!(creator.companies.is_empty());
+    assert_eq!(import.unmatched_companies.len(), 1);
+    assert_eq!(import.unmatched_companies[0].0, "ASCII Media Works");
+    assert_eq!(creator.dates.len(), 1);
+    assert_eq!(creator.dates[0].0, NaiveDate::from_ymd_opt(2006, 2, 10).unwrap());
+    assert_eq!(import.volume_count, Some(24));
+    assert_eq!(creator.related_links.len(), 1);
+    assert_eq!(creator.related_links[0].0, url);
+}
+
+#[test]
+fn japanese_wikitext() {
+    let db = make_db();
+    let import = wikipedia::import(&db, JA_WIKITEXT, Lang::Japanese, None).unwrap();
+    let creator = &import.source_creator;
+
+    assert_eq!(creator.names.get(&Lang::Japanese).map(String::as_str), Some("狼と香辛料"));
+    assert_eq!(creator.source_type.as_str(), "Novel");
+    // The author is found by their alias
+    assert_eq!(role_names(&creator.people), vec![(1, "Writer")]);
+    assert_eq!(import.unmatched_people[0].0, "文倉十");
+    assert_eq!(creator.companies.len(), 1);
+    assert_eq!(creator.companies[0].0, 1);
+    assert!(import.unmatched_companies.is_empty());
+    assert_eq!(creator.dates[0].0, NaiveDate::from_ymd_opt(2006, 2, 10).unwrap());
+    assert_eq!(import.volume_count, Some(24));
+    assert!(creator.related_links.is_empty());
+}
+
+#[test]
+fn english_html() {
+    let db = make_db();
+    let import = wikipedia::import(&db, EN_HTML, Lang::English, None).unwrap();
+    let creator = &import.source_creator;
+
+    assert_eq!(creator.names.get(&Lang::English).map(String::as_str),
+        Some("The Name of the Wind"));
+    assert_eq!(creator.source_type.as_str(), "Novel");
+    assert!(creator.people.is_empty());
+    assert_eq!(import.unmatched_people.len(), 1);
+    assert_eq!(import.unmatched_people[0].0, "Patrick Rothfuss");
+    assert_eq!(import.unmatched_companies[0].0, "DAW Books");
+    assert_eq!(creator.dates.len(), 1);
+    assert_eq!(creator.dates[0].0, NaiveDate::from_ymd_opt(2007, 3, 27).unwrap());
+    assert_eq!(import.volume_count, None);
+}
+
+#[test]
+fn japanese_html() {
+    let db = make_db();
+    let import = wikipedia::import(&db, JA_HTML, Lang::Japanese, None).unwrap();
+    let creator = &import.source_creator;
+
+    assert_eq!(creator.names.get(&Lang::Japanese).map(String::as_str), Some("よつばと!"));
+    assert_eq!(creator.source_type.as_str(), "Comic");
+    assert_eq!(import.unmatched_people[0].0, "あずまきよひこ");
+    assert_eq!(creator.companies.len(), 1);
+    assert_eq!(creator.dates[0].0, NaiveDate::from_ymd_opt(2003, 3, 21).unwrap());
+    assert_eq!(import.volume_count, Some(15));
+}
+
+#[test]
+fn reviewed_import_can_be_created() {
+    let db = make_db();
+    let url = "https://en.wikipedia.org/wiki/Spice_and_Wolf";
+    let import = wikipedia::import(&db, EN_WIKITEXT, Lang::English, Some(url)).unwrap();
+    let source_id = source::create(&db, import.source_creator).unwrap();
+
+    let exported = backup::export(&db).unwrap();
+    let source = exported.sources.iter().find(|source| source.id == source_id).unwrap();
+    assert_eq!(source.names.get(&Lang::English).map(String::as_str), Some("Spice and Wolf"));
+    assert_eq!(source.related_links[0].0, url);
+    assert_eq!(role_names(&source.people), vec![(1, "Writer")]);
+    assert_eq!(source.dates[0].0, NaiveDate::from_ymd_opt(2006, 2, 10).unwrap());
+}
+
+#[test]
+fn page_without_infobox() {
+    let db = make_db();
+    assert!(wikipedia::import(&db, "Just some text", Lang::English, None).is_err());
+    assert!(wikipedia::import(&db, "<html><p>No box</p></html>", Lang::English, None).is_err());
+}
@@ -0,0 +1,564 @@
+//! The admin commands, which work on the DB files without going through the server.
+//! Anything that a command gives back is printed as JSON, so it can be read by other tools.
+
+use std::{
+    collections::{BTreeMap},
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{NaiveDate};
+use serde::{Serialize};
+
+use completion_tracker_lib::{
+    TrackerDBs,
+    db_link::{ConnectionHolder},
+    error::{Error, ResultExt},
+    exports::{
+        MediaType, anilist, atom, my_anime_list,
+        calendar::{self, CalendarFilter},
+        csv::{self, CsvExportOptions},
+        graph::{self, GraphFormat, GraphOptions, Subgraph},
+        site,
+    },
+    imports::{self, narou, wikipedia},
+    simple_enum::{SimpleEnum},
+    sources::{
+        api::{
+            search::{self, ItemType, SearchQuery},
+            source::{self, SourceCreator},
+            universe_tag::{self, UniverseTagCreator},
+        },
+        backup::{self, ImportMode},
+        source_types::{Lang, SourceType},
+    },
+    tracking::{
+        CompletionStatus,
+        entry::{self, StatusUpdate},
+    },
+    utils,
+};
+
+use crate::{
+    run_info::{RunInfo},
+    static_server::{StaticServer},
+};
+
+pub const USAGE: &str = "\
+Usage: completion_tracker [options] [command]
+With no command, the server is started.
+
+Options (each one can also be set with its environment variable):
+    --config <file>             COMPLETION_TRACKER_CONFIG (runInfo.json by default)
+    --port <port>               COMPLETION_TRACKER_PORT
+    --bind <address>            COMPLETION_TRACKER_BIND
+    --tracking-folder <folder>  COMPLETION_TRACKER_TRACKING_FOLDER
+    --sources-db <file>         COMPLETION_TRACKER_SOURCES_DB
+
+Commands:
+    search <query> [--type <item type>] [--tracker <name>]
+    show <item type> <id>
+    create <UniverseTag | Source> <file.json>
+    mark <source id> <InProgress | Complete | GameComplete>
+        [--date <YYYY-MM-DD>] [--play-time <seconds>] [--tracker <name>]
+    import my-anime-list <list.xml> [--tracker <name>]
+    import csv <file.csv> [--preset Goodreads | --mapping <file.json>] [--tracker <name>]
+    import wikipedia <page file> [--lang <lang>] [--url <url>] [--create]
+    import narou <url> <data file> [--create]
+    import backup <file.json> [--merge]
+    export backup [--out <file>]
+    export csv [--lang <lang>] [--bom] [--tracker <name>] [--out <file>]
+    export anilist <anime | manga> [--tracker <name>] [--out <file>]
+    export my-anime-list <anime | manga> [--tracker <name>] [--out <file>]
+    export feed [--lang <lang>] [--tracker <name>] [--out <file>]
+    export calendar [--lang <lang>] [--universe-tag <id>] [--source-type <type>] [--out <file>]
+    export graph <dot | graphml> [--lang <lang>] [--subgraphs <a,b>] [--out <file>]
+    export site [--lang <lang>]
+    check
+    backup <folder>
+    help
+";
+
+/// The options that don't take a value
+const FLAGS: &[&str] = &["bom", "create", "merge"];
+
+/// The arguments after the command.
+/// An option is "--name value", a flag is only "--name", and everything else is positional.
+struct Args {
+    positional: Vec<String>,
+    options: BTreeMap<String, String>,
+}
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, Error> {
+        let mut positional = Vec::new();
+        let mut options = BTreeMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if FLAGS.contains(&name) => {
+                    options.insert(name.to_string(), String::new());
+                },
+                Some(name) => {
+                    let value = args.next().ok_or_else(|| Error::Validation(
+                        format!("--{} needs a value", name)))?;
+                    options.insert(name.to_string(), value.clone());
+                },
+                None => positional.push(arg.clone()),
+            }
+        }
+        // Taken from the front
+        positional.reverse();
+        Ok(Args { positional, options })
+    }
+
+    fn next(&mut self, name: &str) -> Result<String, Error> {
+        self.positional.pop()
+            .ok_or_else(|| Error::Validation(format!("The {} is missing", name)))
+    }
+
+    fn option(&mut self, name: &str) -> Option<String> {
+        self.options.remove(name)
+    }
+
+    fn flag(&mut self, name: &str) -> bool {
+        self.options.remove(name).is_some()
+    }
+
+    fn lang(&mut self) -> Result<Lang, Error> {
+        match self.option("lang") {
+            Some(lang) => parse_enum("language", &lang),
+            None => Ok(Lang::English),
+        }
+    }
+
+    /// Anything left over is a mistake, so it isn't quietly ignored
+    fn finish(self) -> Result<(), Error> {
+        if let Some(arg) = self.positional.last() {
+            return Err(Error::Validation(format!("Didn't expect {}", arg)));
+        }
+        if let Some(name) = self.options.keys().next() {
+            return Err(Error::Validation(format!("--{} isn't an option here", name)));
+        }
+        Ok(())
+    }
+}
+
+fn parse_enum<T: SimpleEnum>(what: &str, string: &str) -> Result<T, Error> {
+    T::from_str(string).ok_or_else(|| Error::Validation(format!(
+        "There isn't a {} called {} (it can be {})", what, string,
+        T::all().iter().map(|value| value.as_str()).collect::<Vec<_>>().join(", "),
+    )))
+}
+
+fn parse_id(what: &str, string: &str) -> Result<u64, Error> {
+    string.parse()
+        .map_err(|_| Error::Validation(format!("The {} must be an ID, not {}", what, string)))
+}
+
+fn parse_media_type(media_type: &str) -> Result<MediaType, Error> {
+    match media_type {
+        "anime" => Ok(MediaType::Anime),
+        "manga" => Ok(MediaType::Manga),
+        _ => Err(Error::Validation(
+            format!("The media type must be anime or manga, not {}", media_type))),
+    }
+}
+
+/// The DBs from the RunInfo, opened once for the command
+struct Admin<'a> {
+    run_info: &'a RunInfo,
+    sources_db: ConnectionHolder,
+    tracker_dbs: TrackerDBs,
+    out: &'a mut dyn Write,
+}
+impl Admin<'_> {
+    fn print_json(&mut self, data: &impl Serialize) -> Result<(), Error> {
+        writeln!(self.out, "{}", serde_json::to_string_pretty(data)?)?;
+        Ok(())
+    }
+
+    /// Into the --out file, or printed when there isn't one
+    fn write_output(&mut self, out_file: Option<String>, contents: &str) -> Result<(), Error> {
+        match out_file {
+            Some(file) => fs::write(&file, contents)
+                .with_context(|| format!("Failed to write {}", file)),
+            None => self.out.write_all(contents.as_bytes()).map_err(Error::from),
+        }
+    }
+
+    fn tracker_db(&self, args: &mut Args) -> Result<&ConnectionHolder, Error> {
+        completion_tracker_lib::find_tracker_db(&self.tracker_dbs, args.option("tracker").as_ref())
+    }
+
+    fn search(&mut self, mut args: Args) -> Result<(), Error> {
+        let query = args.next("query")?;
+        let item_type = match args.option("type") {
+            Some(item_type) => Some(parse_enum("item type", &item_type)?),
+            None => None,
+        };
+        let tracker = args.option("tracker");
+        args.finish()?;
+
+        let results = search::search(&self.sources_db, &self.tracker_dbs, SearchQuery {
+            query,
+            item_type,
+            tracker,
+            type_boosts: BTreeMap::new(),
+        })?;
+        self.print_json(&results)
+    }
+
+    /// The item is shown the same way that it is in a backup
+    fn show(&mut self, mut args: Args) -> Result<(), Error> {
+        let item_type: ItemType = parse_enum("item type", &args.next("item type")?)?;
+        let id = parse_id("item", &args.next("ID")?)?;
+        args.finish()?;
+
+        let backup = backup::export(&self.sources_db)?;
+        let item = match item_type {
+            ItemType::UniverseTag => backup.universe_tags.iter().find(|item| item.id == id)
+                .map(serde_json::to_value),
+            ItemType::Person => backup.people.iter().find(|item| item.id == id)
+                .map(serde_json::to_value),
+            ItemType::Company => backup.companies.iter().find(|item| item.id == id)
+                .map(serde_json::to_value),
+            ItemType::Character => backup.characters.iter().find(|item| item.id == id)
+                .map(serde_json::to_value),
+            ItemType::Source => backup.sources.iter().find(|item| item.id == id)
+                .map(serde_json::to_value),
+        };
+        match item {
+            Some(item) => self.print_json(&item?),
+            None => Err(Error::NotFound(format!("There isn't a {} with the ID {}",
+                item_type.as_str(), id))),
+        }
+    }
+
+    fn create(&mut self, mut args: Args) -> Result<(), Error> {
+        let item_type: ItemType = parse_enum("item type", &args.next("item type")?)?;
+        let file = args.next("JSON file")?;
+        args.finish()?;
+
+        let id = match item_type {
+            ItemType::UniverseTag => {
+                let creator: UniverseTagCreator = utils::read_json_file(&file)
+                    .with_context(|| format!("Failed to read a UniverseTagCreator from {}", file))?;
+                universe_tag::create(&self.sources_db, creator)
+                    .context("Failed to create a new Universe Tag")?
+            },
+            ItemType::Source => {
+                let creator: SourceCreator = utils::read_json_file(&file)
+                    .with_context(|| format!("Failed to read a SourceCreator from {}", file))?;
+                source::create(&self.sources_db, creator)
+                    .context("Failed to create a new Source")?
+            },
+            ItemType::Character | ItemType::Company | ItemType::Person => {
+                return Err(Error::Validation(format!(
+                    "Only a UniverseTag or a Source can be created, not a {}",
+                    item_type.as_str())));
+            },
+        };
+        self.print_json(&id)
+    }
+
+    fn mark(&mut self, mut args: Args) -> Result<(), Error> {
+        let source_id = parse_id("Source", &args.next("Source ID")?)?;
+        let status: CompletionStatus = parse_enum("status", &args.next("status")?)?;
+        let date = match args.option("date") {
+            Some(date) => Some(NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .map_err(|e| Error::Validation(format!("Bad date {}: {}", date, e)))?),
+            None => None,
+        };
+        let play_time = match args.option("play-time") {
+            Some(play_time) => Some(play_time.parse().map_err(|_| Error::Validation(
+                format!("The play time must be in seconds, not {}", play_time)))?),
+            None => None,
+        };
+        let tracker_db = self.tracker_db(&mut args)?;
+        args.finish()?;
+
+        // The tracker doesn't know about the sources DB, so the ID is checked here
+        if !source::exists(&self.sources_db, source_id)? {
+            return Err(Error::NotFound(format!("There isn't a Source with the ID {}", source_id)));
+        }
+        entry::update_status(tracker_db, StatusUpdate {
+            source_id,
+            status,
+            date,
+            play_time,
+        }).context("Failed to mark the Source")
+    }
+
+    fn import(&mut self, mut args: Args) -> Result<(), Error> {
+        match args.next("import")?.as_str() {
+            "my-anime-list" => {
+                let file = args.next("list file")?;
+                let tracker_db = self.tracker_db(&mut args)?;
+                args.finish()?;
+                let xml = fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file))?;
+                let report = imports::my_anime_list::import(&self.sources_db, tracker_db, &xml)
+                    .context("Failed to import the MyAnimeList list")?;
+                self.print_json(&report)
+            },
+            "csv" => {
+                let file = args.next("CSV file")?;
+                let mapping = match (args.option("mapping"), args.option("preset")) {
+                    (Some(mapping), _) => utils::read_json_file(&mapping)
+                        .with_context(|| format!("Failed to read a mapping from {}", mapping))?,
+                    (None, Some(preset)) => {
+                        // The preset names are only known by serde
+                        let preset: imports::csv::CsvPreset =
+                            serde_json::from_value(serde_json::Value::String(preset.clone()))
+                            .map_err(|_| Error::Validation(
+                                format!("There isn't a CSV preset called {}", preset)))?;
+                        preset.mapping()
+                    },
+                    (None, None) => return Err(Error::Validation(
+                        "Either --preset or --mapping is needed for the CSV columns".to_string())),
+                };
+                let tracker_db = self.tracker_db(&mut args)?;
+                args.finish()?;
+                let csv = fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file))?;
+                let report = imports::csv::import(&self.sources_db, tracker_db, &csv, &mapping)
+                    .context("Failed to import the CSV")?;
+                self.print_json(&report)
+            },
+            "wikipedia" => {
+                let file = args.next("page file")?;
+                let lang = args.lang()?;
+                let url = args.option("url");
+                let create = args.flag("create");
+                args.finish()?;
+                let page = fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file))?;
+                let import = wikipedia::import(&self.sources_db, &page, lang, url.as_deref())
+                    .context("Failed to read the Wikipedia page")?;
+                if create {
+                    self.create_imported(import.source_creator)
+                } else {
+                    self.print_json(&import)
+                }
+            },
+            "narou" => {
+                let url = args.next("URL")?;
+                let file = args.next("data file")?;
+                let create = args.flag("create");
+                args.finish()?;
+                let data = fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file))?;
+                let import = narou::import(&self.sources_db, &url, &data)
+                    .context("Failed to read the novel")?;
+                if create {
+                    self.create_imported(import.source_creator)
+                } else {
+                    self.print_json(&import)
+                }
+            },
+            "backup" => {
+                let file = args.next("backup file")?;
+                let mode = if args.flag("merge") { ImportMode::Merge } else { ImportMode::Restore };
+                args.finish()?;
+                let id_maps = backup::import_from_file(&self.sources_db, &file, mode)
+                    .with_context(|| format!("Failed to import the backup from {}", file))?;
+                self.print_json(&id_maps)
+            },
+            other => Err(Error::Validation(format!("There isn't an import called {}", other))),
+        }
+    }
+
+    /// Only the Source gets created. What couldn't be matched has to be added by hand.
+    fn create_imported(&mut self, creator: SourceCreator) -> Result<(), Error> {
+        let id = source::create(&self.sources_db, creator)
+            .context("Failed to create the imported Source")?;
+        self.print_json(&id)
+    }
+
+    /// Every option is checked before anything gets written
+    fn export(&mut self, mut args: Args) -> Result<(), Error> {
+        let out_file = args.option("out");
+        let contents = match args.next("export")?.as_str() {
+            "backup" => {
+                args.finish()?;
+                serde_json::to_string_pretty(&backup::export(&self.sources_db)?)?
+            },
+            "csv" => {
+                let options = CsvExportOptions {
+                    lang: args.lang()?,
+                    bom: args.flag("bom"),
+                };
+                let tracker_db = self.tracker_db(&mut args)?;
+                args.finish()?;
+                let mut csv = String::new();
+                csv::export(&self.sources_db, tracker_db, &options)
+                    .context("Failed to export the CSV")?
+                    .read_to_string(&mut csv)?;
+                csv
+            },
+            "anilist" => {
+                let media_type = parse_media_type(&args.next("media type")?)?;
+                let tracker_db = self.tracker_db(&mut args)?;
+                args.finish()?;
+                let collection = anilist::export(&self.sources_db, tracker_db, media_type)
+                    .context("Failed to export to AniList")?;
+                serde_json::to_string_pretty(&collection)?
+            },
+            "my-anime-list" => {
+                let media_type = parse_media_type(&args.next("media type")?)?;
+                let tracker_db = self.tracker_db(&mut args)?;
+                args.finish()?;
+                my_anime_list::export(&self.sources_db, tracker_db, media_type)
+                    .context("Failed to export to MyAnimeList")?
+            },
+            "feed" => {
+                let lang = args.lang()?;
+                let tracker_name = args.option("tracker");
+                args.finish()?;
+                let tracker_db = completion_tracker_lib::find_tracker_db(&self.tracker_dbs,
+                    tracker_name.as_ref())?;
+                // Without a name, it's the only tracker
+                let tracker_name = tracker_name
+                    .unwrap_or_else(|| self.tracker_dbs.keys().next().unwrap().clone());
+                atom::export(&self.sources_db, tracker_db, &tracker_name, lang)
+                    .context("Failed to make the feed")?
+            },
+            "calendar" => {
+                let universe_tag_id = match args.option("universe-tag") {
+                    Some(id) => Some(parse_id("Universe Tag", &id)?),
+                    None => None,
+                };
+                let source_type: Option<SourceType> = match args.option("source-type") {
+                    Some(source_type) => Some(parse_enum("source type", &source_type)?),
+                    None => None,
+                };
+                let filter = CalendarFilter {
+                    universe_tag_id,
+                    source_type,
+                    lang: args.lang()?,
+                };
+                args.finish()?;
+                calendar::export(&self.sources_db, &filter)
+                    .context("Failed to make the calendar")?
+            },
+            "graph" => {
+                let format = match args.next("graph format")?.as_str() {
+                    "dot" => GraphFormat::Dot,
+                    "graphml" => GraphFormat::GraphML,
+                    other => return Err(Error::Validation(
+                        format!("The graph format must be dot or graphml, not {}", other))),
+                };
+                let mut subgraphs = Vec::new();
+                for subgraph in args.option("subgraphs").unwrap_or_default().split(',')
+                    .filter(|subgraph| !subgraph.is_empty()) {
+                    subgraphs.push(parse_enum::<Subgraph>("subgraph", subgraph)?);
+                }
+                let options = GraphOptions {
+                    subgraphs,
+                    lang: args.lang()?,
+                    format,
+                };
+                args.finish()?;
+                graph::export(&self.sources_db, &options)
+                    .context("Failed to make the graph")?
+            },
+            "site" => {
+                let lang = args.lang()?;
+                args.finish()?;
+                if out_file.is_some() {
+                    // It's always written into the site folder
+                    return Err(Error::Validation("--out isn't an option here".to_string()));
+                }
+                let site_folder = self.run_info.site_folder();
+                let report = site::export(&self.sources_db, &self.tracker_dbs, lang,
+                    StaticServer.site_css(), &site_folder)
+                    .context("Failed to export the site")?;
+                return self.print_json(&report);
+            },
+            other => return Err(Error::Validation(
+                format!("There isn't an export called {}", other))),
+        };
+        self.write_output(out_file, &contents)
+    }
+
+    /// Fails if anything is wrong, after printing everything that's wrong in every DB
+    fn check(&mut self, args: Args) -> Result<(), Error> {
+        args.finish()?;
+
+        let mut problems = BTreeMap::new();
+        problems.insert("sources".to_string(), self.sources_db.check()?);
+        for (tracker_name, tracker_db) in self.tracker_dbs.iter() {
+            problems.insert(format!("tracker {}", tracker_name), tracker_db.check()?);
+        }
+        self.print_json(&problems)?;
+
+        let problem_count: usize = problems.values().map(Vec::len).sum();
+        if problem_count == 0 {
+            Ok(())
+        } else {
+            Err(Error::Database(format!("Found {} problems in the DBs", problem_count)))
+        }
+    }
+
+    /// Copies every DB into the folder, along with a JSON backup of the sources DB.
+    /// The trackers' DBs go into trackers/.
+    fn backup(&mut self, mut args: Args) -> Result<(), Error> {
+        let folder = PathBuf::from(args.next("backup folder")?);
+        args.finish()?;
+        fs::create_dir_all(&folder)
+            .with_context(|| format!("Failed to create {}", folder.display()))?;
+
+        let mut files = vec![backup_file(&folder, "sources.db")];
+        self.sources_db.backup_to(&files[0])?;
+        // In their own folder, so a tracker can be called anything, even sources
+        let trackers_folder = folder.join("trackers");
+        fs::create_dir_all(&trackers_folder)
+            .with_context(|| format!("Failed to create {}", trackers_folder.display()))?;
+        for (tracker_name, tracker_db) in self.tracker_dbs.iter() {
+            let file = backup_file(&trackers_folder, &format!("{}.db", tracker_name));
+            tracker_db.backup_to(&file)?;
+            files.push(file);
+        }
+        let json_file = backup_file(&folder, "sources.json");
+        backup::export_to_file(&self.sources_db, &json_file)
+            .context("Failed to write the sources backup")?;
+        files.push(json_file);
+
+        self.print_json(&files)
+    }
+}
+
+fn backup_file(folder: &Path, file_name: &str) -> PathBuf {
+    folder.join(file_name)
+}
+
+/// Runs the command in the arguments (without the program name) on the DBs from the RunInfo
+pub fn run(run_info: &RunInfo, args: &[String], out: &mut dyn Write) -> Result<(), Error> {
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), Args::parse(args)?),
+        None => return Err(Error::Validation(format!("A command is needed\n{}", USAGE))),
+    };
+    if command == "help" {
+        return out.write_all(USAGE.as_bytes()).map_err(Error::from);
+    }
+
+    let trackers = run_info.trackers()?;
+    let mut admin = Admin {
+        run_info,
+        sources_db: completion_tracker_lib::open_source_db(run_info.sources_db_file())?,
+        tracker_dbs: completion_tracker_lib::init_tracker_dbs(&trackers)?,
+        out,
+    };
+    match command {
+        "search" => admin.search(args),
+        "show" => admin.show(args),
+        "create" => admin.create(args),
+        "mark" => admin.mark(args),
+        "import" => admin.import(args),
+        "export" => admin.export(args),
+        "check" => admin.check(args),
+        "backup" => admin.backup(args),
+        _ => Err(Error::Validation(format!("There isn't a command called {}\n{}", command, USAGE))),
+    }
+}
@@ -1,11 +1,57 @@
+pub mod export;
+pub mod import;
+pub mod link_check;
 mod search;
-pub use self::search::{search};
+pub use self::search::{search, suggest};
+pub mod smart_list;
+pub mod source;
 pub mod universe_tag;
 
+use std::fmt;
+
+use rouille::{Response};
+use schemars::{JsonSchema};
 use serde::{Serialize};
 
-#[derive(Serialize)]
+use completion_tracker_lib::error::{Error};
+
+#[derive(Serialize, JsonSchema)]
 pub struct APIResult<T> {
     pub success: bool,
     pub data: T,
 }
+
+/// A failed call. The code is the kind of error (like "NotFound"), so it can be told apart
+/// without reading the message.
+#[derive(Serialize, JsonSchema)]
+pub struct APIError {
+    pub success: bool,
+    pub code: &'static str,
+    pub data: String,
+}
+
+/// Sends the error with the status that goes with its kind
+pub fn error_response(error: Error) -> Response {
+    let status = match error {
+        Error::NotFound(_) => 404,
+        Error::Conflict(_) => 409,
+        Error::Validation(_) => 400,
+        Error::Database(_) | Error::Io(_) => 500,
+    };
+    Response::json(
+        &APIError {
+            success: false,
+            code: error.code(),
+            data: error.to_string(),
+        }
+    ).with_status_code(status)
+}
+
+/// For a request that couldn't be read, or that doesn't make sense
+pub fn bad_input(message: impl fmt::Display) -> Response {
+    error_response(Error::Validation(message.to_string()))
+}
+
+pub fn unknown_tracker(tracker_name: &str) -> Response {
+    error_response(Error::NotFound(format!("There isn't a tracker called {}", tracker_name)))
+}
@@ -0,0 +1,261 @@
+use std::path::{Path};
+
+use chrono::{DateTime, Utc};
+use rouille::{Request, Response, ResponseBody};
+use schemars::{JsonSchema};
+use serde::{Deserialize};
+
+use completion_tracker_lib::{
+    TrackerDBs,
+    db_link::{ConnectionHolder},
+    exports::{
+        MediaType, anilist, my_anime_list,
+        atom,
+        calendar::{self, CalendarFilter},
+        csv::{self, CsvExportOptions},
+        graph::{self, GraphFormat, GraphOptions, Subgraph},
+        site,
+    },
+    simple_enum::{SimpleEnum},
+    sources::source_types::{Lang, SourceType},
+};
+
+use crate::api_json::{APIResult, bad_input, error_response, unknown_tracker};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SiteExport {
+    /// The language for the names on the pages
+    lang: Lang,
+}
+
+/// The media type is in the URL as "anime" or "manga"
+fn parse_media_type(media_type: &str) -> Result<MediaType, Response> {
+    match media_type {
+        "anime" => Ok(MediaType::Anime),
+        "manga" => Ok(MediaType::Manga),
+        _ => Err(bad_input(
+            format!("The media type must be anime or manga, not {}", media_type))),
+    }
+}
+
+pub fn anilist_request(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs,
+tracker_name: &str, media_type: &str, _req: &Request) -> Response {
+    let tracker_db = match tracker_dbs.get(tracker_name) {
+        Some(tracker_db) => tracker_db,
+        None => return unknown_tracker(tracker_name),
+    };
+    let media_type = match parse_media_type(media_type) {
+        Ok(media_type) => media_type,
+        Err(response) => return response,
+    };
+    let collection = match anilist::export(sources_db, tracker_db, media_type) {
+        Ok(collection) => collection,
+        Err(e) => return error_response(e.context("Failed to export to AniList")),
+    };
+
+    Response::json(&collection)
+        .with_content_disposition_attachment(
+            &format!("{} {}.json", tracker_name, match media_type {
+                MediaType::Anime => "anime",
+                MediaType::Manga => "manga",
+            })
+        )
+}
+
+pub fn my_anime_list_request(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs,
+tracker_name: &str, media_type: &str, _req: &Request) -> Response {
+    let tracker_db = match tracker_dbs.get(tracker_name) {
+        Some(tracker_db) => tracker_db,
+        None => return unknown_tracker(tracker_name),
+    };
+    let media_type = match parse_media_type(media_type) {
+        Ok(media_type) => media_type,
+        Err(response) => return response,
+    };
+    let xml = match my_anime_list::export(sources_db, tracker_db, media_type) {
+        Ok(xml) => xml,
+        Err(e) => return error_response(e.context("Failed to export to MyAnimeList")),
+    };
+
+    let file_name = match media_type {
+        MediaType::Anime => "animelist.xml",
+        MediaType::Manga => "mangalist.xml",
+    };
+    Response::from_data("application/xml; charset=utf-8", xml)
+        .with_content_disposition_attachment(file_name)
+}
+
+/// The "lang" query parameter, which is English when it's missing
+fn lang_param(req: &Request) -> Result<Lang, Response> {
+    match req.get_param("lang") {
+        Some(lang) => Lang::from_str(&lang)
+            .ok_or_else(|| bad_input(format!("There isn't a language called {}", lang))),
+        None => Ok(Lang::English),
+    }
+}
+
+/// The options are in the query string: ?lang=Japanese&bom=true
+pub fn csv_request(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs, tracker_name: &str,
+req: &Request) -> Response {
+    let tracker_db = match tracker_dbs.get(tracker_name) {
+        Some(tracker_db) => tracker_db,
+        None => return unknown_tracker(tracker_name),
+    };
+    let lang = match lang_param(req) {
+        Ok(lang) => lang,
+        Err(response) => return response,
+    };
+    let options = CsvExportOptions {
+        lang,
+        bom: req.get_param("bom").as_deref() == Some("true"),
+    };
+    let csv = match csv::export(sources_db, tracker_db, &options) {
+        Ok(csv) => csv,
+        Err(e) => return error_response(e.context("Failed to export the CSV")),
+    };
+
+    // The rows are made as they're sent, so there's no Content-Length
+    Response {
+        status_code: 200,
+        headers: vec![("Content-Type".into(), "text/csv; charset=utf-8".into())],
+        data: ResponseBody::from_reader(csv),
+        upgrade: None,
+    }.with_content_disposition_attachment(&format!("{}.csv", tracker_name))
+}
+
+/// The filter is in the query string: ?universeTag=1&sourceType=Novel&lang=Japanese
+/// Every part of it is optional.
+pub fn calendar_request(sources_db: &ConnectionHolder, req: &Request) -> Response {
+    let universe_tag_id = match req.get_param("universeTag") {
+        Some(id) => match id.parse() {
+            Ok(id) => Some(id),
+            Err(_) => return bad_input(format!("The Universe Tag must be an ID, not {}", id)),
+        },
+        None => None,
+    };
+    let source_type = match req.get_param("sourceType") {
+        Some(source_type) => match SourceType::from_str(&source_type) {
+            Some(source_type) => Some(source_type),
+            None => return bad_input(format!("There isn't a source type called {}", source_type)),
+        },
+        None => None,
+    };
+    let lang = match lang_param(req) {
+        Ok(lang) => lang,
+        Err(response) => return response,
+    };
+    let filter = CalendarFilter {
+        universe_tag_id,
+        source_type,
+        lang,
+    };
+    let calendar = match calendar::export(sources_db, &filter) {
+        Ok(calendar) => calendar,
+        Err(e) => return error_response(e.context("Failed to make the calendar")),
+    };
+
+    Response::from_data("text/calendar; charset=utf-8", calendar)
+}
+
+/// The subgraphs are in the query string, like
+/// ?subgraphs=SourceRelations,CharacterRelations&lang=Japanese
+/// All of them are exported when it's missing.
+pub fn graph_request(sources_db: &ConnectionHolder, format: GraphFormat, req: &Request)
+-> Response {
+    let mut subgraphs = Vec::new();
+    for subgraph in req.get_param("subgraphs").unwrap_or_default().split(',')
+        .filter(|subgraph| !subgraph.is_empty()) {
+        match Subgraph::from_str(subgraph) {
+            Some(subgraph) => subgraphs.push(subgraph),
+            None => return bad_input(format!("There isn't a subgraph called {}", subgraph)),
+        }
+    }
+    let lang = match lang_param(req) {
+        Ok(lang) => lang,
+        Err(response) => return response,
+    };
+    let options = GraphOptions {
+        subgraphs,
+        lang,
+        format,
+    };
+    let graph = match graph::export(sources_db, &options) {
+        Ok(graph) => graph,
+        Err(e) => return error_response(e.context("Failed to make the graph")),
+    };
+
+    match format {
+        GraphFormat::Dot => Response::from_data("text/vnd.graphviz; charset=utf-8", graph),
+        GraphFormat::GraphML => {
+            Response::from_data("application/graphml+xml; charset=utf-8", graph)
+        },
+    }
+}
+
+/// Polling is cheap with If-Modified-Since, since the feed doesn't need to be made to check it
+pub fn feed_request(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs, tracker_name: &str,
+req: &Request) -> Response {
+    let tracker_db = match tracker_dbs.get(tracker_name) {
+        Some(tracker_db) => tracker_db,
+        None => return unknown_tracker(tracker_name),
+    };
+    let lang = match lang_param(req) {
+        Ok(lang) => lang,
+        Err(response) => return response,
+    };
+    let last_modified = match atom::last_modified(tracker_db) {
+        Ok(last_modified) => last_modified,
+        Err(e) => return error_response(e.context("Failed to read the tracker")),
+    };
+
+    let if_modified_since = req.header("If-Modified-Since")
+        .and_then(|since| DateTime::parse_from_rfc2822(since).ok());
+    if let (Some(last_modified), Some(since)) = (last_modified, if_modified_since) {
+        // HTTP dates don't have anything smaller than seconds
+        if last_modified.timestamp() <= since.timestamp() {
+            return Response {
+                status_code: 304,
+                headers: vec![("Last-Modified".into(), http_date(last_modified).into())],
+                data: ResponseBody::empty(),
+                upgrade: None,
+            };
+        }
+    }
+
+    let feed = match atom::export(sources_db, tracker_db, tracker_name, lang) {
+        Ok(feed) => feed,
+        Err(e) => return error_response(e.context("Failed to make the feed")),
+    };
+
+    let response = Response::from_data("application/atom+xml; charset=utf-8", feed);
+    match last_modified {
+        Some(last_modified) => {
+            response.with_unique_header("Last-Modified", http_date(last_modified))
+        },
+        None => response,
+    }
+}
+
+fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Writes the static site into the folder, replacing the pages that were there before
+pub fn site_request(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs, site_folder: &Path,
+css: &str, req: &Request) -> Response {
+    let site_export: SiteExport = match rouille::input::json_input(req) {
+        Ok(site_export) => site_export,
+        Err(e) => return bad_input(format!("Failed to make a SiteExport: {}", e)),
+    };
+    let report = match site::export(sources_db, tracker_dbs, site_export.lang, css, site_folder) {
+        Ok(report) => report,
+        Err(e) => return error_response(e.context("Failed to export the site")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: report,
+        }
+    )
+}
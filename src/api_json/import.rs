@@ -0,0 +1,146 @@
+use rouille::{Request, Response};
+use schemars::{JsonSchema};
+use serde::{Deserialize};
+
+use completion_tracker_lib::{
+    TrackerDBs,
+    db_link::{ConnectionHolder},
+    imports::{
+        ImportReport,
+        my_anime_list,
+        csv::{self, CsvColumnMapping, CsvPreset},
+        narou,
+        wikipedia,
+    },
+    sources::source_types::{Lang},
+};
+
+use crate::api_json::{APIResult, bad_input, error_response, unknown_tracker};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct MyAnimeListImport {
+    /// The contents of animelist.xml or mangalist.xml
+    xml: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CsvImport {
+    csv: String,
+    /// Either a preset or a mapping needs to be given. The mapping wins if both are.
+    #[serde(default)]
+    preset: Option<CsvPreset>,
+    #[serde(default)]
+    mapping: Option<CsvColumnMapping>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(rename = "WikipediaImportRequest")]
+pub struct WikipediaImport {
+    /// The saved page, as wikitext or HTML
+    page: String,
+    /// The language of the page
+    lang: Lang,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(rename = "NarouImportRequest")]
+pub struct NarouImport {
+    /// Any page of the novel
+    url: String,
+    /// The API's JSON for the novel, or the HTML of its table of contents
+    data: String,
+}
+
+pub fn my_anime_list_request(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs,
+tracker_name: &str, req: &Request) -> Response {
+    let tracker_db = match tracker_dbs.get(tracker_name) {
+        Some(tracker_db) => tracker_db,
+        None => return unknown_tracker(tracker_name),
+    };
+    let list: MyAnimeListImport = match rouille::input::json_input(req) {
+        Ok(list) => list,
+        Err(e) => return bad_input(format!("Failed to make a MyAnimeListImport: {}", e)),
+    };
+    let report = match my_anime_list::import(sources_db, tracker_db, &list.xml) {
+        Ok(report) => report,
+        Err(e) => return error_response(e.context("Failed to import the MyAnimeList list")),
+    };
+
+    report_response("MyAnimeList", tracker_name, report)
+}
+
+pub fn csv_request(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs, tracker_name: &str,
+req: &Request) -> Response {
+    let tracker_db = match tracker_dbs.get(tracker_name) {
+        Some(tracker_db) => tracker_db,
+        None => return unknown_tracker(tracker_name),
+    };
+    let csv_import: CsvImport = match rouille::input::json_input(req) {
+        Ok(csv_import) => csv_import,
+        Err(e) => return bad_input(format!("Failed to make a CsvImport: {}", e)),
+    };
+    let mapping = match (csv_import.mapping, csv_import.preset) {
+        (Some(mapping), _) => mapping,
+        (None, Some(preset)) => preset.mapping(),
+        (None, None) => return bad_input(
+            "Either a preset or a mapping is needed for the CSV columns"),
+    };
+    let report = match csv::import(sources_db, tracker_db, &csv_import.csv, &mapping) {
+        Ok(report) => report,
+        Err(e) => return error_response(e.context("Failed to import the CSV")),
+    };
+
+    report_response("CSV", tracker_name, report)
+}
+
+/// Every import into a tracker is logged the same way
+fn report_response(import_name: &str, tracker_name: &str, report: ImportReport) -> Response {
+    println!("{} import into {}:\n{}", import_name, tracker_name, report);
+    Response::json(
+        &APIResult {
+            success: true,
+            data: report,
+        }
+    )
+}
+
+/// Nothing gets created, so the SourceCreator can be looked over before it's sent back to create
+pub fn wikipedia_request(sources_db: &ConnectionHolder, req: &Request) -> Response {
+    let wikipedia_import: WikipediaImport = match rouille::input::json_input(req) {
+        Ok(wikipedia_import) => wikipedia_import,
+        Err(e) => return bad_input(format!("Failed to make a WikipediaImport: {}", e)),
+    };
+    let import = match wikipedia::import(sources_db, &wikipedia_import.page, wikipedia_import.lang,
+        wikipedia_import.url.as_deref()) {
+        Ok(import) => import,
+        Err(e) => return error_response(e.context("Failed to read the Wikipedia page")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: import,
+        }
+    )
+}
+
+/// Like a Wikipedia import, this only reads the novel
+pub fn narou_request(sources_db: &ConnectionHolder, req: &Request) -> Response {
+    let narou_import: NarouImport = match rouille::input::json_input(req) {
+        Ok(narou_import) => narou_import,
+        Err(e) => return bad_input(format!("Failed to make a NarouImport: {}", e)),
+    };
+    let import = match narou::import(sources_db, &narou_import.url, &narou_import.data) {
+        Ok(import) => import,
+        Err(e) => return error_response(e.context("Failed to read the novel")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: import,
+        }
+    )
+}
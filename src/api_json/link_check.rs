@@ -0,0 +1,43 @@
+use rouille::{Request, Response};
+
+use completion_tracker_lib::{
+    db_link::{ConnectionHolder},
+    link_check::{self, HttpClient, LinkCheckOptions},
+};
+
+use crate::api_json::{APIResult, bad_input, error_response};
+
+/// Checks every link before responding, so this can take a while
+pub fn check_request(sources_db: &ConnectionHolder, client: &dyn HttpClient, req: &Request)
+-> Response {
+    let options: LinkCheckOptions = match rouille::input::json_input(req) {
+        Ok(options) => options,
+        Err(e) => return bad_input(format!("Failed to make the LinkCheckOptions: {}", e)),
+    };
+    let summary = match link_check::check_links(sources_db, client, &options) {
+        Ok(summary) => summary,
+        Err(e) => return error_response(e.context("Failed to check the links")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: summary,
+        }
+    )
+}
+
+/// The items with a link that was broken when it was last checked
+pub fn broken_request(sources_db: &ConnectionHolder) -> Response {
+    let items = match link_check::broken_links(sources_db) {
+        Ok(items) => items,
+        Err(e) => return error_response(e.context("Failed to read the broken links")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: items,
+        }
+    )
+}
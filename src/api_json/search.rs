@@ -1,33 +1,25 @@
 use rouille::{Request, Response};
 
 use completion_tracker_lib::{
+    TrackerDBs,
     db_link::{ConnectionHolder},
     sources::api::search::{
-        SearchQuery, SearchResults,
-        search as api_search
+        SearchQuery, SearchResults, SuggestQuery, Suggestion,
+        search as api_search, suggest as api_suggest,
     },
 };
 
-use crate::api_json::{APIResult};
+use crate::api_json::{APIResult, bad_input, error_response};
 
-pub fn search(sources_db: &ConnectionHolder, req: &Request) -> Response {
+pub fn search(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs, req: &Request)
+-> Response {
     let search_query: SearchQuery = match rouille::input::json_input(req) {
         Ok(search_query) => search_query,
-        Err(e) => return Response::json(
-            &APIResult {
-                success: false,
-                data: format!("Failed to make a SearchQuery: {}", e),
-            }
-        ),
+        Err(e) => return bad_input(format!("Failed to make a SearchQuery: {}", e)),
     };
-    let search_results: SearchResults = match api_search(&sources_db, search_query) {
+    let search_results: SearchResults = match api_search(&sources_db, tracker_dbs, search_query) {
         Ok(search_results) => search_results,
-        Err(e) => return Response::json(
-            &APIResult {
-                success: false,
-                data: format!("Failed to get any search results: {}", e),
-            }
-        ),
+        Err(e) => return error_response(e.context("Failed to get any search results")),
     };
 
     Response::json(
@@ -37,3 +29,21 @@ pub fn search(sources_db: &ConnectionHolder, req: &Request) -> Response {
         }
     )
 }
+
+pub fn suggest(sources_db: &ConnectionHolder, req: &Request) -> Response {
+    let suggest_query: SuggestQuery = match rouille::input::json_input(req) {
+        Ok(suggest_query) => suggest_query,
+        Err(e) => return bad_input(format!("Failed to make a SuggestQuery: {}", e)),
+    };
+    let suggestions: Vec<Suggestion> = match api_suggest(sources_db, suggest_query) {
+        Ok(suggestions) => suggestions,
+        Err(e) => return error_response(e.context("Failed to get any suggestions")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: suggestions,
+        }
+    )
+}
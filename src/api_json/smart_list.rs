@@ -0,0 +1,93 @@
+use rouille::{Request, Response};
+
+use completion_tracker_lib::{
+    TrackerDBs,
+    db_link::{ConnectionHolder},
+    tracking::smart_list::{
+        self,
+        SmartListCreator, SmartListSelector,
+    },
+};
+
+use crate::api_json::{APIResult, bad_input, error_response, unknown_tracker};
+
+pub fn create_request(tracker_dbs: &TrackerDBs, tracker_name: &str, req: &Request) -> Response {
+    let tracker_db = match tracker_dbs.get(tracker_name) {
+        Some(tracker_db) => tracker_db,
+        None => return unknown_tracker(tracker_name),
+    };
+    let creator: SmartListCreator = match rouille::input::json_input(req) {
+        Ok(creator) => creator,
+        Err(e) => return bad_input(format!("Failed to make a SmartListCreator: {}", e)),
+    };
+    let id = match smart_list::create(tracker_db, creator) {
+        Ok(id) => id,
+        Err(e) => return error_response(e.context("Failed to create a new Smart List")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: id,
+        }
+    )
+}
+
+pub fn read_all_request(tracker_dbs: &TrackerDBs, tracker_name: &str, _req: &Request)
+-> Response {
+    let tracker_db = match tracker_dbs.get(tracker_name) {
+        Some(tracker_db) => tracker_db,
+        None => return unknown_tracker(tracker_name),
+    };
+    let smart_lists = match smart_list::read_all(tracker_db) {
+        Ok(smart_lists) => smart_lists,
+        Err(e) => return error_response(e.context("Failed to read the Smart Lists")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: smart_lists,
+        }
+    )
+}
+
+pub fn run_request(sources_db: &ConnectionHolder, tracker_dbs: &TrackerDBs, tracker_name: &str,
+req: &Request) -> Response {
+    let selector: SmartListSelector = match rouille::input::json_input(req) {
+        Ok(selector) => selector,
+        Err(e) => return bad_input(format!("Failed to make a SmartListSelector: {}", e)),
+    };
+    let search_results = match smart_list::run(sources_db, tracker_dbs, tracker_name, selector) {
+        Ok(search_results) => search_results,
+        Err(e) => return error_response(e.context("Failed to run the Smart List")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: search_results,
+        }
+    )
+}
+
+pub fn delete_request(tracker_dbs: &TrackerDBs, tracker_name: &str, req: &Request) -> Response {
+    let tracker_db = match tracker_dbs.get(tracker_name) {
+        Some(tracker_db) => tracker_db,
+        None => return unknown_tracker(tracker_name),
+    };
+    let selector: SmartListSelector = match rouille::input::json_input(req) {
+        Ok(selector) => selector,
+        Err(e) => return bad_input(format!("Failed to make a SmartListSelector: {}", e)),
+    };
+    if let Err(e) = smart_list::delete(tracker_db, selector) {
+        return error_response(e.context("Failed to delete the Smart List"));
+    }
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: (),
+        }
+    )
+}
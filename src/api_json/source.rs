@@ -0,0 +1,68 @@
+use rouille::{Request, Response};
+use schemars::{JsonSchema};
+use serde::{Deserialize};
+
+use completion_tracker_lib::{
+    db_link::{ConnectionHolder},
+    sources::{
+        api::source::{self, SourceCreator},
+        source_types::link_types,
+    },
+};
+
+use crate::api_json::{APIResult, bad_input, error_response};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ExternalIdFinder {
+    /// The name of the link type, like "MyAnimeList"
+    link_type: String,
+    external_id: String,
+}
+
+pub fn create_request(sources_db: &ConnectionHolder, req: &Request) -> Response {
+    let creator: SourceCreator = match rouille::input::json_input(req) {
+        Ok(creator) => creator,
+        Err(e) => return bad_input(format!("Failed to make a SourceCreator: {}", e)),
+    };
+    let id = match source::create(sources_db, creator) {
+        Ok(id) => id,
+        Err(e) => return error_response(e.context("Failed to create a new Source")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: id,
+        }
+    )
+}
+
+/// The IDs of the Sources that link to the item on the site
+pub fn find_by_external_id_request(sources_db: &ConnectionHolder, req: &Request) -> Response {
+    let finder: ExternalIdFinder = match rouille::input::json_input(req) {
+        Ok(finder) => finder,
+        Err(e) => return bad_input(format!("Failed to make an ExternalIdFinder: {}", e)),
+    };
+    let ids = match source::find_by_external_id(sources_db, &finder.link_type,
+        &finder.external_id) {
+        Ok(ids) => ids,
+        Err(e) => return error_response(e.context("Failed to find the Sources")),
+    };
+
+    Response::json(
+        &APIResult {
+            success: true,
+            data: ids,
+        }
+    )
+}
+
+/// Every site that a link can go to
+pub fn link_types_request() -> Response {
+    Response::json(
+        &APIResult {
+            success: true,
+            data: link_types::registry().definitions(),
+        }
+    )
+}
@@ -8,26 +8,16 @@ use completion_tracker_lib::{
     },
 };
 
-use crate::api_json::{APIResult};
+use crate::api_json::{APIResult, bad_input, error_response};
 
 pub fn create_request(sources_db: &ConnectionHolder, req: &Request) -> Response {
     let creator: UniverseTagCreator = match rouille::input::json_input(req) {
         Ok(creator) => creator,
-        Err(e) => return Response::json(
-            &APIResult {
-                success: false,
-                data: format!("Failed to make a UniverseTagCreator: {}", e),
-            }
-        ),
+        Err(e) => return bad_input(format!("Failed to make a UniverseTagCreator: {}", e)),
     };
     let id = match universe_tag::create(&sources_db, creator) {
         Ok(id) => id,
-        Err(e) => return Response::json(
-            &APIResult {
-                success: false,
-                data: format!("Failed to create a new Universe Tag: {}", e),
-            }
-        ),
+        Err(e) => return error_response(e.context("Failed to create a new Universe Tag")),
     };
 
     Response::json(
@@ -41,12 +31,7 @@ pub fn create_request(sources_db: &ConnectionHolder, req: &Request) -> Response
 pub fn read_root_request(sources_db: &ConnectionHolder, _req: &Request) -> Response {
     let root_ids = match universe_tag::read_root_level_ids(&sources_db) {
         Ok(root_ids) => root_ids,
-        Err(e) => return Response::json(
-            &APIResult {
-                success: false,
-                data: format!("Failed to read the root Universe Tag IDs: {}", e),
-            }
-        ),
+        Err(e) => return error_response(e.context("Failed to read the root Universe Tag IDs")),
     };
 
     Response::json(
@@ -60,21 +45,11 @@ pub fn read_root_request(sources_db: &ConnectionHolder, _req: &Request) -> Respo
 pub fn read_request(sources_db: &ConnectionHolder, req: &Request) -> Response {
     let reader: UniverseTagReader = match rouille::input::json_input(req) {
         Ok(reader) => reader,
-        Err(e) => return Response::json(
-            &APIResult {
-                success: false,
-                data: format!("Failed to make a UniverseTagReader: {}", e),
-            }
-        ),
+        Err(e) => return bad_input(format!("Failed to make a UniverseTagReader: {}", e)),
     };
     let read_result = match universe_tag::read_list(&sources_db, reader) {
         Ok(read_result) => read_result,
-        Err(e) => return Response::json(
-            &APIResult {
-                success: false,
-                data: format!("Failed to read a Universe Tag: {}", e),
-            }
-        ),
+        Err(e) => return error_response(e.context("Failed to read a Universe Tag")),
     };
 
     Response::json(
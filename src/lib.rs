@@ -0,0 +1,168 @@
+//! The server, apart from where it gets its settings.
+//! main.rs starts it from the runInfo.json, and the tests can start it with their own DBs.
+
+pub mod admin;
+mod api_json;
+mod openapi;
+pub mod run_info;
+mod static_server;
+
+use std::path::{PathBuf};
+
+use rouille::{Request, Response, router};
+
+use completion_tracker_lib::{
+    TrackerDBs,
+    db_link::{ConnectionHolder},
+    exports::graph::{GraphFormat},
+    link_check::{UreqClient},
+};
+
+use crate::static_server::{StaticServer};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Everything that the routes need
+pub struct App {
+    sources_db: ConnectionHolder,
+    tracker_dbs: TrackerDBs,
+    /// Where the static site export goes
+    site_folder: PathBuf,
+    link_client: UreqClient,
+    openapi_json: String,
+}
+impl App {
+    pub fn new(sources_db: ConnectionHolder, tracker_dbs: TrackerDBs, site_folder: PathBuf) -> App {
+        App {
+            sources_db,
+            tracker_dbs,
+            site_folder,
+            link_client: UreqClient::new(),
+            openapi_json: openapi::spec_json(),
+        }
+    }
+
+    /// Sends the request to its route
+    pub fn handle(&self, req: &Request) -> Response {
+        router!(req,
+            (GET) (/jsbundle) => { StaticServer.serve_bundle_js() },
+
+            (POST) (/api/search) => {
+                api_json::search(&self.sources_db, &self.tracker_dbs, req)
+            },
+            (POST) (/api/search/suggest) => {
+                api_json::suggest(&self.sources_db, req)
+            },
+
+            // The router can't match a "." so the file names are checked here
+            (GET) (/api/{file_name: String}) => {
+                match file_name.as_str() {
+                    "openapi.json" =>
+                        Response::from_data("application/json", self.openapi_json.clone()),
+                    "calendar.ics" => api_json::export::calendar_request(&self.sources_db, req),
+                    "graph.dot" =>
+                        api_json::export::graph_request(&self.sources_db, GraphFormat::Dot, req),
+                    "graph.graphml" =>
+                        api_json::export::graph_request(&self.sources_db, GraphFormat::GraphML,
+                            req),
+                    _ => Response::empty_404(),
+                }
+            },
+
+            (POST) (/api/site/export) => {
+                api_json::export::site_request(&self.sources_db, &self.tracker_dbs,
+                    &self.site_folder,
+                    StaticServer.site_css(), req)
+            },
+
+            (POST) (/api/links/check) => {
+                api_json::link_check::check_request(&self.sources_db, &self.link_client, req)
+            },
+            (POST) (/api/links/broken/read) => {
+                api_json::link_check::broken_request(&self.sources_db)
+            },
+            (POST) (/api/linkTypes/read) => {
+                api_json::source::link_types_request()
+            },
+            (POST) (/api/sources/findByExternalId) => {
+                api_json::source::find_by_external_id_request(&self.sources_db, req)
+            },
+            (POST) (/api/source/create) => {
+                api_json::source::create_request(&self.sources_db, req)
+            },
+            (POST) (/api/source/import/wikipedia) => {
+                api_json::import::wikipedia_request(&self.sources_db, req)
+            },
+            (POST) (/api/source/import/narou) => {
+                api_json::import::narou_request(&self.sources_db, req)
+            },
+
+            (POST) (/api/universeTag/create) => {
+                api_json::universe_tag::create_request(&self.sources_db, req)
+            },
+            (POST) (/api/universeTags/read) => {
+                api_json::universe_tag::read_request(&self.sources_db, req)
+            },
+            (POST) (/api/universeTags/readRoot) => {
+                api_json::universe_tag::read_root_request(&self.sources_db, req)
+            },
+
+            (POST) (/api/tracker/{tracker_name: String}/smartList/create) => {
+                api_json::smart_list::create_request(&self.tracker_dbs, &tracker_name, req)
+            },
+            (POST) (/api/tracker/{tracker_name: String}/smartLists/read) => {
+                api_json::smart_list::read_all_request(&self.tracker_dbs, &tracker_name, req)
+            },
+            (POST) (/api/tracker/{tracker_name: String}/smartList/run) => {
+                api_json::smart_list::run_request(&self.sources_db, &self.tracker_dbs,
+                    &tracker_name, req)
+            },
+            (POST) (/api/tracker/{tracker_name: String}/smartList/delete) => {
+                api_json::smart_list::delete_request(&self.tracker_dbs, &tracker_name, req)
+            },
+            (POST) (/api/tracker/{tracker_name: String}/import/myAnimeList) => {
+                api_json::import::my_anime_list_request(&self.sources_db, &self.tracker_dbs,
+                    &tracker_name, req)
+            },
+            (POST) (/api/tracker/{tracker_name: String}/import/csv) => {
+                api_json::import::csv_request(&self.sources_db, &self.tracker_dbs, &tracker_name,
+                    req)
+            },
+            // The router can't match a "." so the file names are checked here
+            (GET) (/api/tracker/{tracker_name: String}/{file_name: String}) => {
+                match file_name.as_str() {
+                    "export.csv" =>
+                        api_json::export::csv_request(&self.sources_db, &self.tracker_dbs,
+                            &tracker_name, req),
+                    "feed.atom" =>
+                        api_json::export::feed_request(&self.sources_db, &self.tracker_dbs,
+                            &tracker_name, req),
+                    _ => Response::empty_404(),
+                }
+            },
+            (GET) (/api/tracker/{tracker_name: String}/export/aniList/{media_type: String}) => {
+                api_json::export::anilist_request(&self.sources_db, &self.tracker_dbs,
+                    &tracker_name, &media_type, req)
+            },
+            (GET) (/api/tracker/{tracker_name: String}/export/myAnimeList/{media_type: String}) => {
+                api_json::export::my_anime_list_request(&self.sources_db, &self.tracker_dbs,
+                    &tracker_name, &media_type, req)
+            },
+
+            _ => {
+                if req.method() == "GET" {
+                    // Send the main page, inserting the path after the "#"
+                    //  This is good for saved pages (bookmarks or otherwise)
+                    StaticServer.serve_main_html()
+                } else {
+                    // The only time we should ever hit this is if the frontend misses a call
+                    //  or somebody calls this directly
+                    // In both cases, a normal 404 should be enough to tell them to
+                    //  take their business elsewhere
+                    Response::empty_404()
+                }
+            },
+            // All the other real API calls
+        )
+    }
+}
@@ -1,56 +1,50 @@
-mod api_json;
-mod run_info;
-mod static_server;
+use std::{
+    env,
+    io,
+    process,
+};
 
-use rouille::{Response, Server, router};
+use rouille::{Server};
 
-use crate::{
-    run_info::{RunInfo},
-    static_server::{StaticServer},
+use completion_tracker::{
+    App,
+    admin,
+    run_info::{Overrides, RunInfo},
+};
+use completion_tracker_lib::{
+    error::{Error},
+    sources::source_types::link_types,
 };
 
-const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}: {}", e.code(), e);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (overrides, command) = Overrides::from_args(&args)?;
+    let overrides = overrides.or_env(|name| env::var(name).ok())?;
+    let run_info = RunInfo::load(&overrides)?;
+    link_types::init_registry(run_info.link_type_registry()?)?;
 
-fn main() -> Result<(), String> {
-    let run_info = RunInfo::new_default()
-        .map_err(|e| format!("Error in the runInfo.json: {}", e))?;
-    let trackers = run_info.trackers()?;
+    // Anything after the options is an admin command, which runs without the server
+    if !command.is_empty() {
+        return admin::run(&run_info, command, &mut io::stdout());
+    }
 
-    let sources_db = completion_tracker_lib::init_source_db()?;
+    let trackers = run_info.trackers()?;
+    let site_folder = run_info.site_folder();
+    let sources_db = completion_tracker_lib::open_source_db(run_info.sources_db_file())?;
+    let tracker_dbs = completion_tracker_lib::init_tracker_dbs(&trackers)?;
+    let app = App::new(sources_db, tracker_dbs, site_folder);
 
     // Start up the server
-    let server = Server::new(run_info.socket_addr(), move |req| router!(req,
-        (GET) (/jsbundle) => { StaticServer.serve_bundle_js() },
-
-        (POST) (/api/search) => {
-            api_json::search(&sources_db, req)
-        },
-
-        (POST) (/api/universeTag/create) => {
-            api_json::universe_tag::create_request(&sources_db, req)
-        },
-        (POST) (/api/universeTags/read) => {
-            api_json::universe_tag::read_request(&sources_db, req)
-        },
-        (POST) (/api/universeTags/readRoot) => {
-            api_json::universe_tag::read_root_request(&sources_db, req)
-        },
-
-        _ => {
-            if req.method() == "GET" {
-                // Send the main page, inserting the path after the "#"
-                //  This is good for saved pages (bookmarks or otherwise)
-                StaticServer.serve_main_html()
-            } else {
-                // The only time we should ever hit this is if the frontend misses a call
-                //  or somebody calls this directly
-                // In both cases, a normal 404 should be enough to tell them to
-                //  take their business elsewhere
-                Response::empty_404()
-            }
-        },
-        // All the other real API calls
-    )).expect("Failed to create the server");
+    let server = Server::new(run_info.socket_addr(), move |req| app.handle(req))
+        .map_err(|e| Error::Io(format!("Failed to start the server on {}: {}",
+            run_info.socket_addr(), e)))?;
     // Set the fixed pool size to something small since this is only running locally
     let server = server.pool_size(10);
 
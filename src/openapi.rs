@@ -0,0 +1,325 @@
+//! The OpenAPI description of the HTTP API, made from the same types that the routes read and send.
+//! A copy is kept in assets/openapi.json so that changes to it show up in review.
+//! The tests make sure that the copy is up to date, and that every route in lib.rs is in it.
+
+use schemars::{
+    JsonSchema,
+    gen::{SchemaGenerator, SchemaSettings},
+};
+use serde_json::{Map, Value, json};
+
+use completion_tracker_lib::{
+    exports::{
+        anilist::{MediaListCollection},
+        graph::{Subgraph},
+        site::{SiteReport},
+    },
+    imports::{self, narou, wikipedia},
+    link_check::{ItemBrokenLinks, LinkCheckOptions, LinkCheckSummary},
+    simple_enum::{SimpleEnum},
+    sources::{
+        api::{
+            search::{SearchQuery, SearchResults, SuggestQuery, Suggestion},
+            source::{SourceCreator},
+            universe_tag::{UniverseTagCreator, UniverseTagReadResult, UniverseTagReader},
+        },
+        source_types::{Lang, SourceType, link_types::{LinkTypeDefinition}},
+    },
+    tracking::smart_list::{SmartList, SmartListCreator, SmartListSelector},
+};
+
+use crate::api_json::{
+    APIError, APIResult,
+    export::{SiteExport},
+    import::{CsvImport, MyAnimeListImport, NarouImport, WikipediaImport},
+    source::{ExternalIdFinder},
+};
+
+/// Builds up the paths, keeping the schemas of every type that they use
+struct SpecBuilder {
+    gen: SchemaGenerator,
+    paths: Map<String, Value>,
+}
+impl SpecBuilder {
+    fn new() -> SpecBuilder {
+        SpecBuilder {
+            gen: SchemaSettings::openapi3().into_generator(),
+            paths: Map::new(),
+        }
+    }
+
+    /// A POST route that reads a T and sends back an APIResult with an R
+    fn json_route<T: JsonSchema, R: JsonSchema>(&mut self, path: &str, summary: &str) {
+        let request = self.gen.subschema_for::<T>();
+        self.post_route::<R>(path, summary, json!(request));
+    }
+
+    /// A POST route that doesn't read anything from the body, so it's sent {}
+    fn read_route<R: JsonSchema>(&mut self, path: &str, summary: &str) {
+        self.post_route::<R>(path, summary, json!({ "type": "object" }));
+    }
+
+    fn post_route<R: JsonSchema>(&mut self, path: &str, summary: &str, request: Value) {
+        let response = self.gen.subschema_for::<APIResult<R>>();
+        self.add(path, "post", json!({
+            "summary": summary,
+            "parameters": path_parameters(path),
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": request } },
+            },
+            "responses": {
+                "200": {
+                    "description": "The call worked",
+                    "content": { "application/json": { "schema": response } },
+                },
+                "default": { "$ref": "#/components/responses/Error" },
+            },
+        }));
+    }
+
+    /// A GET route for a file. The query parameters are (name, description, schema).
+    fn file_route(&mut self, path: &str, summary: &str, content_type: &str,
+    query: Vec<(&str, &str, Value)>) {
+        let schema = json!({ "type": "string" });
+        self.file_route_with_schema(path, summary, content_type, query, schema);
+    }
+
+    fn file_route_with_schema(&mut self, path: &str, summary: &str, content_type: &str,
+    query: Vec<(&str, &str, Value)>, schema: Value) {
+        let mut parameters = path_parameters(path);
+        parameters.extend(query.into_iter().map(|(name, description, schema)| json!({
+            "name": name,
+            "in": "query",
+            "required": false,
+            "description": description,
+            "schema": schema,
+        })));
+        self.add(path, "get", json!({
+            "summary": summary,
+            "parameters": parameters,
+            "responses": {
+                "200": {
+                    "description": "The file",
+                    "content": { content_type: { "schema": schema } },
+                },
+                "default": { "$ref": "#/components/responses/Error" },
+            },
+        }));
+    }
+
+    fn add(&mut self, path: &str, method: &str, operation: Value) {
+        let item = self.paths.entry(path).or_insert_with(|| json!({}));
+        item[method] = operation;
+    }
+
+    fn schema_of<T: JsonSchema>(&mut self) -> Value {
+        json!(self.gen.subschema_for::<T>())
+    }
+
+    fn build(mut self) -> Value {
+        let error = self.gen.subschema_for::<APIError>();
+        json!({
+            "openapi": "3.0.3",
+            "info": {
+                "title": "Completion Tracker",
+                "version": crate::VERSION,
+                "description": "Every POST route takes JSON and sends back an APIResult. \
+                    When a call fails, the status and the code say what kind of error it was.",
+            },
+            "paths": self.paths,
+            "components": {
+                "schemas": self.gen.take_definitions(),
+                "responses": {
+                    "Error": {
+                        "description": "400 for bad input, 404 when something doesn't exist, \
+                            409 for a conflict with what's already there, and 500 for the rest",
+                        "content": { "application/json": { "schema": error } },
+                    },
+                },
+            },
+        })
+    }
+}
+
+/// Every {name} in the path is a string parameter
+fn path_parameters(path: &str) -> Vec<Value> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| json!({
+            "name": name,
+            "in": "path",
+            "required": true,
+            "schema": { "type": "string" },
+        }))
+        .collect()
+}
+
+/// The whole description, made from the types
+pub fn spec() -> Value {
+    let mut spec = SpecBuilder::new();
+    let lang = spec.schema_of::<Lang>();
+    let lang_param = || ("lang", "The language for the names. English when it's missing.",
+        lang.clone());
+
+    spec.json_route::<SearchQuery, SearchResults>("/api/search", "Searches everything");
+    spec.json_route::<SuggestQuery, Vec<Suggestion>>("/api/search/suggest",
+        "Suggests names while typing");
+
+    spec.file_route("/api/openapi.json", "This description", "application/json", Vec::new());
+    let source_type = spec.schema_of::<SourceType>();
+    spec.file_route("/api/calendar.ics", "The release dates as an iCalendar", "text/calendar",
+        vec![
+            ("universeTag", "Only the Sources in the Universe Tag",
+                json!({ "type": "integer", "format": "uint64" })),
+            ("sourceType", "Only the Sources of the type", source_type),
+            lang_param(),
+        ]);
+    let subgraphs = Subgraph::all().iter().map(|subgraph| subgraph.as_str())
+        .collect::<Vec<_>>().join(", ");
+    let subgraphs_description = format!(
+        "A comma separated list of {}. All of them when it's missing.", subgraphs);
+    for (path, content_type) in [
+        ("/api/graph.dot", "text/vnd.graphviz"),
+        ("/api/graph.graphml", "application/graphml+xml"),
+    ].iter() {
+        spec.file_route(path, "The relations between the items as a graph", content_type, vec![
+            ("subgraphs", subgraphs_description.as_str(), json!({ "type": "string" })),
+            lang_param(),
+        ]);
+    }
+
+    spec.json_route::<SiteExport, SiteReport>("/api/site/export",
+        "Writes the static site into the tracking folder");
+    spec.json_route::<LinkCheckOptions, LinkCheckSummary>("/api/links/check",
+        "Checks every related link");
+    spec.read_route::<Vec<ItemBrokenLinks>>("/api/links/broken/read",
+        "The items with a link that was broken the last time it was checked");
+    spec.read_route::<Vec<LinkTypeDefinition>>("/api/linkTypes/read",
+        "The sites that a related link can point to");
+    spec.json_route::<ExternalIdFinder, Vec<u64>>("/api/sources/findByExternalId",
+        "The IDs of the Sources that link to the item on the site");
+    spec.json_route::<SourceCreator, u64>("/api/source/create", "Creates a Source");
+    spec.json_route::<WikipediaImport, wikipedia::WikipediaImport>("/api/source/import/wikipedia",
+        "Reads a Source from a Wikipedia page");
+    spec.json_route::<NarouImport, narou::NarouImport>("/api/source/import/narou",
+        "Reads a Source from a Shousetsuka ni Narou novel");
+
+    spec.json_route::<UniverseTagCreator, u64>("/api/universeTag/create",
+        "Creates a Universe Tag");
+    spec.json_route::<UniverseTagReader, Vec<UniverseTagReadResult>>("/api/universeTags/read",
+        "Reads the Universe Tags");
+    spec.read_route::<Vec<u64>>("/api/universeTags/readRoot",
+        "The IDs of the Universe Tags without a parent");
+
+    spec.json_route::<SmartListCreator, u64>("/api/tracker/{tracker_name}/smartList/create",
+        "Creates a Smart List");
+    spec.read_route::<Vec<SmartList>>("/api/tracker/{tracker_name}/smartLists/read",
+        "Reads every Smart List of the tracker");
+    spec.json_route::<SmartListSelector, SearchResults>("/api/tracker/{tracker_name}/smartList/run",
+        "Runs the search of a Smart List");
+    spec.json_route::<SmartListSelector, ()>("/api/tracker/{tracker_name}/smartList/delete",
+        "Deletes a Smart List");
+    spec.json_route::<MyAnimeListImport, imports::ImportReport>(
+        "/api/tracker/{tracker_name}/import/myAnimeList", "Imports a MyAnimeList export");
+    spec.json_route::<CsvImport, imports::ImportReport>("/api/tracker/{tracker_name}/import/csv",
+        "Imports a CSV, with a mapping or one of the presets");
+
+    spec.file_route("/api/tracker/{tracker_name}/export.csv", "The tracker as a CSV", "text/csv",
+        vec![
+            lang_param(),
+            ("bom", "Puts a byte order mark at the start when it's true",
+                json!({ "type": "boolean" })),
+        ]);
+    spec.file_route("/api/tracker/{tracker_name}/feed.atom",
+        "The latest changes to the tracker as an Atom feed", "application/atom+xml",
+        vec![lang_param()]);
+    let collection = spec.schema_of::<MediaListCollection>();
+    spec.file_route_with_schema("/api/tracker/{tracker_name}/export/aniList/{media_type}",
+        "The anime or manga of the tracker for AniList", "application/json", Vec::new(),
+        collection);
+    spec.file_route("/api/tracker/{tracker_name}/export/myAnimeList/{media_type}",
+        "The anime or manga of the tracker as a MyAnimeList export", "application/xml",
+        Vec::new());
+
+    spec.build()
+}
+
+/// The description as it gets served and saved
+pub fn spec_json() -> String {
+    serde_json::to_string_pretty(&spec()).expect("Failed to write the OpenAPI description") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet};
+
+    use super::*;
+
+    /// Where the copy is, from the root of the repo
+    const SPEC_FILE: &str = "assets/openapi.json";
+
+    /// Set UPDATE_OPENAPI=1 to write the new description into the file instead of failing
+    #[test]
+    fn spec_file_is_up_to_date() {
+        let spec = spec_json();
+        let file = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(SPEC_FILE);
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(&file, &spec).unwrap();
+        }
+        let saved = std::fs::read_to_string(&file).unwrap_or_default();
+        assert!(saved == spec, "{} is out of date with the API types. \
+            Run the tests with UPDATE_OPENAPI=1 to update it.", SPEC_FILE);
+    }
+
+    /// The routes in lib.rs, with the file names of the routes that match on them
+    fn main_routes() -> BTreeSet<String> {
+        let main = include_str!("lib.rs");
+        let mut routes = BTreeSet::new();
+        let mut lines = main.lines();
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            let route = line.strip_prefix("(POST) (").or_else(|| line.strip_prefix("(GET) ("));
+            let route = match route {
+                Some(route) => &route[..route.find(')').unwrap()],
+                None => continue,
+            };
+            if !route.starts_with("/api/") {
+                continue;
+            }
+            // {name: Type} becomes {name}
+            let route = route.split('/')
+                .map(|segment| match segment.find(':') {
+                    Some(index) if segment.starts_with('{') => format!("{}}}", &segment[..index]),
+                    _ => segment.to_string(),
+                })
+                .collect::<Vec<_>>().join("/");
+
+            match route.strip_suffix("/{file_name}") {
+                Some(folder) => {
+                    // The file names are in the match right after
+                    for line in lines.by_ref() {
+                        let line = line.trim();
+                        if line.starts_with("_ =>") {
+                            break;
+                        }
+                        if let Some(file_name) = line.strip_prefix('"') {
+                            let file_name = &file_name[..file_name.find('"').unwrap()];
+                            routes.insert(format!("{}/{}", folder, file_name));
+                        }
+                    }
+                },
+                None => { routes.insert(route); },
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn every_route_is_described() {
+        let spec = spec();
+        let described: BTreeSet<String> = spec["paths"].as_object().unwrap().keys()
+            .cloned().collect();
+        assert_eq!(main_routes(), described);
+    }
+}
@@ -1,34 +1,352 @@
 use std::{
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
     path::{Path, PathBuf},
+    str::{FromStr},
 };
-use serde::{Deserialize};
+use serde::de::{DeserializeOwned};
+use serde_json::{Map, Value};
 
 use completion_tracker_lib::{
+    error::{Error, ResultExt},
+    sources::source_types::link_types::{LinkTypeRegistry},
     tracking::{Tracker, TrackingInfo},
     utils,
 };
 
-const RUN_INFO_FILE: &'static str = "runInfo.json";
+const RUN_INFO_FILE: &str = "runInfo.json";
+/// In the tracking folder, next to the trackers' DBs
+const SOURCES_DB_FILE: &str = "sources.db";
+/// These are used when there isn't a runInfo.json
+const DEFAULT_PORT: u16 = 8080;
+const DEFAULT_TRACKING_FOLDER: &str = "tracking";
+
+/// The options that can be given on the command line (as "--port 8080") or in the environment.
+/// Each one's environment variable is its name in uppercase after this prefix,
+/// like COMPLETION_TRACKER_TRACKING_FOLDER.
+pub const OPTIONS: &[&str] = &["config", "port", "bind", "tracking-folder", "sources-db"];
+const ENV_PREFIX: &str = "COMPLETION_TRACKER_";
+
+pub fn env_var_name(option: &str) -> String {
+    format!("{}{}", ENV_PREFIX, option.to_uppercase().replace('-', "_"))
+}
+
+/// Settings that win over the ones in the config file
+#[derive(Default)]
+pub struct Overrides {
+    /// Another config file instead of the runInfo.json
+    pub config_file: Option<PathBuf>,
+    pub port: Option<u16>,
+    pub bind_address: Option<IpAddr>,
+    pub tracking_folder: Option<PathBuf>,
+    pub sources_db_file: Option<PathBuf>,
+}
+impl Overrides {
+    /// Takes the options from the front of the arguments (without the program name).
+    /// The rest of the arguments are given back, which is the admin command if there is one.
+    pub fn from_args(args: &[String]) -> Result<(Overrides, &[String]), Error> {
+        let mut overrides = Overrides::default();
+        let mut rest = args;
+        while let Some(name) = rest.first().and_then(|arg| arg.strip_prefix("--")) {
+            if !OPTIONS.contains(&name) {
+                return Err(Error::Validation(format!("--{} isn't an option", name)));
+            }
+            let value = rest.get(1)
+                .ok_or_else(|| Error::Validation(format!("--{} needs a value", name)))?;
+            overrides.set(name, value).context(format!("--{}", name))?;
+            rest = &rest[2..];
+        }
+        Ok( (overrides, rest) )
+    }
+
+    /// Fills in what wasn't on the command line from the environment variables.
+    /// The variable is looked up by its name, which is std::env::var outside of the tests.
+    pub fn or_env(self, var: impl Fn(&str) -> Option<String>) -> Result<Overrides, Error> {
+        let mut env_overrides = Overrides::default();
+        for option in OPTIONS {
+            let var_name = env_var_name(option);
+            if let Some(value) = var(&var_name) {
+                env_overrides.set(option, &value).context(&var_name)?;
+            }
+        }
+
+        Ok(Overrides {
+            config_file: self.config_file.or(env_overrides.config_file),
+            port: self.port.or(env_overrides.port),
+            bind_address: self.bind_address.or(env_overrides.bind_address),
+            tracking_folder: self.tracking_folder.or(env_overrides.tracking_folder),
+            sources_db_file: self.sources_db_file.or(env_overrides.sources_db_file),
+        })
+    }
+
+    fn set(&mut self, option: &str, value: &str) -> Result<(), Error> {
+        match option {
+            "config" => self.config_file = Some(PathBuf::from(value)),
+            "port" => self.port = Some(parse_value(value)?),
+            "bind" => self.bind_address = Some(parse_value(value)?),
+            "tracking-folder" => self.tracking_folder = Some(PathBuf::from(value)),
+            "sources-db" => self.sources_db_file = Some(PathBuf::from(value)),
+            _ => return Err(Error::Validation(format!("{} isn't an option", option))),
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: FromStr>(value: &str) -> Result<T, Error>
+where T::Err: std::fmt::Display {
+    value.parse()
+        .map_err(|e| Error::Validation(format!("{} can't be used: {}", value, e)))
+}
 
-#[derive(Deserialize)]
 pub struct RunInfo {
     port: u16,
+    /// Every available address by default (for non-local connections)
+    bind_address: IpAddr,
     local_tracking_folder: PathBuf,
     trackers: Vec<TrackingInfo>,
+    /// More link types for the built-in ones, in a JSON file
+    link_types_file: Option<PathBuf>,
+    /// Where the sources DB is kept, when it isn't in the tracking folder
+    sources_db_file: Option<PathBuf>,
+}
+impl Default for RunInfo {
+    fn default() -> RunInfo {
+        RunInfo {
+            port: DEFAULT_PORT,
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            local_tracking_folder: PathBuf::from(DEFAULT_TRACKING_FOLDER),
+            trackers: Vec::new(),
+            link_types_file: None,
+            sources_db_file: None,
+        }
+    }
 }
 impl RunInfo {
-    pub fn new_default() -> Result<RunInfo, String> { Self::new(RUN_INFO_FILE) }
-    pub fn new(info_path: impl AsRef<Path>) -> Result<RunInfo, String> {
-        utils::read_json_file(info_path)
+    /// The config file with the overrides on top of it.
+    /// The built-in defaults are used when there isn't a runInfo.json,
+    /// but a config file from the overrides has to be there.
+    pub fn load(overrides: &Overrides) -> Result<RunInfo, Error> {
+        Self::load_with_default(Path::new(RUN_INFO_FILE), overrides)
+    }
+
+    fn load_with_default(default_file: &Path, overrides: &Overrides) -> Result<RunInfo, Error> {
+        let mut run_info = match &overrides.config_file {
+            Some(file) => Self::read_file(file)?,
+            None if default_file.exists() => Self::read_file(default_file)?,
+            None => RunInfo::default(),
+        };
+
+        if let Some(port) = overrides.port {
+            run_info.port = port;
+        }
+        if let Some(bind_address) = overrides.bind_address {
+            run_info.bind_address = bind_address;
+        }
+        if let Some(tracking_folder) = &overrides.tracking_folder {
+            run_info.local_tracking_folder = tracking_folder.clone();
+        }
+        if let Some(sources_db_file) = &overrides.sources_db_file {
+            run_info.sources_db_file = Some(sources_db_file.clone());
+        }
+
+        run_info.validate()?;
+        Ok(run_info)
+    }
+
+    /// Only the config file, without any overrides
+    pub fn new(info_path: impl AsRef<Path>) -> Result<RunInfo, Error> {
+        Self::load(&Overrides {
+            config_file: Some(info_path.as_ref().to_path_buf()),
+            ..Overrides::default()
+        })
+    }
+
+    fn read_file(info_path: &Path) -> Result<RunInfo, Error> {
+        let info: Value = utils::read_json_file(info_path)
+            .with_context(|| format!("Failed to read {}", info_path.display()))?;
+        Self::from_json(info)
+            .with_context(|| format!("Error in {}", info_path.display()))
+    }
+
+    /// Every field is read on its own, so that an error can say which one is wrong
+    fn from_json(info: Value) -> Result<RunInfo, Error> {
+        let mut fields = match info {
+            Value::Object(fields) => fields,
+            _ => return Err(Error::Validation("The settings must be in a JSON object".to_string())),
+        };
+        let defaults = RunInfo::default();
+        let run_info = RunInfo {
+            port: take_field(&mut fields, "port")?.unwrap_or(defaults.port),
+            bind_address: take_field(&mut fields, "bind_address")?
+                .unwrap_or(defaults.bind_address),
+            local_tracking_folder: take_field(&mut fields, "local_tracking_folder")?
+                .unwrap_or(defaults.local_tracking_folder),
+            trackers: take_field(&mut fields, "trackers")?.unwrap_or_default(),
+            link_types_file: take_field(&mut fields, "link_types_file")?,
+            sources_db_file: take_field(&mut fields, "sources_db_file")?,
+        };
+
+        // A typo would otherwise quietly use the default
+        if let Some(name) = fields.keys().next() {
+            return Err(Error::Validation(
+                format!("{}: There isn't a setting with this name", name)));
+        }
+        Ok(run_info)
+    }
+
+    /// The errors start with the field's name, since they can come from the file or an override
+    fn validate(&self) -> Result<(), Error> {
+        if self.port == 0 {
+            return Err(Error::Validation("port: It must be from 1 to 65535".to_string()));
+        }
+        if self.local_tracking_folder.as_os_str().is_empty() {
+            return Err(Error::Validation("local_tracking_folder: It can't be empty".to_string()));
+        }
+        if self.local_tracking_folder.exists() && !self.local_tracking_folder.is_dir() {
+            return Err(Error::Validation(format!("local_tracking_folder: {} isn't a folder",
+                self.local_tracking_folder.display())));
+        }
+        if let Some(file) = &self.link_types_file {
+            if !file.is_file() {
+                return Err(Error::Validation(format!("link_types_file: {} isn't a file",
+                    file.display())));
+            }
+        }
+        if let Some(file) = &self.sources_db_file {
+            if file.as_os_str().is_empty() || file.is_dir() {
+                return Err(Error::Validation(format!("sources_db_file: {} can't be a DB file",
+                    file.display())));
+            }
+        }
+        Ok(())
     }
 
     pub fn socket_addr(&self) -> SocketAddr {
-        // Set up shop on every available address (for non-local connections)
-        SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, self.port))
+        SocketAddr::new(self.bind_address, self.port)
     }
 
-    pub fn trackers(&self) -> Result<Vec<Tracker>, String> {
-        Tracker::new_from_info(&self.local_tracking_folder, &self.trackers)
+    /// Where the static site export goes
+    pub fn site_folder(&self) -> PathBuf {
+        self.local_tracking_folder.join("site")
+    }
+
+    pub fn sources_db_file(&self) -> PathBuf {
+        match &self.sources_db_file {
+            Some(file) => file.clone(),
+            None => self.local_tracking_folder.join(SOURCES_DB_FILE),
+        }
+    }
+
+    pub fn link_type_registry(&self) -> Result<LinkTypeRegistry, Error> {
+        match &self.link_types_file {
+            Some(file) => LinkTypeRegistry::with_config_file(file).context("link_types_file"),
+            None => Ok(LinkTypeRegistry::built_in()),
+        }
+    }
+
+    pub fn trackers(&self) -> Result<Vec<Tracker>, Error> {
+        let trackers = Tracker::new_from_info(&self.local_tracking_folder, &self.trackers)
+            .context("trackers")?;
+        let sources_db_file = self.sources_db_file();
+        if let Some(tracker) = trackers.iter().find(|tracker| tracker.file() == sources_db_file) {
+            return Err(Error::Conflict(format!(
+                "sources_db_file: The tracker {} would use the same file", tracker.name())));
+        }
+        Ok(trackers)
+    }
+}
+
+/// Takes the field out, so the fields that are left over can be found.
+/// A null is the same as leaving it out.
+fn take_field<T: DeserializeOwned>(fields: &mut Map<String, Value>, name: &str)
+-> Result<Option<T>, Error> {
+    match fields.remove(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| Error::Validation(format!("{}: {}", name, e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::{json};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn error_message(result: Result<impl Sized, Error>) -> String {
+        match result {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn defaults_without_a_config_file() {
+        let overrides = Overrides {
+            port: Some(9000),
+            ..Overrides::default()
+        };
+        let run_info = RunInfo::load_with_default(Path::new("missingRunInfo.json"), &overrides)
+            .unwrap();
+        assert_eq!(run_info.socket_addr(), "0.0.0.0:9000".parse().unwrap());
+        assert_eq!(run_info.sources_db_file(), Path::new("tracking").join("sources.db"));
+
+        // It's only left out when nobody asked for it
+        let overrides = Overrides {
+            config_file: Some(PathBuf::from("missingRunInfo.json")),
+            ..Overrides::default()
+        };
+        assert!(matches!(RunInfo::load(&overrides), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn command_line_wins_over_the_environment() {
+        let args = args(&["--port", "9000", "--tracking-folder", "here", "search", "--port"]);
+        let (overrides, rest) = Overrides::from_args(&args).unwrap();
+        assert_eq!(rest, &args[4..]);
+
+        let overrides = overrides.or_env(|name| match name {
+            "COMPLETION_TRACKER_PORT" => Some("1".to_string()),
+            "COMPLETION_TRACKER_BIND" => Some("127.0.0.1".to_string()),
+            _ => None,
+        }).unwrap();
+        assert_eq!(overrides.port, Some(9000));
+        assert_eq!(overrides.bind_address, Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+        assert_eq!(overrides.tracking_folder, Some(PathBuf::from("here")));
+        assert!(overrides.sources_db_file.is_none());
+    }
+
+    #[test]
+    fn every_option_is_in_the_usage() {
+        for option in OPTIONS {
+            assert!(crate::admin::USAGE.contains(&format!("--{} ", option)), "{}", option);
+            assert!(crate::admin::USAGE.contains(&env_var_name(option)), "{}", option);
+        }
+    }
+
+    #[test]
+    fn errors_name_the_field() {
+        let message = error_message(RunInfo::from_json(json!({ "port": "eighty" })));
+        assert!(message.starts_with("port: "), "{}", message);
+        let message = error_message(RunInfo::from_json(json!({ "bind_address": "nowhere" })));
+        assert!(message.starts_with("bind_address: "), "{}", message);
+        let message = error_message(RunInfo::from_json(json!({ "prot": 8080 })));
+        assert!(message.starts_with("prot: "), "{}", message);
+
+        let message = error_message(RunInfo::from_json(json!({ "port": 0 }))
+            .and_then(|run_info| run_info.validate()));
+        assert!(message.starts_with("port: "), "{}", message);
+
+        let message = error_message(Overrides::from_args(&args(&["--port", "eighty"])));
+        assert!(message.starts_with("--port: "), "{}", message);
+        let message = error_message(Overrides::from_args(&args(&["--porrt", "80"])));
+        assert!(message.contains("--porrt"), "{}", message);
+        let message = error_message(Overrides::default()
+            .or_env(|name| Some(name).filter(|name| name.ends_with("BIND")).map(str::to_string)));
+        assert!(message.starts_with("COMPLETION_TRACKER_BIND: "), "{}", message);
     }
 }
@@ -2,10 +2,13 @@ use rouille::{Response};
 
 static BUNDLE_JS: &'static [u8] = include_bytes!(concat!(env!("OUT_DIR"), "/bundle.js"));
 static MAIN_HTML: &'static str = include_str!(concat!(env!("OUT_DIR"), "/main.html"));
+static SITE_CSS: &'static str = include_str!(concat!(env!("OUT_DIR"), "/site.css"));
 
 /// Give a special debug implementation of the static server to make it cache-less
 pub struct StaticServer;
 impl StaticServer {
     pub fn serve_main_html(&self) -> Response { Response::html(MAIN_HTML) }
     pub fn serve_bundle_js(&self) -> Response { Response::from_data("text/javascript", BUNDLE_JS) }
+    /// The same CSS that the bundle adds, for the static site export
+    pub fn site_css(&self) -> &'static str { SITE_CSS }
 }
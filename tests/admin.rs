@@ -0,0 +1,164 @@
+//! Every test gets its own tracking folder, with the runInfo.json written into it.
+
+use std::{
+    fs,
+    path::{PathBuf},
+};
+
+use completion_tracker::{
+    admin,
+    run_info::{RunInfo},
+};
+use completion_tracker_lib::error::{Error};
+
+fn test_folder(test_name: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("completion_tracker_admin_{}_{}", std::process::id(), test_name))
+}
+
+/// Makes a new tracking folder with the runInfo.json and JSON files for the create command
+fn setup(test_name: &str) -> (PathBuf, RunInfo) {
+    let folder = test_folder(test_name);
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+
+    let info_file = folder.join("runInfo.json");
+    fs::write(&info_file, serde_json::json!({
+        "port": 8080,
+        "local_tracking_folder": folder.join("tracking"),
+        "trackers": [],
+    }).to_string()).unwrap();
+    fs::write(folder.join("tag.json"), serde_json::json!({
+        "names": { "English": "Spice and Wolf" },
+        "descriptions": {},
+        "related_links": [],
+        "parents": [],
+        "children": [],
+        "related_universe_tags": [],
+    }).to_string()).unwrap();
+    fs::write(folder.join("source.json"), serde_json::json!({
+        "names": { "English": "Spice and Wolf" },
+        "descriptions": {},
+        "source_type": "Novel",
+        "related_links": [],
+        "dates": [],
+        "universe_tags": [1],
+        "related_sources": [],
+        "people": [],
+        "characters": [],
+        "companies": [],
+    }).to_string()).unwrap();
+
+    (folder, RunInfo::new(info_file).unwrap())
+}
+
+/// Runs the command, giving back what it printed
+fn run(run_info: &RunInfo, args: &[&str]) -> Result<String, Error> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let mut out = Vec::new();
+    admin::run(run_info, &args, &mut out)?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn create_search_and_mark() {
+    let (folder, run_info) = setup("create");
+    let tag_file = folder.join("tag.json");
+    let source_file = folder.join("source.json");
+    assert_eq!(run(&run_info, &["create", "UniverseTag", tag_file.to_str().unwrap()]).unwrap(),
+        "1\n");
+    assert_eq!(run(&run_info, &["create", "Source", source_file.to_str().unwrap()]).unwrap(),
+        "1\n");
+
+    // Every command opens the DB files again, so this only works if they were saved
+    let results: serde_json::Value =
+        serde_json::from_str(&run(&run_info, &["search", "wolf", "--type", "Source"]).unwrap())
+        .unwrap();
+    assert_eq!(results[0]["id"], 1);
+    let source: serde_json::Value =
+        serde_json::from_str(&run(&run_info, &["show", "Source", "1"]).unwrap()).unwrap();
+    assert_eq!(source["names"]["English"], "Spice and Wolf");
+    assert_eq!(source["universe_tags"][0], 1);
+
+    run(&run_info, &["mark", "1", "Complete", "--date", "2020-01-02"]).unwrap();
+    let csv = run(&run_info, &["export", "csv"]).unwrap();
+    assert!(csv.contains("Spice and Wolf,Novel,Spice and Wolf,Complete,2020-01-02"));
+
+    assert!(matches!(run(&run_info, &["mark", "2", "Complete"]), Err(Error::NotFound(_))));
+    assert!(matches!(run(&run_info, &["show", "Person", "1"]), Err(Error::NotFound(_))));
+}
+
+#[test]
+fn check_and_back_up() {
+    let (folder, run_info) = setup("backup");
+    let source_file = folder.join("source.json");
+    let source_file = source_file.to_str().unwrap();
+    // The Universe Tag that it points to isn't there
+    assert!(matches!(run(&run_info, &["create", "Source", source_file]),
+        Err(Error::Conflict(_))));
+
+    let problems: serde_json::Value =
+        serde_json::from_str(&run(&run_info, &["check"]).unwrap()).unwrap();
+    assert_eq!(problems["sources"], serde_json::json!([]));
+
+    let backup_folder = folder.join("backup");
+    let backup_folder = backup_folder.to_str().unwrap();
+    run(&run_info, &["backup", backup_folder]).unwrap();
+    for file_name in &["sources.db", "trackers/Default Tracker.db", "sources.json"] {
+        assert!(folder.join("backup").join(file_name).is_file(), "{} is missing", file_name);
+    }
+    assert!(matches!(run(&run_info, &["backup", backup_folder]), Err(Error::Conflict(_))));
+}
+
+#[test]
+fn back_up_a_tracker_called_sources() {
+    let (folder, _) = setup("backup_sources");
+    let info_file = folder.join("runInfo.json");
+    fs::write(&info_file, serde_json::json!({
+        "port": 8080,
+        "local_tracking_folder": folder.join("tracking"),
+        "sources_db_file": folder.join("main.db"),
+        "trackers": [{ "tracker_name": "sources", "game_id": {} }],
+    }).to_string()).unwrap();
+    let run_info = RunInfo::new(info_file).unwrap();
+
+    let backup_folder = folder.join("backup");
+    run(&run_info, &["backup", backup_folder.to_str().unwrap()]).unwrap();
+    for file_name in &["sources.db", "trackers/sources.db", "sources.json"] {
+        assert!(backup_folder.join(file_name).is_file(), "{} is missing", file_name);
+    }
+}
+
+#[test]
+fn bad_arguments() {
+    let (_, run_info) = setup("arguments");
+    assert!(run(&run_info, &["help"]).unwrap().starts_with("Usage"));
+    assert!(matches!(run(&run_info, &[]), Err(Error::Validation(_))));
+    assert!(matches!(run(&run_info, &["nothing"]), Err(Error::Validation(_))));
+    assert!(matches!(run(&run_info, &["show", "Book", "1"]), Err(Error::Validation(_))));
+    assert!(matches!(run(&run_info, &["show", "Source"]), Err(Error::Validation(_))));
+    assert!(matches!(run(&run_info, &["check", "--extra", "1"]), Err(Error::Validation(_))));
+    assert!(matches!(run(&run_info, &["search", "wolf", "--type"]), Err(Error::Validation(_))));
+    assert!(matches!(run(&run_info, &["export", "anilist", "books"]),
+        Err(Error::Validation(_))));
+}
+
+#[test]
+fn bad_export_options_write_nothing() {
+    let (folder, run_info) = setup("export");
+    let out_file = folder.join("export.csv");
+    let out_file = out_file.to_str().unwrap();
+    assert!(matches!(run(&run_info, &["export", "csv", "--langg", "Japanese", "--out", out_file]),
+        Err(Error::Validation(_))));
+    assert!(!folder.join("export.csv").exists());
+
+    fs::write(out_file, "kept").unwrap();
+    assert!(matches!(run(&run_info, &["export", "backup", "--out", out_file, "extra"]),
+        Err(Error::Validation(_))));
+    assert_eq!(fs::read_to_string(out_file).unwrap(), "kept");
+
+    assert_eq!(run(&run_info, &["export", "csv", "--out", out_file]).unwrap(), "");
+    assert_ne!(fs::read_to_string(out_file).unwrap(), "kept");
+    assert!(matches!(run(&run_info, &["export", "site", "--out", out_file]),
+        Err(Error::Validation(_))));
+}
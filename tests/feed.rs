@@ -0,0 +1,100 @@
+//! The feed goes through the whole server, since the caching is in the headers.
+
+use std::{fs};
+
+use rouille::{Request, Response};
+
+use completion_tracker::{App};
+use completion_tracker_lib::{
+    sources::{
+        api::source::{self, SourceCreator},
+        source_types::{Lang, LangMap, SourceType},
+    },
+    tracking::{
+        CompletionStatus, Tracker,
+        entry::{self, StatusUpdate},
+    },
+};
+
+/// The tracker that it makes when there aren't any in the runInfo.json
+const TRACKER_URL: &str = "/api/tracker/Default%20Tracker/feed.atom";
+
+/// A server with a single Source that's been started
+fn make_app(test_name: &str) -> App {
+    let folder = std::env::temp_dir()
+        .join(format!("completion_tracker_feed_{}_{}", std::process::id(), test_name));
+    let _ = fs::remove_dir_all(&folder);
+    let trackers = Tracker::new_from_info(&folder, &[]).unwrap();
+    let tracker_dbs = completion_tracker_lib::init_tracker_dbs(&trackers).unwrap();
+    let sources_db = completion_tracker_lib::init_source_db().unwrap();
+
+    let mut names = LangMap::new();
+    names.insert(Lang::English, "Spice and Wolf".to_string());
+    let source_id = source::create(&sources_db, SourceCreator {
+        names,
+        descriptions: LangMap::new(),
+        source_type: SourceType::Novel,
+        related_links: Vec::new(),
+        dates: Vec::new(),
+        universe_tags: Vec::new(),
+        related_sources: Vec::new(),
+        people: Vec::new(),
+        characters: Vec::new(),
+        companies: Vec::new(),
+    }).unwrap();
+    entry::update_status(tracker_dbs.values().next().unwrap(), StatusUpdate {
+        source_id,
+        status: CompletionStatus::InProgress,
+        date: None,
+        play_time: None,
+    }).unwrap();
+
+    App::new(sources_db, tracker_dbs, folder.join("site"))
+}
+
+fn get_feed(app: &App, if_modified_since: Option<&str>) -> Response {
+    let headers = if_modified_since
+        .map(|since| vec![("If-Modified-Since".to_string(), since.to_string())])
+        .unwrap_or_default();
+    app.handle(&Request::fake_http("GET", TRACKER_URL, headers, Vec::new()))
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response.headers.iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_ref())
+}
+
+fn body(response: Response) -> String {
+    let (mut reader, _) = response.data.into_reader_and_size();
+    let mut body = String::new();
+    reader.read_to_string(&mut body).unwrap();
+    body
+}
+
+#[test]
+fn unchanged_feeds_are_not_sent_again() {
+    let app = make_app("unchanged");
+    let response = get_feed(&app, None);
+    assert_eq!(response.status_code, 200);
+    let last_modified = header(&response, "Last-Modified").unwrap().to_string();
+    assert!(body(response).contains("<title>InProgress: Spice and Wolf</title>"));
+
+    let response = get_feed(&app, Some(&last_modified));
+    assert_eq!(response.status_code, 304);
+    assert_eq!(header(&response, "Last-Modified"), Some(last_modified.as_str()));
+    assert_eq!(header(&response, "Content-Type"), None);
+    assert_eq!(body(response), "");
+}
+
+#[test]
+fn changed_feeds_are_sent() {
+    let app = make_app("changed");
+    let response = get_feed(&app, Some("Tue, 01 Jan 2008 00:00:00 GMT"));
+    assert_eq!(response.status_code, 200);
+    assert!(body(response).contains("<title>InProgress: Spice and Wolf</title>"));
+
+    // A date that can't be read is the same as not having one
+    let response = get_feed(&app, Some("yesterday"));
+    assert_eq!(response.status_code, 200);
+}